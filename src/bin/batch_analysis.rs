@@ -0,0 +1,40 @@
+// Classifies a directory of screenshots offline, e.g. to tune detector thresholds:
+//
+//     cargo run --bin batch_analysis -- <screenshot-dir> [pokemon|mario]
+use std::path::PathBuf;
+
+use pokebot_rust::error::AppError;
+use pokebot_rust::pipeline::detection::color_thresholds::ColorThresholds;
+use pokebot_rust::pipeline::detection::game_registry::GameKind;
+use pokebot_rust::pipeline::orchestration::batch_analysis::analyze_directory;
+use pokebot_rust::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+#[tokio::main]
+async fn main() -> Result<(), AppError> {
+    tracing_subscriber::fmt().init();
+
+    let mut args = std::env::args().skip(1);
+    let Some(directory) = args.next().map(PathBuf::from) else {
+        eprintln!("usage: batch_analysis <screenshot-dir> [pokemon|mario]");
+        std::process::exit(2);
+    };
+    let game_kind = match args.next().as_deref() {
+        Some("mario") => GameKind::Mario,
+        _ => GameKind::Pokemon,
+    };
+
+    let analyzer = SceneAnalyzer::for_game(game_kind, ColorThresholds::default());
+    let results = analyze_directory(&directory, &analyzer).await?;
+    println!("{:<40} {:<12} {:>10}", "file", "scene", "confidence");
+    for (path, analysis) in &results {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!(
+            "{:<40} {:<12} {:>10.2}",
+            name,
+            format!("{:?}", analysis.scene_type()),
+            analysis.confidence()
+        );
+    }
+    println!("{} images analyzed", results.len());
+    Ok(())
+}
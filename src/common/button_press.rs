@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::button_set::ButtonSet;
+use crate::common::game_action::GameAction;
+
+// Buttons sent to the emulator, either tapped for a single cycle or held down for a duration.
+// Holding avoids the jitter of re-sending taps every tick, e.g. when walking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ButtonPress {
+    buttons: ButtonSet,
+    hold: Option<Duration>,
+}
+
+impl ButtonPress {
+    // Size of the encoding produced by `to_wire_bytes`.
+    pub const WIRE_LEN: usize = 6;
+
+    pub fn tap(buttons: impl Into<ButtonSet>) -> Self {
+        Self {
+            buttons: buttons.into(),
+            hold: None,
+        }
+    }
+
+    pub fn hold(buttons: impl Into<ButtonSet>, duration: Duration) -> Self {
+        Self {
+            buttons: buttons.into(),
+            hold: Some(duration),
+        }
+    }
+
+    // Presses nothing for the duration, e.g. to let an animation finish. Any held keys are
+    // released and the emulator applies no other action until the wait is over.
+    pub fn wait(duration: Duration) -> Self {
+        Self::hold(ButtonSet::empty(), duration)
+    }
+
+    // Same timing with different buttons.
+    pub fn with_buttons(self, buttons: ButtonSet) -> Self {
+        Self { buttons, ..self }
+    }
+
+    pub fn is_wait(&self) -> bool {
+        self.buttons.is_empty()
+    }
+
+    pub fn buttons(&self) -> ButtonSet {
+        self.buttons
+    }
+
+    // How long the buttons stay pressed, `None` for a single-cycle tap.
+    pub fn hold_duration(&self) -> Option<Duration> {
+        self.hold
+    }
+
+    // Input word followed by the hold duration in milliseconds, both little endian.
+    // A zero duration encodes a tap.
+    pub fn to_wire_bytes(self) -> [u8; Self::WIRE_LEN] {
+        let hold_ms = self
+            .hold
+            .map_or(0, |hold| hold.as_millis().clamp(1, u32::MAX as u128) as u32);
+        let mut bytes = [0u8; Self::WIRE_LEN];
+        bytes[..2].copy_from_slice(&self.buttons.to_wire_bits().to_le_bytes());
+        bytes[2..].copy_from_slice(&hold_ms.to_le_bytes());
+        bytes
+    }
+
+    // Decodes bytes produced by `to_wire_bytes`, returns `None` if unknown button bits are set.
+    pub fn from_wire_bytes(bytes: [u8; Self::WIRE_LEN]) -> Option<Self> {
+        let buttons = ButtonSet::from_wire_bits(u16::from_le_bytes([bytes[0], bytes[1]]))?;
+        let hold_ms = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+        Some(Self {
+            buttons,
+            hold: (hold_ms > 0).then(|| Duration::from_millis(hold_ms as u64)),
+        })
+    }
+}
+
+impl From<ButtonSet> for ButtonPress {
+    fn from(buttons: ButtonSet) -> Self {
+        Self::tap(buttons)
+    }
+}
+
+impl From<GameAction> for ButtonPress {
+    fn from(action: GameAction) -> Self {
+        Self::tap(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hold_duration_round_trips_through_the_wire() {
+        let walk = ButtonPress::hold(GameAction::Up, Duration::from_millis(250));
+        let bytes = walk.to_wire_bytes();
+        assert_eq!(
+            u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+            250
+        );
+
+        let decoded = ButtonPress::from_wire_bytes(bytes).unwrap();
+        assert_eq!(decoded, walk);
+        assert_eq!(decoded.hold_duration(), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn taps_encode_a_zero_duration() {
+        let tap = ButtonPress::from(GameAction::A);
+        assert_eq!(tap.to_wire_bytes()[2..], [0, 0, 0, 0]);
+        assert_eq!(ButtonPress::from_wire_bytes(tap.to_wire_bytes()), Some(tap));
+    }
+
+    #[test]
+    fn waits_press_nothing_for_their_duration() {
+        let wait = ButtonPress::wait(Duration::from_millis(400));
+        assert!(wait.is_wait());
+        assert!(!ButtonPress::from(GameAction::A).is_wait());
+
+        let decoded = ButtonPress::from_wire_bytes(wait.to_wire_bytes()).unwrap();
+        assert!(decoded.is_wait());
+        assert_eq!(decoded.hold_duration(), Some(Duration::from_millis(400)));
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::game_action::GameAction;
+
+// A set of buttons pressed together on the same frame, e.g. Up+B to run.
+// Each button occupies the bit given by its `GameAction::to_wire_byte`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct ButtonSet(u16);
+
+impl ButtonSet {
+    const VALID_BITS: u16 = (1 << GameAction::ALL.len()) - 1;
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn with(mut self, action: GameAction) -> Self {
+        self.insert(action);
+        self
+    }
+
+    pub fn insert(&mut self, action: GameAction) {
+        self.0 |= Self::bit(action);
+    }
+
+    // The buttons of this set that are not in `other`.
+    pub fn without(self, other: ButtonSet) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    pub fn contains(&self, action: GameAction) -> bool {
+        self.0 & Self::bit(action) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = GameAction> + '_ {
+        GameAction::ALL
+            .into_iter()
+            .filter(|action| self.contains(*action))
+    }
+
+    // Packs all pressed buttons into a single input word for the wire.
+    pub fn to_wire_bits(self) -> u16 {
+        self.0
+    }
+
+    // Decodes an input word produced by `to_wire_bits`, returns `None` if unknown bits are set.
+    pub fn from_wire_bits(bits: u16) -> Option<Self> {
+        (bits & !Self::VALID_BITS == 0).then_some(Self(bits))
+    }
+
+    fn bit(action: GameAction) -> u16 {
+        1 << action.to_wire_byte()
+    }
+}
+
+impl From<GameAction> for ButtonSet {
+    fn from(action: GameAction) -> Self {
+        Self::empty().with(action)
+    }
+}
+
+impl FromIterator<GameAction> for ButtonSet {
+    fn from_iter<I: IntoIterator<Item = GameAction>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::empty(), ButtonSet::with)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_and_b_round_trip_through_wire_bits() {
+        let running = ButtonSet::from(GameAction::Up).with(GameAction::B);
+        let decoded = ButtonSet::from_wire_bits(running.to_wire_bits()).unwrap();
+        assert_eq!(decoded, running);
+        assert_eq!(
+            decoded.iter().collect::<Vec<_>>(),
+            vec![GameAction::B, GameAction::Up]
+        );
+    }
+
+    #[test]
+    fn single_button_set_contains_only_that_action() {
+        for action in GameAction::ALL {
+            let set = ButtonSet::from(action);
+            assert_eq!(set.len(), 1);
+            assert_eq!(set.iter().next(), Some(action));
+        }
+    }
+
+    #[test]
+    fn unknown_wire_bits_are_rejected() {
+        assert_eq!(ButtonSet::from_wire_bits(1 << 15), None);
+    }
+}
@@ -0,0 +1,7 @@
+// Values read straight from the game's RAM by emulators or mods that expose them alongside
+// the frame. Every field is optional, whatever is missing is still estimated from the pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EmulatorState {
+    pub badges: Option<u8>,
+    pub party_size: Option<u8>,
+}
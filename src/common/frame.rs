@@ -3,12 +3,15 @@ use image::DynamicImage;
 use std::sync::Arc;
 use uuid::Uuid;
 
+use crate::common::EmulatorState;
+
 #[derive(Clone)]
 pub struct Frame {
     client_id: Uuid,
     image: Arc<DynamicImage>,
     captured_at: DateTime<Utc>,
     frame_id: Uuid,
+    emulator_state: Option<EmulatorState>,
 }
 
 impl Frame {
@@ -23,12 +26,43 @@ impl Frame {
             image: Arc::new(image),
             captured_at,
             frame_id,
+            emulator_state: None,
+        }
+    }
+
+    // Same frame with a different image, e.g. after preprocessing.
+    pub fn with_image(&self, image: DynamicImage) -> Self {
+        Self {
+            image: Arc::new(image),
+            ..self.clone()
         }
     }
 
+    // Attaches the game state the emulator reported for this frame, if it reports any.
+    pub fn with_emulator_state(mut self, state: EmulatorState) -> Self {
+        self.emulator_state = Some(state);
+        self
+    }
+
     pub fn get_client_id(&self) -> Uuid {
         self.client_id
     }
+
+    pub fn get_image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    pub fn get_captured_at(&self) -> DateTime<Utc> {
+        self.captured_at
+    }
+
+    pub fn get_frame_id(&self) -> Uuid {
+        self.frame_id
+    }
+
+    pub fn get_emulator_state(&self) -> Option<EmulatorState> {
+        self.emulator_state
+    }
 }
 
 #[cfg(test)]
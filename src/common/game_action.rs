@@ -18,20 +18,64 @@ pub enum GameAction {
     X = 10,
 }
 
+impl GameAction {
+    pub const ALL: [GameAction; 11] = [
+        GameAction::A,
+        GameAction::B,
+        GameAction::Up,
+        GameAction::Down,
+        GameAction::Left,
+        GameAction::Right,
+        GameAction::Start,
+        GameAction::Select,
+        GameAction::L,
+        GameAction::R,
+        GameAction::X,
+    ];
+
+    // Canonical single-byte encoding of the action, shared with emulator bridges.
+    // The byte is the enum discriminant, so it is stable as long as the variants keep their values.
+    pub fn to_wire_byte(self) -> u8 {
+        self as u8
+    }
+
+    // Decodes a byte produced by `to_wire_byte`, returns `None` for unknown values.
+    pub fn from_wire_byte(byte: u8) -> Option<GameAction> {
+        Self::ALL.get(byte as usize).copied()
+    }
+}
+
 impl Distribution<GameAction> for StandardUniform {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> GameAction {
-        match rng.random_range(0..=10) {
-            0 => GameAction::A,
-            1 => GameAction::B,
-            2 => GameAction::Up,
-            3 => GameAction::Down,
-            4 => GameAction::Left,
-            5 => GameAction::Right,
-            6 => GameAction::Start,
-            7 => GameAction::Select,
-            8 => GameAction::L,
-            9 => GameAction::R,
-            _ => GameAction::X,
+        GameAction::ALL[rng.random_range(0..GameAction::ALL.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wire_byte_round_trips_every_action() {
+        for action in GameAction::ALL {
+            assert_eq!(
+                GameAction::from_wire_byte(action.to_wire_byte()),
+                Some(action)
+            );
+        }
+    }
+
+    #[test]
+    fn wire_byte_matches_discriminant() {
+        for (index, action) in GameAction::ALL.iter().enumerate() {
+            assert_eq!(action.to_wire_byte() as usize, index);
+        }
+    }
+
+    #[test]
+    fn unknown_wire_bytes_are_rejected() {
+        for byte in GameAction::ALL.len() as u8..=u8::MAX {
+            assert_eq!(GameAction::from_wire_byte(byte), None);
         }
     }
 }
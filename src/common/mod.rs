@@ -1,5 +1,11 @@
+pub mod button_press;
+pub mod button_set;
+pub mod emulator_state;
 pub mod frame;
 pub mod game_action;
 
+pub use button_press::ButtonPress;
+pub use button_set::ButtonSet;
+pub use emulator_state::EmulatorState;
 pub use frame::Frame;
 pub use game_action::GameAction;
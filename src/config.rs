@@ -1,10 +1,142 @@
-use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 
+use serde::{Deserialize, Serialize};
+
+use crate::common::GameAction;
+use crate::emulator::restart_backoff::RestartPolicy;
+use crate::error::AppError;
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::domain::scene_analysis::{BattleMenuSelection, SceneType};
+use crate::pipeline::orchestration::checkpoint_manager::CheckpointPolicy;
+use crate::pipeline::orchestration::frame_drop_policy::FrameDropPolicy;
+use crate::pipeline::orchestration::service::preprocess::PaletteRemap;
+use crate::pipeline::orchestration::step::scene_analyzer::AnalyzerSettings;
+
+// Missing keys in a configuration file fall back to the defaults below.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Configuration {
     pub rom_path: String,
     pub frame_buffer_size: usize,
     pub action_buffer_size: usize,
     pub enable_metrics: bool,
+    // Minimum time between two actions applied to the emulator, faster actions are held back.
+    pub min_action_interval_ms: u64,
+    pub frame_drop_policy: FrameDropPolicy,
+    // Frames smaller than [width, height] are skipped instead of analyzed, e.g. while the
+    // emulator window is still being created.
+    pub min_frame_size: (u32, u32),
+    // When set, a JSON snapshot of the pipeline stats is streamed to every client connected to this address.
+    pub metrics_export_addr: Option<SocketAddr>,
+    pub metrics_export_interval_ms: u64,
+    // When set, clients whose scene and image stay unchanged this long get escalating inputs.
+    pub stuck_timeout_ms: Option<u64>,
+    // Side of the thumbnail the stuck watchdog compares frames on, e.g. 8, 32 or 64. Higher
+    // values catch small changes like a cursor moving but cost more per frame.
+    pub stuck_signature_resolution: u32,
+    // When set with stuck_timeout_ms, the emulator is reset after this many soft resets failed.
+    pub emulator_reset_after_soft_resets: Option<u32>,
+    // Presses Start when a client's scenes keep cycling through the title screen like a demo loop.
+    pub exit_attract_mode: bool,
+    // When set, battle turns are answered by moving the menu cursor to this entry and selecting it.
+    pub battle_menu_target: Option<BattleMenuSelection>,
+    // Declines move-learn prompts and waits out evolutions instead of leaving them to the watchdog.
+    pub advance_prompts: bool,
+    // Backs out of the bag, party, trainer card and other menus with B, since the bot has no
+    // plan for them. Shops are left the same way rather than risk buying.
+    pub leave_menus: bool,
+    // Buttons stripped from every input sent while a client is in the given scene.
+    pub forbidden_actions: HashMap<SceneType, Vec<GameAction>>,
+    // When set, save states are taken on progress and rewound to when a client gets stuck or
+    // whites out.
+    pub checkpoints: Option<CheckpointPolicy>,
+    // How the emulator is restarted when it fails to start or stops unexpectedly.
+    pub emulator_restart: RestartPolicy,
+    // When set, every input applied to the emulator is logged and written here as JSON on shutdown.
+    pub input_log_path: Option<PathBuf>,
+    // When set, every frame entering the pipeline is written here as a PNG with JSON metadata,
+    // for replaying with FrameReplayer.
+    pub record_frames_dir: Option<PathBuf>,
+    // When set, a heatmap of where each frame changed is written here as a PNG, for tuning the
+    // change thresholds.
+    pub change_heatmap_dir: Option<PathBuf>,
+    // When set, this input log is replayed against the freshly started emulator. The bot sends
+    // no inputs of its own until the replay is over.
+    pub replay_input_log: Option<PathBuf>,
+    // When set, frames are mapped back to the game's palette before analysis, for shaders and
+    // filters that shift colors.
+    pub palette_remap: Option<PaletteRemap>,
+    // Confidence threshold and detector selection for the scene analyzer.
+    pub analyzer: AnalyzerSettings,
+    // Luma and saturation cut-offs every detector classifies pixels with, for palettes that
+    // differ from the game's defaults.
+    pub color_thresholds: ColorThresholds,
+}
+
+impl Configuration {
+    // Loads a configuration file (TOML, YAML, JSON, ... picked by extension) and validates it.
+    pub fn from_file(path: &Path) -> Result<Self, AppError> {
+        let configuration: Self = config::Config::builder()
+            .add_source(config::File::from(path))
+            .build()
+            .and_then(|config| config.try_deserialize())
+            .map_err(|e| {
+                AppError::Config(format!(
+                    "Failed to load configuration from {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        configuration.validate()?;
+        Ok(configuration)
+    }
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if self.rom_path.is_empty() {
+            return Err(AppError::Config("rom_path is not set".to_string()));
+        }
+        if !Path::new(&self.rom_path).is_file() {
+            return Err(AppError::Config(format!(
+                "ROM not found at '{}'",
+                self.rom_path
+            )));
+        }
+        if self.frame_buffer_size == 0 || self.action_buffer_size == 0 {
+            return Err(AppError::Config(
+                "Frame and action buffer sizes must be greater than zero".to_string(),
+            ));
+        }
+        if self.metrics_export_interval_ms == 0 {
+            return Err(AppError::Config(
+                "Metrics export interval must be greater than zero".to_string(),
+            ));
+        }
+        if self.stuck_signature_resolution == 0 {
+            return Err(AppError::Config(
+                "Stuck signature resolution must be greater than zero".to_string(),
+            ));
+        }
+        if let Some(PaletteRemap::Matrix(rows)) = &self.palette_remap
+            && !rows.iter().flatten().all(|weight| weight.is_finite())
+        {
+            return Err(AppError::Config(
+                "Palette remap matrix weights must be finite numbers".to_string(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.analyzer.confidence_threshold) {
+            return Err(AppError::Config(
+                "Analyzer confidence threshold must be between 0 and 1".to_string(),
+            ));
+        }
+        if let FrameDropPolicy::EveryNth(0) = self.frame_drop_policy {
+            return Err(AppError::Config(
+                "EveryNth frame drop policy needs N greater than zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 impl Default for Configuration {
@@ -14,6 +146,198 @@ impl Default for Configuration {
             frame_buffer_size: 60,
             action_buffer_size: 10,
             enable_metrics: false,
+            min_action_interval_ms: 16,
+            frame_drop_policy: FrameDropPolicy::default(),
+            min_frame_size: (32, 32),
+            metrics_export_addr: None,
+            metrics_export_interval_ms: 1000,
+            stuck_timeout_ms: None,
+            stuck_signature_resolution: 8,
+            emulator_reset_after_soft_resets: None,
+            exit_attract_mode: false,
+            battle_menu_target: None,
+            advance_prompts: false,
+            leave_menus: false,
+            forbidden_actions: HashMap::new(),
+            checkpoints: None,
+            emulator_restart: RestartPolicy::default(),
+            input_log_path: None,
+            record_frames_dir: None,
+            change_heatmap_dir: None,
+            replay_input_log: None,
+            palette_remap: None,
+            analyzer: AnalyzerSettings::default(),
+            color_thresholds: ColorThresholds::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::pipeline::orchestration::step::scene_analyzer::SmoothingSettings;
+
+    // Writes the configuration next to an empty ROM it points at, unless it sets its own rom_path.
+    fn write_config(contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("pokebot-config-{}.toml", Uuid::new_v4()));
+        let rom = path.with_extension("nds");
+        fs::write(&rom, []).unwrap();
+        if contents.contains("rom_path") {
+            fs::write(&path, contents).unwrap();
+        } else {
+            fs::write(
+                &path,
+                format!("rom_path = '{}'\n{}", rom.display(), contents),
+            )
+            .unwrap();
+        }
+        path
+    }
+
+    fn remove_config(path: std::path::PathBuf) {
+        fs::remove_file(path.with_extension("nds")).unwrap();
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn loads_a_toml_file_over_the_defaults() {
+        let path = write_config(
+            r#"
+            enable_metrics = true
+            metrics_export_addr = "127.0.0.1:9100"
+            stuck_timeout_ms = 15000
+            frame_drop_policy = { every_nth = 3 }
+            min_frame_size = [256, 192]
+
+            [forbidden_actions]
+            bag = ["Start", "Select"]
+            "#,
+        );
+
+        let configuration = Configuration::from_file(&path).unwrap();
+        assert!(configuration.rom_path.ends_with(".nds"));
+        assert!(configuration.enable_metrics);
+        assert_eq!(
+            configuration.metrics_export_addr,
+            Some("127.0.0.1:9100".parse().unwrap())
+        );
+        assert_eq!(configuration.stuck_timeout_ms, Some(15000));
+        assert_eq!(
+            configuration.frame_drop_policy,
+            FrameDropPolicy::EveryNth(3)
+        );
+        assert_eq!(configuration.min_frame_size, (256, 192));
+        assert_eq!(configuration.frame_buffer_size, 60);
+        assert_eq!(
+            configuration.forbidden_actions[&SceneType::Bag],
+            vec![GameAction::Start, GameAction::Select]
+        );
+
+        remove_config(path);
+    }
+
+    #[test]
+    fn palette_remaps_load_as_matrices_or_lookups() {
+        let path = write_config("palette_remap = { matrix = [[0, 0, 1], [1, 0, 0], [0, 1, 0]] }");
+        assert_eq!(
+            Configuration::from_file(&path).unwrap().palette_remap,
+            Some(PaletteRemap::Matrix([
+                [0.0, 0.0, 1.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0]
+            ]))
+        );
+        remove_config(path);
+
+        let path = write_config(
+            r#"
+            [palette_remap.lookup]
+            colors = [[[200, 88, 96], [96, 200, 88]]]
+            tolerance = 4
+            "#,
+        );
+        assert_eq!(
+            Configuration::from_file(&path).unwrap().palette_remap,
+            Some(PaletteRemap::Lookup {
+                colors: vec![([200, 88, 96], [96, 200, 88])],
+                tolerance: 4,
+            })
+        );
+        remove_config(path);
+    }
+
+    #[test]
+    fn loads_analyzer_settings() {
+        let path = write_config(
+            r#"
+            [analyzer]
+            confidence_threshold = 0.6
+            enabled_detectors = ["cave", "trainer_card"]
+            smoothing = { window = 5, switch_after = 3 }
+            "#,
+        );
+        let analyzer = Configuration::from_file(&path).unwrap().analyzer;
+        assert_eq!(analyzer.confidence_threshold, 0.6);
+        assert_eq!(
+            analyzer.enabled_detectors,
+            Some(vec!["cave".to_string(), "trainer_card".to_string()])
+        );
+        assert_eq!(
+            analyzer.smoothing,
+            Some(SmoothingSettings {
+                window: 5,
+                switch_after: 3
+            })
+        );
+        remove_config(path);
+    }
+
+    #[test]
+    fn loads_color_thresholds_over_the_defaults() {
+        let path = write_config(
+            r#"
+            [color_thresholds]
+            rock_luma = 40
+            gray_luma = [50, 210]
+            "#,
+        );
+        assert_eq!(
+            Configuration::from_file(&path).unwrap().color_thresholds,
+            ColorThresholds {
+                rock_luma: 40,
+                gray_luma: (50, 210),
+                ..ColorThresholds::default()
+            }
+        );
+        remove_config(path);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for contents in [
+            "action_buffer_size = 0",
+            "[analyzer]\nconfidence_threshold = 1.5",
+        ] {
+            let path = write_config(contents);
+            assert!(matches!(
+                Configuration::from_file(&path),
+                Err(AppError::Config(_))
+            ));
+            remove_config(path);
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_rom() {
+        let path = write_config("rom_path = 'roms/missing.nds'");
+        assert!(matches!(
+            Configuration::from_file(&path),
+            Err(AppError::Config(_))
+        ));
+        remove_config(path);
+    }
+}
@@ -1,37 +1,155 @@
 use crate::{
-    common::{frame::Frame, game_action::GameAction},
+    common::{ButtonPress, ButtonSet, frame::Frame, game_action::GameAction},
     config::Configuration,
-    emulator::emulator_client::EmulatorClient,
+    emulator::{
+        emulator_client::{EmulatorClient, EmulatorControl, SaveStateCommand},
+        input_log::{InputRecorder, InputReplayer},
+    },
     error::AppError,
-    pipeline::orchestration::processing_pipeline::ProcessingPipeline,
+    pipeline::{
+        context::{frame_context::FrameContext, metrics::PerformanceStats, state::AnalyzedState},
+        domain::scene_analysis::{BattleMenuSelection, SceneAnalysis, SceneType},
+        orchestration::{
+            action_mask::{ActionMask, MaskedActionSender},
+            attract_mode::AttractModeWatch,
+            checkpoint_manager::CheckpointManager,
+            dialog_print::DialogPrintWatch,
+            effect_latency::EffectLatencyEstimator,
+            exploration_map::ExplorationMap,
+            frame_decimator::{FrameDecimation, FrameDecimator},
+            frame_drop_policy::{FrameDropPolicy, FrameDropper},
+            menu_navigator::MenuNavigator,
+            processing_pipeline::ProcessingPipeline,
+            stuck_watchdog::StuckWatchdog,
+        },
+    },
 };
-use tokio::sync::mpsc::Receiver;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{Receiver, Sender};
 use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+// How long shutdown waits for tasks to wind down on their own before aborting them.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+// Change signal telling animations apart from still frames, see FrameDecimation::Hysteresis.
+const ANIMATING: FrameDecimation = FrameDecimation::Hysteresis {
+    rising: 0.04,
+    falling: 0.01,
+};
+// Wait sent in place of a press while the client's frames are changing.
+const ANIMATION_WAIT: Duration = Duration::from_millis(100);
+// Longest a press is held back for an animation, endless ones like a title demo still get it.
+const MAX_ANIMATION_WAIT: Duration = Duration::from_secs(3);
+// How long a walk is held until a client's effect latency has been measured, 8 frames at 60fps.
+const WALK_HOLD: Duration = Duration::from_millis(133);
+// Time a menu gets to react to a plan before the next one is made from what it shows.
+const PLAN_COOLDOWN: Duration = Duration::from_secs(1);
+// Pixels per tile of the change heatmaps written for debugging.
+const HEATMAP_TILE_SIZE: u32 = 16;
+// Change a tile needs before a heatmap is written, so idle flicker doesn't flood the directory.
+const HEATMAP_MIN_HEAT: f32 = 0.25;
 
 pub struct Coordinator {
-    pipeline_task: tokio::task::JoinHandle<()>,
+    pipeline_task: Option<tokio::task::JoinHandle<()>>,
+    metrics_task: Option<tokio::task::JoinHandle<()>>,
+    replay_task: Option<tokio::task::JoinHandle<()>>,
+    action_tx: Option<Sender<ButtonPress>>,
+    input_log: Option<(InputRecorder, PathBuf)>,
     cancel_token: CancellationToken,
 }
 
 impl Coordinator {
-    fn new(configuration: Configuration, pipeline: ProcessingPipeline) -> Self {
+    fn new(
+        configuration: Configuration,
+        mut pipeline: ProcessingPipeline,
+    ) -> Result<Self, AppError> {
+        let replayer = configuration
+            .replay_input_log
+            .as_deref()
+            .map(InputReplayer::open)
+            .transpose()?;
         let cancel_token = CancellationToken::new();
+        if configuration.enable_metrics {
+            pipeline.enable_metrics = true;
+        }
+        let metrics_task = configuration.metrics_export_addr.map(|addr| {
+            Self::start_metrics_export_task(
+                addr,
+                Duration::from_millis(configuration.metrics_export_interval_ms),
+                pipeline.stats(),
+                cancel_token.clone(),
+            )
+        });
+        let (action_tx, action_rx) = tokio::sync::mpsc::channel(configuration.action_buffer_size);
+        let input_log = configuration
+            .input_log_path
+            .clone()
+            .map(|path| (InputRecorder::new(), path));
+        let replaying = replayer.as_ref().map(|_| Arc::new(AtomicBool::new(true)));
+        let replay_task = replayer
+            .zip(replaying.clone())
+            .map(|(replayer, replaying)| {
+                Self::start_replay_task(
+                    replayer,
+                    action_tx.clone(),
+                    replaying,
+                    cancel_token.clone(),
+                )
+            });
 
-        Self {
-            pipeline_task: Self::start_tasks(configuration, pipeline, cancel_token.clone()),
+        Ok(Self {
+            pipeline_task: Some(Self::start_tasks(
+                configuration,
+                pipeline,
+                (action_tx.clone(), action_rx),
+                input_log.as_ref().map(|(recorder, _)| recorder.clone()),
+                replaying,
+                cancel_token.clone(),
+            )),
+            metrics_task,
+            replay_task,
+            action_tx: Some(action_tx),
+            input_log,
             cancel_token,
-        }
+        })
     }
 
     fn start_tasks(
         configuration: Configuration,
         pipeline: ProcessingPipeline,
+        (action_tx, action_rx): (Sender<ButtonPress>, Receiver<ButtonPress>),
+        input_recorder: Option<InputRecorder>,
+        replaying: Option<Arc<AtomicBool>>,
         cancel_token: CancellationToken,
     ) -> tokio::task::JoinHandle<()> {
+        let control = EmulatorControl::new();
+        let mut reactions = FrameReactions::from_config(&configuration, action_tx, control.clone());
+        reactions.replaying = replaying;
         let (frame_tx, frame_rx) = tokio::sync::mpsc::channel(configuration.frame_buffer_size);
-        let (_action_tx, action_rx) = tokio::sync::mpsc::channel(configuration.action_buffer_size);
-        let mut client = EmulatorClient::new(action_rx, frame_tx, configuration.rom_path.clone());
-        let pipeline_task = Self::start_pipeline_task(pipeline, frame_rx, cancel_token.clone());
+        let mut client = EmulatorClient::new(
+            action_rx,
+            frame_tx,
+            configuration.rom_path.clone(),
+            Duration::from_millis(configuration.min_action_interval_ms),
+            configuration.emulator_restart,
+            input_recorder,
+            control,
+        );
+        let pipeline_task = Self::start_pipeline_task(
+            pipeline,
+            frame_rx,
+            configuration.frame_drop_policy,
+            reactions,
+            cancel_token.clone(),
+        );
         let handler_task = tokio::spawn(async move {
             loop {
                 tokio::select! {
@@ -49,26 +167,182 @@ impl Coordinator {
     fn start_pipeline_task(
         mut pipeline: ProcessingPipeline,
         mut frame_rx: Receiver<Frame>,
+        frame_drop_policy: FrameDropPolicy,
+        mut reactions: FrameReactions,
         cancel_token: CancellationToken,
     ) -> tokio::task::JoinHandle<()> {
         let pipeline_task = tokio::spawn(async move {
-            while let Some(frame) = frame_rx.recv().await
-                && !cancel_token.is_cancelled()
-            {
-                let response = pipeline.process(frame).await;
-                if let Err(e) = response {
-                    tracing::error!("Pipeline error: {}", e);
-                } else {
-                    tracing::info!("Pipeline got response.");
+            let mut dropper = FrameDropper::new(frame_drop_policy);
+            let stats = pipeline.stats();
+            // A frame that arrived while the previous one was being processed and cut it short.
+            let mut preempting = None;
+            loop {
+                let frame = match preempting.take() {
+                    Some(frame) => frame,
+                    None => match frame_rx.recv().await {
+                        Some(frame) => frame,
+                        None => break,
+                    },
+                };
+                if cancel_token.is_cancelled() {
+                    break;
+                }
+                let avg_frame_us = stats
+                    .lock()
+                    .map(|stats| stats.total_frame().ewma_us())
+                    .unwrap_or_default();
+                let Some(frame) = dropper.select(frame, &mut frame_rx, avg_frame_us) else {
+                    continue;
+                };
+                let frame_cancel = cancel_token.child_token();
+                let process = pipeline.process_until(frame, &frame_cancel);
+                tokio::pin!(process);
+                let response = loop {
+                    tokio::select! {
+                        response = &mut process => break response,
+                        Some(newer) = frame_rx.recv(),
+                            if dropper.preempts_stale() && preempting.is_none() =>
+                        {
+                            preempting = Some(newer);
+                            frame_cancel.cancel();
+                        }
+                    }
+                };
+                match response {
+                    Err(AppError::FrameSkipped(reason)) => tracing::debug!("{}", reason),
+                    Err(e) => tracing::error!("Pipeline error: {}", e),
+                    Ok(response) => {
+                        tracing::info!("Pipeline got response.");
+                        reactions.react(&response, Instant::now());
+                    }
                 }
             }
         });
         pipeline_task
     }
 
+    // Replays the log through the emulator's action channel, then lets the bot take over.
+    fn start_replay_task(
+        replayer: InputReplayer,
+        action_tx: Sender<ButtonPress>,
+        replaying: Arc<AtomicBool>,
+        cancel_token: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {}
+                replayed = replayer.replay(&action_tx) => match replayed {
+                    Ok(count) => tracing::info!("Replayed {} logged inputs", count),
+                    Err(e) => tracing::error!("Input replay failed: {}", e),
+                }
+            }
+            replaying.store(false, Ordering::SeqCst);
+        })
+    }
+
+    fn start_metrics_export_task(
+        addr: SocketAddr,
+        interval: Duration,
+        stats: Arc<Mutex<PerformanceStats>>,
+        cancel_token: CancellationToken,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let listener = match TcpListener::bind(addr).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    tracing::error!("Failed to bind metrics exporter on {}: {}", addr, e);
+                    return;
+                }
+            };
+            tracing::info!("Exporting metrics on {}", addr);
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            tracing::info!("Metrics consumer connected from {}", peer);
+                            tokio::spawn(Self::stream_metrics(
+                                stream,
+                                interval,
+                                stats.clone(),
+                                cancel_token.clone(),
+                            ));
+                        }
+                        Err(e) => tracing::warn!("Failed to accept metrics consumer: {}", e),
+                    }
+                }
+            }
+        })
+    }
+
+    // Writes one JSON snapshot per line until the consumer disconnects or we shut down.
+    async fn stream_metrics(
+        mut stream: TcpStream,
+        interval: Duration,
+        stats: Arc<Mutex<PerformanceStats>>,
+        cancel_token: CancellationToken,
+    ) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                _ = ticker.tick() => {
+                    let line = match stats.lock() {
+                        Ok(stats) => format!("{}\n", stats.to_json()),
+                        Err(_) => break,
+                    };
+                    if let Err(e) = stream.write_all(line.as_bytes()).await {
+                        tracing::info!("Metrics consumer disconnected: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
     pub fn stop(&self) {
         self.cancel_token.cancel();
-        self.pipeline_task.abort();
+        for task in [&self.pipeline_task, &self.metrics_task, &self.replay_task]
+            .into_iter()
+            .flatten()
+        {
+            task.abort();
+        }
+    }
+
+    // Cancels every task and gives them SHUTDOWN_GRACE to finish before aborting them. The
+    // frame reactions and the replay task hold senders of their own, so the action channel only
+    // closes once they are gone and the coordinator drops the last sender. Aborting the metrics
+    // task drops its listener and closes the socket. The input log, if enabled, is written once
+    // the emulator has stopped.
+    pub async fn shutdown(&mut self) {
+        self.cancel_token.cancel();
+        for mut task in [
+            self.pipeline_task.take(),
+            self.metrics_task.take(),
+            self.replay_task.take(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if tokio::time::timeout(SHUTDOWN_GRACE, &mut task)
+                .await
+                .is_err()
+            {
+                tracing::warn!("Task did not stop within {:?}, aborting", SHUTDOWN_GRACE);
+                task.abort();
+                // Wait for the abort to drop the task and whatever senders it holds.
+                let _ = task.await;
+            }
+        }
+        self.action_tx.take();
+        if let Some((recorder, path)) = self.input_log.take() {
+            let log = recorder.snapshot();
+            match log.save(&path) {
+                Ok(()) => tracing::info!("Wrote {} inputs to {}", log.len(), path.display()),
+                Err(e) => tracing::error!("Failed to write input log to {}: {}", path.display(), e),
+            }
+        }
     }
 }
 
@@ -78,6 +352,305 @@ impl Drop for Coordinator {
     }
 }
 
+// Everything that answers an analyzed frame with inputs or emulator control.
+struct FrameReactions {
+    action_tx: MaskedActionSender,
+    watchdog: Option<StuckWatchdog>,
+    attract_mode: Option<AttractModeWatch>,
+    checkpoints: Option<CheckpointManager>,
+    dialog: DialogPrintWatch,
+    changes: FrameDecimator,
+    // Press held back per client while its frames change, and since when.
+    deferred: HashMap<Uuid, (ButtonPress, Instant)>,
+    latency: EffectLatencyEstimator,
+    exploration: ExplorationMap,
+    battle_menu_target: Option<BattleMenuSelection>,
+    advance_prompts: bool,
+    leave_menus: bool,
+    // When each client was last sent a menu plan.
+    planned_at: HashMap<Uuid, Instant>,
+    heatmap_dir: Option<PathBuf>,
+    // Set while a logged input replay runs, the bot keeps watching but sends nothing.
+    replaying: Option<Arc<AtomicBool>>,
+}
+
+impl FrameReactions {
+    fn from_config(
+        configuration: &Configuration,
+        action_tx: Sender<ButtonPress>,
+        control: EmulatorControl,
+    ) -> Self {
+        let watchdog = configuration.stuck_timeout_ms.map(|timeout_ms| {
+            let watchdog = StuckWatchdog::new(Duration::from_millis(timeout_ms))
+                .with_signature_resolution(configuration.stuck_signature_resolution);
+            match configuration.emulator_reset_after_soft_resets {
+                Some(after) => watchdog.with_emulator_reset(after, control.clone()),
+                None => watchdog,
+            }
+        });
+        Self {
+            action_tx: MaskedActionSender::new(
+                action_tx,
+                Arc::new(ActionMask::from_config(&configuration.forbidden_actions)),
+            ),
+            watchdog,
+            attract_mode: configuration.exit_attract_mode.then(AttractModeWatch::new),
+            checkpoints: configuration
+                .checkpoints
+                .clone()
+                .map(|policy| CheckpointManager::new(policy, control)),
+            dialog: DialogPrintWatch::new(),
+            changes: FrameDecimator::new(ANIMATING),
+            deferred: HashMap::new(),
+            latency: EffectLatencyEstimator::new(WALK_HOLD),
+            exploration: ExplorationMap::new(),
+            battle_menu_target: configuration.battle_menu_target,
+            advance_prompts: configuration.advance_prompts,
+            leave_menus: configuration.leave_menus,
+            planned_at: HashMap::new(),
+            heatmap_dir: configuration.change_heatmap_dir.clone(),
+            replaying: None,
+        }
+    }
+
+    fn react(&mut self, response: &FrameContext<AnalyzedState>, now: Instant) {
+        let client_id = response.frame().get_client_id();
+        let scene_type = response.analysis().scene_type();
+        self.dialog.observe(client_id, response.frame().get_image());
+        self.write_heatmap(response.frame());
+        self.changes.forward(response.frame());
+        self.latency
+            .on_frame(client_id, self.changes.is_changing(client_id), now);
+        // Presses during a fade would land in whatever scene comes next, only the watchdog may
+        // act once the fade outlasts its timeout.
+        let fading = scene_type == SceneType::FadeTransition;
+        if fading && let Some((press, _)) = self.deferred.remove(&client_id) {
+            tracing::debug!("Dropped deferred {:?} on a fade", press);
+        } else if let Some((press, _)) = self.deferred.get(&client_id).copied() {
+            self.send(client_id, scene_type, press, now, "deferred");
+        }
+        let (nudge, reset) = self.watchdog.as_mut().map_or((None, false), |watchdog| {
+            let signature = watchdog.signature(response.frame().get_image());
+            let watched = watchdog.state(client_id, now).is_some();
+            let nudge = watchdog.observe(client_id, scene_type, signature, now);
+            // The watchdog forgets a client when it resets its emulator.
+            (nudge, watched && watchdog.state(client_id, now).is_none())
+        });
+        // Walks are held as long as this client's screen takes to start moving.
+        let nudge = nudge.map(|press| match Self::walk_direction(press) {
+            Some(direction) => self.latency.walk(client_id, direction),
+            None => press,
+        });
+        // Rewinding to a checkpoint replaces whatever the watchdog would try next.
+        let rewound = self.checkpoints.as_mut().is_some_and(|checkpoints| {
+            let escalation = self
+                .watchdog
+                .as_ref()
+                .and_then(|watchdog| watchdog.state(client_id, now))
+                .and_then(|state| state.escalation);
+            matches!(
+                checkpoints.observe(response.analysis()),
+                Some(SaveStateCommand::Load(_))
+            ) || escalation.is_some_and(|escalation| checkpoints.on_stuck(escalation).is_some())
+        });
+        // The player is back somewhere else, the steps mapped so far no longer lead from there.
+        if reset || rewound {
+            self.exploration.forget(client_id);
+        }
+        if !rewound && let Some(press) = nudge {
+            self.send(client_id, scene_type, press, now, "watchdog");
+        }
+        if fading {
+            return;
+        }
+        if let Some(press) = self
+            .attract_mode
+            .as_mut()
+            .and_then(|attract_mode| attract_mode.observe(client_id, scene_type, now))
+        {
+            self.send(client_id, scene_type, press, now, "attract mode");
+        }
+        for press in self
+            .plan(client_id, response.analysis(), now)
+            .unwrap_or_default()
+        {
+            self.dispatch(client_id, scene_type, press, now, now, "menu");
+        }
+    }
+
+    // Writes where the frame changed since the client's last forwarded one, if anything did.
+    fn write_heatmap(&self, frame: &Frame) {
+        let Some(dir) = &self.heatmap_dir else {
+            return;
+        };
+        let Some(heatmap) = self
+            .changes
+            .heatmap(frame)
+            .filter(|heatmap| heatmap.max() >= HEATMAP_MIN_HEAT)
+        else {
+            return;
+        };
+        let dir = dir.clone();
+        let path = dir.join(format!(
+            "{}-{}.png",
+            frame.get_client_id(),
+            frame.get_frame_id()
+        ));
+        // Encoding a PNG takes longer than a frame, keep it off the pipeline's task.
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                tracing::warn!(
+                    "Failed to create heatmap directory {}: {}",
+                    dir.display(),
+                    e
+                );
+            } else if let Err(e) = heatmap.to_image(HEATMAP_TILE_SIZE).save(&path) {
+                tracing::warn!(
+                    "Failed to write change heatmap to {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        });
+    }
+
+    // Presses that answer a battle turn or a prompt, once the client's screen holds still and
+    // the previous plan had time to take effect.
+    fn plan(
+        &mut self,
+        client_id: Uuid,
+        analysis: &SceneAnalysis,
+        now: Instant,
+    ) -> Option<Vec<ButtonPress>> {
+        if self.changes.is_changing(client_id)
+            || self
+                .planned_at
+                .get(&client_id)
+                .is_some_and(|at| now.saturating_duration_since(*at) < PLAN_COOLDOWN)
+        {
+            return None;
+        }
+        let plan = match analysis.scene_type() {
+            SceneType::Battle => MenuNavigator::battle_menu(
+                analysis.battle_menu_selection()?,
+                self.battle_menu_target?,
+            ),
+            scene_type => {
+                let prompt =
+                    MenuNavigator::advance_prompt(scene_type).filter(|_| self.advance_prompts);
+                let back_out = || MenuNavigator::back_out(scene_type).filter(|_| self.leave_menus);
+                vec![prompt.or_else(back_out)?]
+            }
+        };
+        self.planned_at.insert(client_id, now);
+        Some(plan)
+    }
+
+    // Sends a press answering the client's latest frame. While its frames are changing, the
+    // press is held back until they settle, up to MAX_ANIMATION_WAIT, and a wait goes out
+    // instead: pressing mid-animation can cut it short or land in the wrong menu. A newer press
+    // replaces the held back one.
+    fn send(
+        &mut self,
+        client_id: Uuid,
+        scene_type: SceneType,
+        press: ButtonPress,
+        now: Instant,
+        source: &str,
+    ) {
+        let deferred_since = self.deferred.remove(&client_id).map(|(_, since)| since);
+        let since = deferred_since.unwrap_or(now);
+        if self.changes.is_changing(client_id)
+            && now.saturating_duration_since(since) < MAX_ANIMATION_WAIT
+        {
+            if deferred_since.is_none() {
+                self.dispatch(
+                    client_id,
+                    scene_type,
+                    ButtonPress::wait(ANIMATION_WAIT),
+                    now,
+                    now,
+                    source,
+                );
+            }
+            self.deferred.insert(client_id, (press, since));
+            return;
+        }
+        self.dispatch(client_id, scene_type, press, since, now, source);
+    }
+
+    // A and B are held back while dialog text is still printing, either would skip the rest of
+    // it. They are deferred like presses during an animation, up to MAX_ANIMATION_WAIT counted
+    // from `since`, while the rest of the press goes out. Walks sent start timing their effect
+    // and are tracked on the exploration map.
+    fn dispatch(
+        &mut self,
+        client_id: Uuid,
+        scene_type: SceneType,
+        press: ButtonPress,
+        since: Instant,
+        now: Instant,
+        source: &str,
+    ) {
+        if self
+            .replaying
+            .as_ref()
+            .is_some_and(|replaying| replaying.load(Ordering::SeqCst))
+        {
+            tracing::debug!("Dropped {} action {:?} during input replay", source, press);
+            return;
+        }
+        let press = if self.dialog.allows_advance(client_id)
+            || now.saturating_duration_since(since) >= MAX_ANIMATION_WAIT
+        {
+            press
+        } else {
+            let advance = ButtonSet::from(GameAction::A).with(GameAction::B);
+            let buttons = press.buttons().without(advance);
+            let held = press.buttons().without(buttons);
+            if !held.is_empty() {
+                tracing::debug!("Holding back {:?} until the dialog text printed", held);
+                self.deferred
+                    .insert(client_id, (press.with_buttons(held), since));
+            }
+            if buttons.is_empty() && !press.is_wait() {
+                return;
+            }
+            press.with_buttons(buttons)
+        };
+        if let Err(e) = self.action_tx.try_send(scene_type, press) {
+            tracing::warn!("Failed to send {} action: {}", source, e);
+            return;
+        }
+        if let Some(direction) = Self::walk_direction(press) {
+            self.latency.on_walk_sent(client_id, now);
+            if self.exploration.record_move(
+                client_id,
+                direction,
+                scene_type,
+                press.hold_duration().unwrap_or_default(),
+            ) {
+                tracing::debug!(
+                    "Client {} reached new ground, {:.0}% of its steps explored",
+                    client_id,
+                    self.exploration.coverage(client_id) * 100.0
+                );
+            }
+        }
+    }
+
+    // The direction of a press that only walks.
+    fn walk_direction(press: ButtonPress) -> Option<GameAction> {
+        let buttons = press.buttons();
+        let direction = buttons.iter().next()?;
+        let walks = matches!(
+            direction,
+            GameAction::Up | GameAction::Down | GameAction::Left | GameAction::Right
+        );
+        (walks && buttons.len() == 1).then_some(direction)
+    }
+}
+
 pub struct CoordinatorBuilder {
     configuration: Configuration,
     pipeline: Option<ProcessingPipeline>,
@@ -115,6 +688,12 @@ impl CoordinatorBuilder {
         self
     }
 
+    // Streams JSON metrics snapshots to consumers connecting on this address.
+    pub fn metrics_export_addr(mut self, metrics_export_addr: SocketAddr) -> Self {
+        self.configuration.metrics_export_addr = Some(metrics_export_addr);
+        self
+    }
+
     pub fn pipeline(mut self, pipeline: ProcessingPipeline) -> Self {
         self.pipeline = Some(pipeline);
         self
@@ -124,16 +703,536 @@ impl CoordinatorBuilder {
         let pipeline = self
             .pipeline
             .ok_or(AppError::Pipeline("Pipeline not set".to_string()))?;
-        Ok(Coordinator::new(self.configuration, pipeline))
+        Coordinator::new(self.configuration, pipeline)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::VecDeque;
+
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use crate::emulator::input_log::InputLog;
+    use crate::pipeline::context::state::IngestedState;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+    use crate::pipeline::domain::scene_analysis::OverworldKind;
+    use crate::pipeline::orchestration::checkpoint_manager::CheckpointPolicy;
+    use crate::pipeline::orchestration::processing_pipeline::AnalyzerStep;
     use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
 
     use super::*;
 
+    // Hands out the scripted analyses in order, one per frame.
+    struct ScriptedAnalyzer(Mutex<VecDeque<SceneAnalysis>>);
+
+    #[async_trait]
+    impl AnalyzerStep for ScriptedAnalyzer {
+        async fn analyze(
+            &self,
+            _ctx: &FrameContext<IngestedState>,
+        ) -> Result<SceneAnalysis, AppError> {
+            self.0
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or(AppError::Pipeline("script ran out".to_string()))
+        }
+    }
+
+    // Feeds one frame per scripted step through the pipeline task, `interval` apart, and
+    // returns once every frame was handled.
+    async fn run_script(
+        configuration: &Configuration,
+        script: Vec<(SceneAnalysis, RgbImage)>,
+        interval: Duration,
+    ) -> (EmulatorControl, Receiver<ButtonPress>) {
+        let control = EmulatorControl::new();
+        let (action_tx, action_rx) = tokio::sync::mpsc::channel(64);
+        let reactions = FrameReactions::from_config(configuration, action_tx, control.clone());
+        let (analyses, images): (VecDeque<_>, Vec<_>) = script.into_iter().unzip();
+        let pipeline = ProcessingPipeline::builder()
+            .add_analyzer(Box::new(ScriptedAnalyzer(Mutex::new(analyses))))
+            .build();
+        let (frame_tx, frame_rx) = tokio::sync::mpsc::channel(images.len().max(1));
+        let task = Coordinator::start_pipeline_task(
+            pipeline,
+            frame_rx,
+            FrameDropPolicy::ProcessAll,
+            reactions,
+            CancellationToken::new(),
+        );
+        let client_id = Uuid::new_v4();
+        for image in images {
+            let frame = Frame::new(
+                client_id,
+                DynamicImage::ImageRgb8(image),
+                Utc::now(),
+                Uuid::new_v4(),
+            );
+            frame_tx.send(frame).await.unwrap();
+            tokio::time::sleep(interval).await;
+        }
+        drop(frame_tx);
+        task.await.unwrap();
+        (control, action_rx)
+    }
+
+    fn blank() -> RgbImage {
+        RgbImage::new(64, 64)
+    }
+
+    fn card(badges: u8) -> (SceneAnalysis, RgbImage) {
+        let analysis =
+            SceneAnalysis::new(SceneType::TrainerCard, 0.9).with_badges_earned(Some(badges));
+        (analysis, blank())
+    }
+
+    fn overworld(kind: OverworldKind) -> (SceneAnalysis, RgbImage) {
+        let analysis =
+            SceneAnalysis::new(SceneType::Overworld, 0.8).with_overworld_kind(Some(kind));
+        (analysis, blank())
+    }
+
+    fn scene(scene_type: SceneType) -> (SceneAnalysis, RgbImage) {
+        (SceneAnalysis::new(scene_type, 0.9), blank())
+    }
+
+    #[tokio::test]
+    async fn whiteouts_rewind_with_only_checkpoints_configured() {
+        let configuration = Configuration {
+            checkpoints: Some(CheckpointPolicy {
+                confirm_frames: 1,
+                ..CheckpointPolicy::default()
+            }),
+            ..Configuration::default()
+        };
+        let script = vec![
+            card(0),
+            card(1),
+            overworld(OverworldKind::Outdoor),
+            scene(SceneType::Battle),
+            scene(SceneType::FadeTransition),
+            overworld(OverworldKind::Indoor),
+        ];
+
+        let (control, _actions) = run_script(&configuration, script, Duration::ZERO).await;
+        assert_eq!(
+            control.take_save_states(),
+            vec![SaveStateCommand::Save(0), SaveStateCommand::Load(0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn rewinds_forget_the_explored_cells() {
+        let configuration = Configuration {
+            checkpoints: Some(CheckpointPolicy {
+                confirm_frames: 1,
+                ..CheckpointPolicy::default()
+            }),
+            ..Configuration::default()
+        };
+        let (action_tx, _action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions =
+            FrameReactions::from_config(&configuration, action_tx, EmulatorControl::new());
+        let client_id = Uuid::new_v4();
+        let now = Instant::now();
+        let react = |reactions: &mut FrameReactions, (analysis, image)| {
+            let frame = Frame::new(
+                client_id,
+                DynamicImage::ImageRgb8(image),
+                Utc::now(),
+                Uuid::new_v4(),
+            );
+            reactions.react(&FrameContext::new(frame).into_analyzed(analysis), now);
+        };
+
+        for step in [card(0), card(1), overworld(OverworldKind::Outdoor)] {
+            react(&mut reactions, step);
+        }
+        let up = ButtonPress::tap(GameAction::Up);
+        reactions.dispatch(client_id, SceneType::Overworld, up, now, now, "test");
+        assert_eq!(reactions.exploration.visited_cells(client_id), 2);
+
+        for step in [
+            scene(SceneType::Battle),
+            scene(SceneType::FadeTransition),
+            overworld(OverworldKind::Indoor),
+        ] {
+            react(&mut reactions, step);
+        }
+        assert_eq!(reactions.exploration.visited_cells(client_id), 0);
+    }
+
+    fn dialog_frame(characters: u32) -> RgbImage {
+        SyntheticFrame::dialog(characters, Rgb([90, 180, 90])).build()
+    }
+
+    fn analyzed(client_id: Uuid, image: RgbImage) -> FrameContext<AnalyzedState> {
+        let frame = Frame::new(
+            client_id,
+            DynamicImage::ImageRgb8(image),
+            Utc::now(),
+            Uuid::new_v4(),
+        );
+        FrameContext::new(frame).into_analyzed(SceneAnalysis::new(SceneType::Menu, 0.9))
+    }
+
+    #[tokio::test]
+    async fn printing_dialogs_hold_back_a_and_b() {
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions = FrameReactions::from_config(
+            &Configuration::default(),
+            action_tx,
+            EmulatorControl::new(),
+        );
+        let client_id = Uuid::new_v4();
+        let a_and_up = ButtonPress::tap(ButtonSet::from(GameAction::A).with(GameAction::Up));
+
+        for characters in [0, 1, 2] {
+            reactions.react(
+                &analyzed(client_id, dialog_frame(characters)),
+                Instant::now(),
+            );
+        }
+        reactions.send(
+            client_id,
+            SceneType::Menu,
+            ButtonPress::tap(GameAction::A),
+            Instant::now(),
+            "test",
+        );
+        reactions.send(client_id, SceneType::Menu, a_and_up, Instant::now(), "test");
+        assert_eq!(action_rx.try_recv(), Ok(ButtonPress::tap(GameAction::Up)));
+        assert!(action_rx.try_recv().is_err());
+
+        // The held back A goes out once the text stopped printing.
+        reactions.react(&analyzed(client_id, dialog_frame(2)), Instant::now());
+        assert_eq!(action_rx.try_recv(), Ok(ButtonPress::tap(GameAction::A)));
+        assert!(action_rx.try_recv().is_err());
+    }
+
+    fn flashing(frame: usize) -> RgbImage {
+        RgbImage::from_pixel(
+            64,
+            64,
+            Rgb([if frame.is_multiple_of(2) { 0 } else { 255 }; 3]),
+        )
+    }
+
+    #[tokio::test]
+    async fn changing_frames_wait_before_leaving_a_demo() {
+        let configuration = Configuration {
+            exit_attract_mode: true,
+            ..Configuration::default()
+        };
+        // A title demo flashing between scenes, then settling on its last frame.
+        let mut script: Vec<_> = (0..6)
+            .map(|frame| {
+                let scene_type = [SceneType::TitleScreen, SceneType::Cutscene][frame % 2];
+                (SceneAnalysis::new(scene_type, 0.9), flashing(frame))
+            })
+            .collect();
+        script.push((SceneAnalysis::new(SceneType::Cutscene, 0.9), flashing(5)));
+
+        let (_control, mut actions) = run_script(&configuration, script, Duration::ZERO).await;
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::wait(ANIMATION_WAIT)));
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::tap(GameAction::Start)));
+        assert!(actions.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn held_back_presses_are_dropped_on_a_fade() {
+        let configuration = Configuration {
+            exit_attract_mode: true,
+            ..Configuration::default()
+        };
+        let mut script: Vec<_> = (0..6)
+            .map(|frame| {
+                let scene_type = [SceneType::TitleScreen, SceneType::Cutscene][frame % 2];
+                (SceneAnalysis::new(scene_type, 0.9), flashing(frame))
+            })
+            .collect();
+        script.extend((0..2).map(|_| {
+            (
+                SceneAnalysis::new(SceneType::FadeTransition, 0.9),
+                flashing(5),
+            )
+        }));
+
+        let (_control, mut actions) = run_script(&configuration, script, Duration::ZERO).await;
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::wait(ANIMATION_WAIT)));
+        assert!(actions.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn endless_animations_get_their_press_eventually() {
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions = FrameReactions::from_config(
+            &Configuration::default(),
+            action_tx,
+            EmulatorControl::new(),
+        );
+        let client_id = Uuid::new_v4();
+        let start = Instant::now();
+        for frame in 0..3 {
+            reactions.react(&analyzed(client_id, flashing(frame)), start);
+        }
+        let press = ButtonPress::tap(GameAction::Start);
+        reactions.send(client_id, SceneType::Menu, press, start, "test");
+        assert_eq!(action_rx.try_recv(), Ok(ButtonPress::wait(ANIMATION_WAIT)));
+
+        reactions.react(
+            &analyzed(client_id, flashing(3)),
+            start + Duration::from_secs(1),
+        );
+        assert!(action_rx.try_recv().is_err());
+        reactions.react(
+            &analyzed(client_id, flashing(4)),
+            start + MAX_ANIMATION_WAIT,
+        );
+        assert_eq!(action_rx.try_recv(), Ok(press));
+    }
+
+    #[tokio::test]
+    async fn walks_are_held_as_long_as_their_effect_takes() {
+        let configuration = Configuration {
+            stuck_timeout_ms: Some(1000),
+            ..Configuration::default()
+        };
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions =
+            FrameReactions::from_config(&configuration, action_tx, EmulatorControl::new());
+        let client_id = Uuid::new_v4();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        reactions.react(&analyzed(client_id, flashing(0)), at(0));
+        reactions.react(&analyzed(client_id, flashing(0)), at(1000));
+        let first = action_rx.try_recv().unwrap();
+        assert_eq!(first.buttons(), ButtonSet::from(GameAction::Up));
+        // The screen only starts moving 20 frames after the walk.
+        for frame in 1..20 {
+            reactions.react(&analyzed(client_id, flashing(0)), at(1000 + frame * 17));
+        }
+        reactions.react(&analyzed(client_id, flashing(1)), at(1340));
+        reactions.react(&analyzed(client_id, flashing(1)), at(1357));
+        reactions.react(&analyzed(client_id, flashing(1)), at(2357));
+
+        let second = action_rx.try_recv().unwrap();
+        assert_eq!(second.buttons(), ButtonSet::from(GameAction::Up));
+        assert!(second.hold_duration() > first.hold_duration());
+        assert!(reactions.latency.hold(client_id) > WALK_HOLD);
+    }
+
+    #[tokio::test]
+    async fn battle_turns_and_prompts_follow_a_plan() {
+        let configuration = Configuration {
+            battle_menu_target: Some(BattleMenuSelection::Run),
+            ..Configuration::default()
+        };
+        let turn = || {
+            SceneAnalysis::new(SceneType::Battle, 0.9)
+                .with_battle_menu_selection(Some(BattleMenuSelection::Fight))
+        };
+        let script = vec![(turn(), blank()), (turn(), blank())];
+
+        let (_control, mut actions) = run_script(&configuration, script, Duration::ZERO).await;
+        for action in [GameAction::Right, GameAction::Down, GameAction::A] {
+            assert_eq!(actions.try_recv(), Ok(ButtonPress::tap(action)));
+        }
+        // The menu gets time to react before the next plan.
+        assert!(actions.try_recv().is_err());
+
+        let configuration = Configuration {
+            advance_prompts: true,
+            ..Configuration::default()
+        };
+        let script = vec![scene(SceneType::MoveLearn)];
+        let (_control, mut actions) = run_script(&configuration, script, Duration::ZERO).await;
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::tap(GameAction::B)));
+    }
+
+    #[tokio::test]
+    async fn menus_are_left_with_b_when_configured() {
+        let script = || vec![scene(SceneType::Bag)];
+        let (_control, mut actions) =
+            run_script(&Configuration::default(), script(), Duration::ZERO).await;
+        assert!(actions.try_recv().is_err());
+
+        let configuration = Configuration {
+            leave_menus: true,
+            ..Configuration::default()
+        };
+        let (_control, mut actions) = run_script(&configuration, script(), Duration::ZERO).await;
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::tap(GameAction::B)));
+        assert!(actions.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn shops_are_left_without_buying() {
+        let configuration = Configuration {
+            leave_menus: true,
+            ..Configuration::default()
+        };
+        let script = vec![scene(SceneType::Shop), scene(SceneType::Shop)];
+        let (_control, mut actions) = run_script(&configuration, script, Duration::ZERO).await;
+        assert_eq!(actions.try_recv(), Ok(ButtonPress::tap(GameAction::B)));
+        // The shop gets time to close before the next B.
+        assert!(actions.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn changed_frames_write_a_heatmap() {
+        let dir = std::env::temp_dir().join(format!("pokebot-heatmaps-{}", Uuid::new_v4()));
+        let configuration = Configuration {
+            change_heatmap_dir: Some(dir.clone()),
+            ..Configuration::default()
+        };
+        let (action_tx, _action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions =
+            FrameReactions::from_config(&configuration, action_tx, EmulatorControl::new());
+        let client_id = Uuid::new_v4();
+        // A faint change in between stays below the threshold.
+        let faint = RgbImage::from_pixel(64, 64, Rgb([16; 3]));
+        for image in [flashing(0), flashing(0), faint, flashing(1)] {
+            reactions.react(&analyzed(client_id, image), Instant::now());
+        }
+
+        // Heatmaps are written in the background.
+        let heatmap = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Ok(mut written) = std::fs::read_dir(&dir)
+                    && let Some(Ok(entry)) = written.next()
+                    && let Ok(heatmap) = image::open(entry.path())
+                {
+                    break heatmap;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(heatmap.width(), 8 * HEATMAP_TILE_SIZE);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overworld_walks_are_mapped() {
+        let (action_tx, _action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions = FrameReactions::from_config(
+            &Configuration::default(),
+            action_tx,
+            EmulatorControl::new(),
+        );
+        let client_id = Uuid::new_v4();
+        let now = Instant::now();
+        for direction in [GameAction::Up, GameAction::Right, GameAction::Down] {
+            let walk = ButtonPress::hold(direction, Duration::from_millis(100));
+            reactions.dispatch(client_id, SceneType::Overworld, walk, now, now, "test");
+        }
+        // Menu cursors moving and buttons other than directions are no steps.
+        reactions.dispatch(
+            client_id,
+            SceneType::Bag,
+            ButtonPress::tap(GameAction::Up),
+            now,
+            now,
+            "test",
+        );
+        reactions.dispatch(
+            client_id,
+            SceneType::Overworld,
+            ButtonPress::tap(GameAction::A),
+            now,
+            now,
+            "test",
+        );
+        assert_eq!(reactions.exploration.visited_cells(client_id), 4);
+        assert!(!reactions.exploration.is_revisiting(client_id));
+
+        let left = ButtonPress::tap(GameAction::Left);
+        reactions.dispatch(client_id, SceneType::Overworld, left, now, now, "test");
+        assert!(reactions.exploration.is_revisiting(client_id));
+    }
+
+    #[tokio::test]
+    async fn replays_logged_inputs_before_the_bot_takes_over() {
+        let mut log = InputLog::new();
+        log.push(ButtonPress::tap(GameAction::Start), Duration::ZERO);
+        log.push(ButtonPress::tap(GameAction::A), Duration::from_millis(20));
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+        let mut reactions = FrameReactions::from_config(
+            &Configuration::default(),
+            action_tx.clone(),
+            EmulatorControl::new(),
+        );
+        let replaying = Arc::new(AtomicBool::new(true));
+        reactions.replaying = Some(replaying.clone());
+        let client_id = Uuid::new_v4();
+        let now = Instant::now();
+        let up = ButtonPress::tap(GameAction::Up);
+
+        let replay = Coordinator::start_replay_task(
+            InputReplayer::new(log),
+            action_tx,
+            replaying.clone(),
+            CancellationToken::new(),
+        );
+        reactions.dispatch(client_id, SceneType::Overworld, up, now, now, "test");
+        replay.await.unwrap();
+        assert!(!replaying.load(Ordering::SeqCst));
+        assert_eq!(
+            action_rx.try_recv(),
+            Ok(ButtonPress::tap(GameAction::Start))
+        );
+        assert_eq!(action_rx.try_recv(), Ok(ButtonPress::tap(GameAction::A)));
+        assert!(action_rx.try_recv().is_err());
+
+        reactions.dispatch(client_id, SceneType::Overworld, up, now, now, "test");
+        assert_eq!(action_rx.try_recv(), Ok(up));
+    }
+
+    #[tokio::test]
+    async fn missing_replay_logs_fail_the_build() {
+        let result = CoordinatorBuilder::new(Configuration {
+            replay_input_log: Some(PathBuf::from("logs/missing.json")),
+            ..Configuration::default()
+        })
+        .pipeline(
+            ProcessingPipeline::builder()
+                .add_analyzer(Box::new(SceneAnalyzer::new()))
+                .build(),
+        )
+        .build();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn stuck_clients_rewind_without_emulator_resets() {
+        let configuration = Configuration {
+            stuck_timeout_ms: Some(10),
+            checkpoints: Some(CheckpointPolicy {
+                confirm_frames: 1,
+                ..CheckpointPolicy::default()
+            }),
+            ..Configuration::default()
+        };
+        let mut script = vec![card(0), card(1)];
+        script.extend((0..8).map(|_| overworld(OverworldKind::Outdoor)));
+
+        let (control, _actions) =
+            run_script(&configuration, script, Duration::from_millis(10)).await;
+        assert_eq!(
+            control.take_save_states(),
+            vec![SaveStateCommand::Save(0), SaveStateCommand::Load(0)]
+        );
+        assert!(!control.take_reset());
+    }
+
     #[tokio::test]
     async fn test_coordinator() {
         let coordinator = CoordinatorBuilder::new(Configuration::default())
@@ -150,4 +1249,25 @@ mod tests {
             .expect("Failed to build coordinator");
         coordinator.stop();
     }
+
+    #[tokio::test]
+    async fn shutdown_stops_every_task() {
+        let mut coordinator = CoordinatorBuilder::new(Configuration::default())
+            .rom_path("tests/roms/missing.nds".to_string())
+            .metrics_export_addr("127.0.0.1:0".parse().unwrap())
+            .pipeline(
+                ProcessingPipeline::builder()
+                    .add_analyzer(Box::new(SceneAnalyzer::new()))
+                    .build(),
+            )
+            .build()
+            .expect("Failed to build coordinator");
+
+        tokio::time::timeout(SHUTDOWN_GRACE * 3, coordinator.shutdown())
+            .await
+            .expect("shutdown hung");
+        assert!(coordinator.pipeline_task.is_none());
+        assert!(coordinator.metrics_task.is_none());
+        assert!(coordinator.action_tx.is_none());
+    }
 }
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+// Enforces a minimum interval between actions applied to one emulator so a fast
+// decision loop can't flood it with inputs it would drop anyway.
+pub struct ActionThrottle {
+    min_interval: Duration,
+    last_sent_at: Option<Instant>,
+}
+
+impl ActionThrottle {
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_sent_at: None,
+        }
+    }
+
+    // Returns true and records the send if enough time passed since the previous action.
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        let ready = self
+            .last_sent_at
+            .is_none_or(|last| now.saturating_duration_since(last) >= self.min_interval);
+        if ready {
+            self.last_sent_at = Some(now);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapid_actions_respect_the_minimum_interval() {
+        let min_interval = Duration::from_millis(16);
+        let mut throttle = ActionThrottle::new(min_interval);
+        let start = Instant::now();
+
+        let sent: Vec<Instant> = (0..100)
+            .map(|ms| start + Duration::from_millis(ms))
+            .filter(|now| throttle.try_acquire(*now))
+            .collect();
+
+        assert_eq!(sent.len(), 7);
+        for pair in sent.windows(2) {
+            assert!(pair[1] - pair[0] >= min_interval);
+        }
+    }
+
+    #[test]
+    fn zero_interval_never_holds_actions() {
+        let mut throttle = ActionThrottle::new(Duration::ZERO);
+        let now = Instant::now();
+        assert!((0..5).all(|_| throttle.try_acquire(now)));
+    }
+}
@@ -1,3 +1,7 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use chrono::Utc;
 use image::{DynamicImage, RgbImage};
 use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
@@ -5,8 +9,56 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
-use crate::common::Frame;
-use crate::{common::game_action::GameAction, error::AppError};
+use crate::common::{ButtonPress, ButtonSet, Frame, GameAction};
+use crate::emulator::action_throttle::ActionThrottle;
+use crate::emulator::input_log::InputRecorder;
+use crate::emulator::restart_backoff::{RestartBackoff, RestartPolicy};
+use crate::error::AppError;
+
+// Save-state slot operations the emulator thread runs between frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveStateCommand {
+    Save(u16),
+    Load(u16),
+}
+
+// Requests for the emulator thread that bypass the input queue: resetting the running game,
+// for softlocks that no input gets out of, and saving or loading save-state slots. Several
+// reset requests before the emulator gets to them result in a single reset.
+#[derive(Debug, Clone, Default)]
+pub struct EmulatorControl {
+    reset: Arc<AtomicBool>,
+    save_states: Arc<Mutex<Vec<SaveStateCommand>>>,
+}
+
+impl EmulatorControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request_reset(&self) {
+        self.reset.store(true, Ordering::SeqCst);
+    }
+
+    // Returns whether a reset was requested and clears the request.
+    pub fn take_reset(&self) -> bool {
+        self.reset.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn request_save_state(&self, command: SaveStateCommand) {
+        if let Ok(mut save_states) = self.save_states.lock() {
+            save_states.push(command);
+        }
+    }
+
+    // Returns the pending save-state commands in request order and clears them.
+    pub fn take_save_states(&self) -> Vec<SaveStateCommand> {
+        self.save_states
+            .lock()
+            .map(|mut save_states| std::mem::take(&mut *save_states))
+            .unwrap_or_default()
+    }
+}
 
 pub struct EmulatorClient {
     cancel_token: CancellationToken,
@@ -14,9 +66,25 @@ pub struct EmulatorClient {
 }
 
 impl EmulatorClient {
-    pub fn new(action_rx: Receiver<GameAction>, frame_tx: Sender<Frame>, rom_path: String) -> Self {
+    pub fn new(
+        action_rx: Receiver<ButtonPress>,
+        frame_tx: Sender<Frame>,
+        rom_path: String,
+        min_action_interval: Duration,
+        restart_policy: RestartPolicy,
+        input_recorder: Option<InputRecorder>,
+        control: EmulatorControl,
+    ) -> Self {
         let cancel_token = CancellationToken::new();
-        let mut emulator = Emulator::new(action_rx, frame_tx, rom_path);
+        let mut emulator = Emulator::new(
+            action_rx,
+            frame_tx,
+            rom_path,
+            min_action_interval,
+            restart_policy,
+            input_recorder,
+            control,
+        );
         Self {
             cancel_token: cancel_token.clone(),
             emulator_thread: Some(std::thread::spawn(move || {
@@ -40,19 +108,42 @@ impl Drop for EmulatorClient {
 }
 
 struct Emulator {
-    action_rx: Receiver<GameAction>,
+    action_rx: Receiver<ButtonPress>,
     frame_tx: Sender<Frame>,
     rom_path: String,
     id: Uuid,
+    throttle: ActionThrottle,
+    // Action held back by the throttle, applied as soon as the interval has passed.
+    pending_action: Option<ButtonPress>,
+    // Deadline of the buttons currently held down, keys are released once it passes.
+    held_until: Option<Instant>,
+    restart_policy: RestartPolicy,
+    // Logs every input at the moment it is applied, for replaying the session later.
+    input_recorder: Option<InputRecorder>,
+    control: EmulatorControl,
 }
 
 impl Emulator {
-    pub fn new(action_rx: Receiver<GameAction>, frame_tx: Sender<Frame>, rom_path: String) -> Self {
+    pub fn new(
+        action_rx: Receiver<ButtonPress>,
+        frame_tx: Sender<Frame>,
+        rom_path: String,
+        min_action_interval: Duration,
+        restart_policy: RestartPolicy,
+        input_recorder: Option<InputRecorder>,
+        control: EmulatorControl,
+    ) -> Self {
         Self {
             action_rx,
             frame_tx,
             rom_path,
             id: Uuid::new_v4(),
+            throttle: ActionThrottle::new(min_action_interval),
+            pending_action: None,
+            held_until: None,
+            restart_policy,
+            input_recorder,
+            control,
         }
     }
     fn initalize_desmume(
@@ -75,12 +166,39 @@ impl Emulator {
         Ok(desmume)
     }
 
+    fn discard_input(&mut self, desmume: &mut desmume_rs::DeSmuME) {
+        self.pending_action = None;
+        self.held_until = None;
+        self.release_key(desmume);
+    }
+
     fn release_key(&mut self, desmume: &mut desmume_rs::DeSmuME) {
         desmume.input_mut().keypad_update(0);
     }
 
-    fn prepare_action(&mut self, action: GameAction, desmume: &mut desmume_rs::DeSmuME) {
-        let mask: u16 = match action {
+    fn prepare_action(&mut self, buttons: ButtonSet, desmume: &mut desmume_rs::DeSmuME) {
+        if buttons.is_empty() {
+            // A wait, press nothing until it is over.
+            self.release_key(desmume);
+            return;
+        }
+        let mask = buttons
+            .iter()
+            .fold(0u16, |mask, action| mask | Self::keypad_mask(action));
+        if mask != 0 {
+            desmume.input_mut().keypad_update(mask);
+            tracing::info!(
+                "Applied keypad mask {:#018b} for buttons {:?}",
+                mask,
+                buttons
+            );
+        } else {
+            tracing::warn!("No keypad mapping for buttons {:?}", buttons);
+        }
+    }
+
+    fn keypad_mask(action: GameAction) -> u16 {
+        match action {
             GameAction::A => 1 << 0,
             GameAction::B => 1 << 1,
             GameAction::Select => 1 << 2,
@@ -92,14 +210,6 @@ impl Emulator {
             GameAction::R => 1 << 8,
             GameAction::L => 1 << 9,
             GameAction::X => 1 << 10,
-            // If GameAction::Y does not exist, map nothing for that slot
-            _ => 0,
-        };
-        if mask != 0 {
-            desmume.input_mut().keypad_update(mask);
-            tracing::info!("Applied keypad mask {:#018b} for action {:?}", mask, action);
-        } else {
-            tracing::warn!("No keypad mapping for action {:?}", action);
         }
     }
 
@@ -128,7 +238,8 @@ impl Emulator {
         }
     }
 
-    fn process_frame(&mut self, desmume: &mut desmume_rs::DeSmuME) {
+    // Sends the current frame, returns false once nobody is listening for frames anymore.
+    fn process_frame(&mut self, desmume: &mut desmume_rs::DeSmuME) -> bool {
         let image = self.get_dynamic_image(desmume);
         match image {
             Some(image) => {
@@ -144,9 +255,7 @@ impl Emulator {
                         }
                         TrySendError::Closed(_) => {
                             tracing::warn!("Frame channel closed, stopping emulator loop");
-                        }
-                        _ => {
-                            tracing::error!("Failed to send frame: {}", err);
+                            return false;
                         }
                     },
                 }
@@ -155,37 +264,135 @@ impl Emulator {
                 tracing::error!("Failed to get dynamic image");
             }
         }
+        true
     }
 
+    // Runs the game, restarting the emulator with backoff when it fails to start or stops on
+    // its own, until cancelled, the action or frame channel closes or the restart policy gives
+    // up.
     pub fn run(&mut self, cancel_token: CancellationToken) {
         tracing::info!("Emulator starting game, with unique id: {}", self.id);
 
-        let desmume = self.initalize_desmume(&self.rom_path.clone(), true);
-        match desmume {
-            Ok(mut desmume) => {
-                while desmume.is_running() && !cancel_token.is_cancelled() {
-                    match self.action_rx.try_recv() {
-                        Ok(action) => {
-                            self.prepare_action(action, &mut desmume);
-                        }
-                        Err(TryRecvError::Disconnected) => {
-                            tracing::error!("Action channel closed, stopping emulator loop");
-                            break;
-                        }
-                        Err(_) => {
-                            // No action to process, cycle the emulator and process the frame
-                        }
+        let mut backoff = RestartBackoff::new(self.restart_policy);
+        while !cancel_token.is_cancelled() {
+            let started = Instant::now();
+            match self.initalize_desmume(&self.rom_path.clone(), true) {
+                Ok(mut desmume) => match self.run_game(&mut desmume, &cancel_token) {
+                    Ok(()) => break,
+                    Err(e) => tracing::error!("{}", e),
+                },
+                Err(e) => tracing::error!("Error initializing desmume: {}", e),
+            }
+            if cancel_token.is_cancelled() {
+                break;
+            }
+            // A game that ran longer than the longest backoff counts as recovered.
+            if started.elapsed() >= Duration::from_millis(self.restart_policy.max_delay_ms) {
+                backoff.reset();
+            }
+            let Some(delay) = backoff.next_delay() else {
+                tracing::error!(
+                    "Emulator {} failed {} restarts in a row, giving up",
+                    self.id,
+                    backoff.attempts()
+                );
+                break;
+            };
+            tracing::warn!(
+                "Emulator {} stopped unexpectedly, restarting in {:?} (attempt {}/{})",
+                self.id,
+                delay,
+                backoff.attempts(),
+                self.restart_policy.max_attempts
+            );
+            Self::sleep_unless_cancelled(delay, &cancel_token);
+        }
+        tracing::info!("Emulator stopped game, with unique id: {}", self.id);
+    }
+
+    // Ok when the game was stopped on purpose: cancelled, or a channel to the pipeline closed.
+    // An error means the game stopped on its own and is worth a restart.
+    fn run_game(
+        &mut self,
+        desmume: &mut desmume_rs::DeSmuME,
+        cancel_token: &CancellationToken,
+    ) -> Result<(), AppError> {
+        while desmume.is_running() && !cancel_token.is_cancelled() {
+            if self.control.take_reset() {
+                tracing::warn!("Resetting the game on emulator {}", self.id);
+                desmume.reset();
+                // Inputs meant for the old game state would land on the boot screens.
+                self.discard_input(desmume);
+            }
+            for command in self.control.take_save_states() {
+                match command {
+                    SaveStateCommand::Save(slot) => {
+                        tracing::info!("Saving state to slot {} on emulator {}", slot, self.id);
+                        desmume.savestate_mut().save(slot);
+                    }
+                    SaveStateCommand::Load(slot) => {
+                        tracing::warn!("Loading state from slot {} on emulator {}", slot, self.id);
+                        desmume.savestate_mut().load(slot);
+                        self.discard_input(desmume);
+                    }
+                }
+            }
+            if self.pending_action.is_none() {
+                match self.action_rx.try_recv() {
+                    Ok(press) => {
+                        self.pending_action = Some(press);
+                    }
+                    Err(TryRecvError::Disconnected) => {
+                        tracing::info!("Action channel closed, stopping emulator loop");
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        // No action to process, cycle the emulator and process the frame
                     }
-                    desmume.cycle();
-                    self.release_key(&mut desmume);
-                    self.process_frame(&mut desmume);
                 }
-                tracing::info!("Emulator stopped game, with unique id: {}", self.id);
             }
-            Err(e) => {
-                tracing::error!("Error initializing desmume: {}", e);
-                tracing::info!("Emulator stopped game, with unique id: {}", self.id);
+            if let Some(press) = self.pending_action
+                && self.held_until.is_none()
+                && self.throttle.try_acquire(Instant::now())
+            {
+                self.pending_action = None;
+                if let Some(recorder) = &self.input_recorder {
+                    recorder.record(press);
+                }
+                self.prepare_action(press.buttons(), desmume);
+                self.held_until = press.hold_duration().map(|hold| Instant::now() + hold);
+            }
+            desmume.cycle();
+            if self
+                .held_until
+                .is_none_or(|deadline| Instant::now() >= deadline)
+            {
+                self.held_until = None;
+                self.release_key(desmume);
+            }
+            if !self.process_frame(desmume) {
+                return Ok(());
+            }
+        }
+        // Keys held in the old emulator instance mean nothing to a restarted one.
+        self.held_until = None;
+        if cancel_token.is_cancelled() {
+            return Ok(());
+        }
+        Err(AppError::Emulator(format!(
+            "Game on emulator {} stopped running on its own",
+            self.id
+        )))
+    }
+
+    fn sleep_unless_cancelled(delay: Duration, cancel_token: &CancellationToken) {
+        let deadline = Instant::now() + delay;
+        while !cancel_token.is_cancelled() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
             }
+            std::thread::sleep(remaining.min(Duration::from_millis(50)));
         }
     }
 }
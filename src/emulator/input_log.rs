@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Sender;
+
+use crate::common::ButtonPress;
+use crate::error::AppError;
+
+// One input applied to the emulator, `offset_ms` after the log was started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputLogEntry {
+    pub offset_ms: u64,
+    pub press: ButtonPress,
+}
+
+// Every input actually applied to the emulator in order, stored as JSON so a session's
+// inputs can be replayed against a fresh emulator.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputLog {
+    entries: Vec<InputLogEntry>,
+}
+
+impl InputLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, press: ButtonPress, offset: Duration) {
+        self.entries.push(InputLogEntry {
+            offset_ms: offset.as_millis() as u64,
+            press,
+        });
+    }
+
+    pub fn entries(&self) -> &[InputLogEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), AppError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, AppError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+// Cloneable handle the emulator thread appends to while the owner keeps a copy for saving.
+#[derive(Clone)]
+pub struct InputRecorder {
+    started: Instant,
+    log: Arc<Mutex<InputLog>>,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            log: Arc::new(Mutex::new(InputLog::new())),
+        }
+    }
+
+    pub fn record(&self, press: ButtonPress) {
+        self.record_at(press, Instant::now());
+    }
+
+    pub fn record_at(&self, press: ButtonPress, at: Instant) {
+        if let Ok(mut log) = self.log.lock() {
+            log.push(press, at.saturating_duration_since(self.started));
+        }
+    }
+
+    pub fn snapshot(&self) -> InputLog {
+        self.log.lock().map(|log| log.clone()).unwrap_or_default()
+    }
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Feeds a recorded log into an emulator's action channel, keeping the original spacing
+// between inputs.
+pub struct InputReplayer {
+    log: InputLog,
+}
+
+impl InputReplayer {
+    pub fn new(log: InputLog) -> Self {
+        Self { log }
+    }
+
+    pub fn open(path: &Path) -> Result<Self, AppError> {
+        Ok(Self::new(InputLog::load(path)?))
+    }
+
+    // Sends every logged input at its original offset from the start of the replay, returns
+    // the number of inputs sent.
+    pub async fn replay(&self, action_tx: &Sender<ButtonPress>) -> Result<usize, AppError> {
+        let started = tokio::time::Instant::now();
+        for entry in self.log.entries() {
+            tokio::time::sleep_until(started + Duration::from_millis(entry.offset_ms)).await;
+            action_tx.send(entry.press).await.map_err(|_| {
+                AppError::Emulator("Action channel closed during input replay".to_string())
+            })?;
+        }
+        Ok(self.log.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::common::GameAction;
+
+    fn presses() -> [ButtonPress; 3] {
+        [
+            ButtonPress::tap(GameAction::A),
+            ButtonPress::wait(Duration::from_millis(100)),
+            ButtonPress::hold(GameAction::Up, Duration::from_millis(250)),
+        ]
+    }
+
+    #[test]
+    fn saved_logs_reload_in_order_with_their_timing() {
+        let recorder = InputRecorder::new();
+        let start = recorder.started;
+        for (press, offset_ms) in presses().into_iter().zip([0, 40, 160]) {
+            recorder.record_at(press, start + Duration::from_millis(offset_ms));
+        }
+
+        let path = std::env::temp_dir().join(format!("pokebot-inputs-{}.json", Uuid::new_v4()));
+        recorder.snapshot().save(&path).unwrap();
+        let loaded = InputLog::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, recorder.snapshot());
+        let pressed: Vec<ButtonPress> = loaded.entries().iter().map(|entry| entry.press).collect();
+        assert_eq!(pressed, presses());
+        let deltas: Vec<u64> = loaded
+            .entries()
+            .windows(2)
+            .map(|pair| pair[1].offset_ms - pair[0].offset_ms)
+            .collect();
+        assert_eq!(deltas, vec![40, 120]);
+    }
+
+    #[tokio::test]
+    async fn replay_honours_the_recorded_spacing() {
+        let mut log = InputLog::new();
+        for (press, offset_ms) in presses().into_iter().zip([0, 30, 60]) {
+            log.push(press, Duration::from_millis(offset_ms));
+        }
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+
+        let started = Instant::now();
+        let sent = InputReplayer::new(log).replay(&action_tx).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(60));
+        assert_eq!(sent, 3);
+        for press in presses() {
+            assert_eq!(action_rx.recv().await, Some(press));
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_fails_once_the_emulator_is_gone() {
+        let mut log = InputLog::new();
+        log.push(ButtonPress::tap(GameAction::B), Duration::ZERO);
+        let (action_tx, action_rx) = tokio::sync::mpsc::channel(1);
+        drop(action_rx);
+
+        let result = InputReplayer::new(log).replay(&action_tx).await;
+        assert!(matches!(result, Err(AppError::Emulator(_))));
+    }
+}
@@ -1 +1,4 @@
+pub mod action_throttle;
 pub mod emulator_client;
+pub mod input_log;
+pub mod restart_backoff;
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// How an emulator that failed to start or stopped unexpectedly is brought back.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    // Fraction of each delay that is randomized, 0.25 spreads a 1s delay over 0.75s..1.25s.
+    pub jitter: f64,
+    // Consecutive failed restarts before giving up, 0 never restarts.
+    pub max_attempts: u32,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter: 0.25,
+            max_attempts: 5,
+        }
+    }
+}
+
+// Exponential backoff between emulator restarts: base, 2 * base, 4 * base, ... capped at the
+// max delay, until the policy runs out of attempts.
+pub struct RestartBackoff {
+    policy: RestartPolicy,
+    attempts: u32,
+}
+
+impl RestartBackoff {
+    pub fn new(policy: RestartPolicy) -> Self {
+        Self {
+            policy,
+            attempts: 0,
+        }
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // Delay before the next restart, or None once the attempts are exhausted.
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempts >= self.policy.max_attempts {
+            return None;
+        }
+        let exponential = self
+            .policy
+            .base_delay_ms
+            .saturating_mul(1u64.checked_shl(self.attempts).unwrap_or(u64::MAX));
+        let delay_ms = exponential.min(self.policy.max_delay_ms) as f64;
+        let jitter = self.policy.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::rng().random_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+        self.attempts += 1;
+        Some(Duration::from_millis((delay_ms * factor) as u64))
+    }
+
+    // Called once the emulator has been running fine again.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: f64) -> RestartPolicy {
+        RestartPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter,
+            max_attempts: 6,
+        }
+    }
+
+    #[test]
+    fn delays_double_up_to_the_cap_then_give_up() {
+        let mut backoff = RestartBackoff::new(policy(0.0));
+        let delays: Vec<u64> = std::iter::from_fn(|| backoff.next_delay())
+            .map(|delay| delay.as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(backoff.next_delay(), None);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        for _ in 0..100 {
+            let mut backoff = RestartBackoff::new(policy(0.25));
+            let delay = backoff.next_delay().unwrap().as_millis();
+            assert!((75..=125).contains(&delay));
+        }
+    }
+}
@@ -12,6 +12,12 @@ pub enum AppError {
     Client(String),
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("Image error: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Frame skipped: {0}")]
+    FrameSkipped(String),
     #[error("Emulator error: {0}")]
     Emulator(String),
     #[error("Configuration error: {0}")]
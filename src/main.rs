@@ -8,8 +8,10 @@ mod pipeline;
 use crate::config::Configuration;
 use crate::coordinator::CoordinatorBuilder;
 use crate::error::AppError;
+use crate::pipeline::detection::game_registry::GameKind;
 use crate::pipeline::orchestration::processing_pipeline::ProcessingPipeline;
 use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+use std::path::Path;
 use tokio::time::Duration;
 use tracing::Level;
 
@@ -19,19 +21,28 @@ fn init_logging() {
 
 #[tokio::main]
 async fn main() -> Result<(), AppError> {
-    let coordinator = CoordinatorBuilder::new(Configuration::default())
-        .rom_path("tests/roms/Super Mario Bros. 3 (USA, Europe) (Rev 1).nes".to_string())
-        .frame_buffer_size(10)
-        .action_buffer_size(10)
-        .enable_metrics(true)
-        .pipeline(
-            ProcessingPipeline::builder()
-                .add_analyzer(Box::new(SceneAnalyzer::new()))
-                .build(),
-        )
+    init_logging();
+    let configuration = Configuration::from_file(Path::new("config/default.toml"))
+        .inspect_err(|e| tracing::error!("{}", e))?;
+    let game_kind = GameKind::detect(Path::new(&configuration.rom_path))
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let analyzer = SceneAnalyzer::for_game(game_kind, configuration.color_thresholds)
+        .with_settings(&configuration.analyzer);
+    let (min_width, min_height) = configuration.min_frame_size;
+    let mut pipeline = ProcessingPipeline::builder().min_frame_size(min_width, min_height);
+    if let Some(directory) = configuration.record_frames_dir.clone() {
+        pipeline = pipeline.record_frames(directory);
+    }
+    if let Some(remap) = configuration.palette_remap.clone() {
+        pipeline = pipeline.preprocess(Box::new(remap));
+    }
+    let mut coordinator = CoordinatorBuilder::new(configuration)
+        .pipeline(pipeline.add_analyzer(Box::new(analyzer)).build())
         .build()
         .expect("Failed to build coordinator");
     tokio::time::sleep(Duration::from_secs(30)).await;
-    coordinator.stop();
+    coordinator.shutdown().await;
     Ok(())
 }
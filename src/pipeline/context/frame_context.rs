@@ -3,14 +3,27 @@ use crate::pipeline::context::metrics::FrameMetrics;
 use crate::pipeline::context::state::AnalyzedState;
 use crate::pipeline::context::state::IngestedState;
 use crate::pipeline::domain::scene_analysis::SceneAnalysis;
+use image::DynamicImage;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+// What one processing step did with a frame, in the order the steps ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepTrace {
+    pub step: &'static str,
+    pub outcome: String,
+    pub duration: Duration,
+}
 
 // FrameContext with compile-time state tracking via the phantom data
 pub struct FrameContext<S> {
     frame: Arc<Frame>,
     metrics: FrameMetrics,
     processing_start: Instant,
+    trace: Vec<StepTrace>,
+    // Fired when the frame is no longer worth finishing, steps check it between units of work.
+    cancel: CancellationToken,
     state: S,
 }
 
@@ -26,6 +39,27 @@ impl<S> FrameContext<S> {
     pub fn elapsed(&self) -> Duration {
         self.processing_start.elapsed()
     }
+
+    pub fn record_step(
+        &mut self,
+        step: &'static str,
+        outcome: impl Into<String>,
+        duration: Duration,
+    ) {
+        self.trace.push(StepTrace {
+            step,
+            outcome: outcome.into(),
+            duration,
+        });
+    }
+
+    pub fn trace(&self) -> &[StepTrace] {
+        &self.trace
+    }
+
+    pub fn cancel_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
 }
 
 impl FrameContext<IngestedState> {
@@ -34,16 +68,29 @@ impl FrameContext<IngestedState> {
             frame: Arc::new(frame),
             metrics: FrameMetrics::new(),
             processing_start: Instant::now(),
+            trace: Vec::new(),
+            cancel: CancellationToken::new(),
             state: IngestedState,
         }
     }
 
+    pub fn set_cancel_token(&mut self, cancel: CancellationToken) {
+        self.cancel = cancel;
+    }
+
+    // Swaps the frame image before analysis, the frame metadata is kept.
+    pub fn replace_image(&mut self, image: DynamicImage) {
+        self.frame = Arc::new(self.frame.with_image(image));
+    }
+
     pub fn into_analyzed(mut self, analysis: SceneAnalysis) -> FrameContext<AnalyzedState> {
         self.metrics.record_analysis_duration(self.elapsed());
         FrameContext::<AnalyzedState> {
             frame: self.frame,
             metrics: self.metrics,
             processing_start: self.processing_start,
+            trace: self.trace,
+            cancel: self.cancel,
             state: AnalyzedState { analysis },
         }
     }
@@ -1,4 +1,9 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use serde_json::json;
+
+// Smoothing factor for the exponentially weighted moving averages.
+const EWMA_ALPHA: f64 = 0.1;
 
 /// Metrics collected during frame processing
 pub struct FrameMetrics {
@@ -15,4 +20,188 @@ impl FrameMetrics {
     pub fn record_analysis_duration(&mut self, duration: Duration) {
         self.analysis_duration = Some(duration);
     }
+
+    pub fn analysis_duration(&self) -> Option<Duration> {
+        self.analysis_duration
+    }
+}
+
+/// Running timing statistics for a single measurement, in microseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingStats {
+    ewma_us: f64,
+    max_us: u64,
+}
+
+impl TimingStats {
+    pub fn record(&mut self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        self.ewma_us = if self.ewma_us == 0.0 {
+            micros as f64
+        } else {
+            EWMA_ALPHA * micros as f64 + (1.0 - EWMA_ALPHA) * self.ewma_us
+        };
+        self.max_us = self.max_us.max(micros);
+    }
+
+    pub fn ewma_us(&self) -> f64 {
+        self.ewma_us
+    }
+
+    pub fn max_us(&self) -> u64 {
+        self.max_us
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        json!({ "ewma": self.ewma_us, "max": self.max_us })
+    }
+}
+
+/// Cumulative timing of a single detector, used to find the slow ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DetectorTiming {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl DetectorTiming {
+    pub fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    pub fn avg(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+/// Aggregate performance statistics across all frames processed by a pipeline.
+#[derive(Debug, Clone, Default)]
+pub struct PerformanceStats {
+    frames_processed: u64,
+    frames_decimated: u64,
+    fps: f64,
+    analysis: TimingStats,
+    total_frame: TimingStats,
+    // Time from capture in the emulator until the pipeline finished with the frame.
+    capture_latency: TimingStats,
+    last_frame_at: Option<Instant>,
+}
+
+impl PerformanceStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, metrics: &FrameMetrics, total_frame: Duration) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame_at {
+            let interval = now.duration_since(last).as_secs_f64();
+            if interval > 0.0 {
+                let instant_fps = 1.0 / interval;
+                self.fps = if self.fps == 0.0 {
+                    instant_fps
+                } else {
+                    EWMA_ALPHA * instant_fps + (1.0 - EWMA_ALPHA) * self.fps
+                };
+            }
+        }
+        self.last_frame_at = Some(now);
+        self.frames_processed += 1;
+
+        if let Some(analysis) = metrics.analysis_duration() {
+            self.analysis.record(analysis);
+        }
+        self.total_frame.record(total_frame);
+    }
+
+    // Starts over from zero, e.g. to drop warmup outliers from the maxes and averages.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn record_decimated(&mut self) {
+        self.frames_decimated += 1;
+    }
+
+    pub fn record_capture_latency(&mut self, latency: Duration) {
+        self.capture_latency.record(latency);
+    }
+
+    pub fn frames_processed(&self) -> u64 {
+        self.frames_processed
+    }
+
+    // Frames skipped by the pipeline's FrameDecimator.
+    pub fn frames_decimated(&self) -> u64 {
+        self.frames_decimated
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn analysis(&self) -> TimingStats {
+        self.analysis
+    }
+
+    pub fn total_frame(&self) -> TimingStats {
+        self.total_frame
+    }
+
+    pub fn capture_latency(&self) -> TimingStats {
+        self.capture_latency
+    }
+
+    // Snapshot suitable for scraping by external dashboards.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "frames_processed": self.frames_processed,
+            "frames_decimated": self.frames_decimated,
+            "fps": self.fps,
+            "analysis_us": self.analysis.to_json(),
+            "total_frame_us": self.total_frame.to_json(),
+            "capture_latency_us": self.capture_latency.to_json(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_json_exposes_timings_and_fps() {
+        let mut stats = PerformanceStats::new();
+        let mut metrics = FrameMetrics::new();
+        metrics.record_analysis_duration(Duration::from_micros(1_500));
+        stats.record(&metrics, Duration::from_micros(2_000));
+        metrics.record_analysis_duration(Duration::from_micros(3_000));
+        stats.record(&metrics, Duration::from_micros(4_000));
+
+        let parsed: serde_json::Value = serde_json::from_str(&stats.to_json().to_string()).unwrap();
+        assert_eq!(parsed["frames_processed"], 2);
+        assert_eq!(parsed["analysis_us"]["max"], 3_000);
+        assert_eq!(parsed["total_frame_us"]["max"], 4_000);
+        assert!(parsed["analysis_us"]["ewma"].as_f64().unwrap() > 1_500.0);
+        assert!(parsed["fps"].as_f64().is_some());
+    }
 }
@@ -1,3 +1,5 @@
 pub mod frame_context;
 pub mod metrics;
+pub mod scene_smoother;
+pub mod scene_transitions;
 pub mod state;
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, VecDeque};
+
+use uuid::Uuid;
+
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+struct ClientScenes {
+    recent: VecDeque<SceneType>,
+    stable: SceneType,
+    candidate: SceneType,
+    streak: usize,
+}
+
+// Damps frame-to-frame flicker in scene classification. Each client keeps a window of its
+// recent raw scenes, and the reported scene only switches once a different scene has been
+// the most frequent one in that window for `switch_after` consecutive frames.
+pub struct SceneSmoother {
+    window: usize,
+    switch_after: usize,
+    clients: HashMap<Uuid, ClientScenes>,
+}
+
+impl SceneSmoother {
+    pub fn new(window: usize, switch_after: usize) -> Self {
+        Self {
+            window: window.max(1),
+            switch_after: switch_after.max(1),
+            clients: HashMap::new(),
+        }
+    }
+
+    // Records the raw scene of the client's latest frame and returns the smoothed scene.
+    pub fn smooth(&mut self, client_id: Uuid, scene: SceneType) -> SceneType {
+        let client = self.clients.entry(client_id).or_insert(ClientScenes {
+            recent: VecDeque::with_capacity(self.window),
+            stable: scene,
+            candidate: scene,
+            streak: 0,
+        });
+        if client.recent.len() == self.window {
+            client.recent.pop_front();
+        }
+        client.recent.push_back(scene);
+
+        let mode = Self::mode(&client.recent);
+        if mode == client.stable {
+            client.streak = 0;
+        } else {
+            if mode == client.candidate {
+                client.streak += 1;
+            } else {
+                client.candidate = mode;
+                client.streak = 1;
+            }
+            if client.streak >= self.switch_after {
+                client.stable = mode;
+                client.streak = 0;
+            }
+        }
+        client.stable
+    }
+
+    // Fraction of the client's window that agrees with its smoothed scene.
+    pub fn agreement(&self, client_id: Uuid) -> f32 {
+        let Some(client) = self.clients.get(&client_id) else {
+            return 0.0;
+        };
+        let agreeing = client
+            .recent
+            .iter()
+            .filter(|scene| **scene == client.stable)
+            .count();
+        agreeing as f32 / client.recent.len().max(1) as f32
+    }
+
+    // Most frequent scene in the window, ties go to the most recently seen one.
+    fn mode(recent: &VecDeque<SceneType>) -> SceneType {
+        let mut counts: Vec<(SceneType, usize)> = Vec::new();
+        for scene in recent.iter().rev() {
+            match counts.iter_mut().find(|(seen, _)| seen == scene) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((*scene, 1)),
+            }
+        }
+        counts
+            .into_iter()
+            .fold(
+                None,
+                |best: Option<(SceneType, usize)>, candidate| match best {
+                    Some(best) if best.1 >= candidate.1 => Some(best),
+                    _ => Some(candidate),
+                },
+            )
+            .map(|(scene, _)| scene)
+            .unwrap_or(SceneType::Unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use SceneType::{Battle, Overworld, Unknown};
+
+    #[test]
+    fn flicker_is_smoothed_away() {
+        let mut smoother = SceneSmoother::new(5, 3);
+        let client = Uuid::new_v4();
+        let noisy = [
+            Overworld, Overworld, Unknown, Overworld, Unknown, Overworld, Overworld, Unknown,
+        ];
+        for scene in noisy {
+            assert_eq!(smoother.smooth(client, scene), Overworld);
+        }
+    }
+
+    #[test]
+    fn switches_once_the_new_scene_dominates() {
+        let mut smoother = SceneSmoother::new(5, 2);
+        let client = Uuid::new_v4();
+        for _ in 0..5 {
+            smoother.smooth(client, Overworld);
+        }
+
+        let smoothed: Vec<_> = [Battle, Battle, Battle, Battle, Battle]
+            .into_iter()
+            .map(|scene| smoother.smooth(client, scene))
+            .collect();
+        assert_eq!(smoothed, [Overworld, Overworld, Overworld, Battle, Battle]);
+        assert_eq!(smoother.agreement(client), 1.0);
+    }
+
+    #[test]
+    fn clients_are_smoothed_independently() {
+        let mut smoother = SceneSmoother::new(3, 1);
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        smoother.smooth(first, Overworld);
+        assert_eq!(smoother.smooth(second, Battle), Battle);
+        assert_eq!(smoother.smooth(first, Overworld), Overworld);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use uuid::Uuid;
+
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+// Per-client history of the scenes a client passed through, bounded to the most recent
+// `capacity` scene changes. Unknown frames (fades, garbage frames) are not recorded so
+// that e.g. Battle -> Unknown -> Overworld still counts as leaving a battle.
+pub struct SceneTransitionTracker {
+    capacity: usize,
+    histories: HashMap<Uuid, VecDeque<(SceneType, Instant)>>,
+}
+
+impl SceneTransitionTracker {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(2),
+            histories: HashMap::new(),
+        }
+    }
+
+    // Records the scene seen at `at`, returning the transition if the scene changed.
+    pub fn record(
+        &mut self,
+        client_id: Uuid,
+        scene: SceneType,
+        at: Instant,
+    ) -> Option<(SceneType, SceneType)> {
+        if scene == SceneType::Unknown {
+            return None;
+        }
+        let history = self.histories.entry(client_id).or_default();
+        let previous = history.back().map(|(previous, _)| *previous);
+        if previous == Some(scene) {
+            return None;
+        }
+        if history.len() == self.capacity {
+            history.pop_front();
+        }
+        history.push_back((scene, at));
+        previous.map(|previous| (previous, scene))
+    }
+
+    pub fn last_transition(&self, client_id: Uuid) -> Option<(SceneType, SceneType)> {
+        let history = self.histories.get(&client_id)?;
+        let mut recent = history.iter().rev();
+        let (to, _) = recent.next()?;
+        let (from, _) = recent.next()?;
+        Some((*from, *to))
+    }
+
+    pub fn count_transitions(&self, client_id: Uuid, from: SceneType, to: SceneType) -> usize {
+        let Some(history) = self.histories.get(&client_id) else {
+            return 0;
+        };
+        history
+            .iter()
+            .zip(history.iter().skip(1))
+            .filter(|((previous, _), (next, _))| *previous == from && *next == to)
+            .count()
+    }
+
+    // Scenes the client passed through, oldest first, with when each was entered.
+    pub fn history(&self, client_id: Uuid) -> impl Iterator<Item = &(SceneType, Instant)> {
+        self.histories.get(&client_id).into_iter().flatten()
+    }
+}
+
+impl Default for SceneTransitionTracker {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_transitions_per_client() {
+        let mut tracker = SceneTransitionTracker::new(16);
+        let client = Uuid::new_v4();
+        let now = Instant::now();
+        let scenes = [
+            SceneType::Overworld,
+            SceneType::Overworld,
+            SceneType::Battle,
+            SceneType::Unknown,
+            SceneType::Overworld,
+            SceneType::Battle,
+            SceneType::Overworld,
+            SceneType::Menu,
+        ];
+        for scene in scenes {
+            tracker.record(client, scene, now);
+        }
+
+        assert_eq!(
+            tracker.count_transitions(client, SceneType::Battle, SceneType::Overworld),
+            2
+        );
+        assert_eq!(
+            tracker.count_transitions(client, SceneType::Overworld, SceneType::Battle),
+            2
+        );
+        assert_eq!(
+            tracker.last_transition(client),
+            Some((SceneType::Overworld, SceneType::Menu))
+        );
+        assert_eq!(tracker.last_transition(Uuid::new_v4()), None);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut tracker = SceneTransitionTracker::new(3);
+        let client = Uuid::new_v4();
+        for scene in [
+            SceneType::Battle,
+            SceneType::Overworld,
+            SceneType::Battle,
+            SceneType::Overworld,
+        ] {
+            tracker.record(client, scene, Instant::now());
+        }
+        assert_eq!(tracker.history(client).count(), 3);
+        assert_eq!(
+            tracker.count_transitions(client, SceneType::Battle, SceneType::Overworld),
+            1
+        );
+    }
+}
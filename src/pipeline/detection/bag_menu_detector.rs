@@ -0,0 +1,120 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{column_edges, listed_rows, luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+const LIST_ROWS: u32 = 8;
+
+// The bag shows its pocket tabs stacked down the left edge and the current pocket's items as
+// a light list on the right, each row an item name with its quantity right-aligned. Items
+// fill the list from the top, so the rows with text must be contiguous.
+pub struct BagMenuDetector {
+    min_list_fill: f32,
+    min_text_edges: f32,
+    min_tab_edges: usize,
+    thresholds: ColorThresholds,
+}
+
+impl BagMenuDetector {
+    pub fn new() -> Self {
+        Self {
+            min_list_fill: 0.6,
+            min_text_edges: 0.03,
+            min_tab_edges: 4,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    // Counts the visible item rows, returns None when the frame doesn't look like the bag.
+    pub fn count_items(&self, image: &RgbImage) -> Option<u8> {
+        let (width, height) = image.dimensions();
+        if height / LIST_ROWS < 4 || width < 40 {
+            return None;
+        }
+        let list_x = width * 2 / 5;
+        let quantity_x = width - width / 6;
+
+        if pixel_ratio(image, list_x..width, 0..height, |p| {
+            luma(p) >= self.thresholds.menu_slot_luma
+        }) < self.min_list_fill
+        {
+            return None;
+        }
+
+        // Walking down the middle of the tab column crosses a border between every pocket.
+        if column_edges(image, width / 10, 0..height) < self.min_tab_edges {
+            return None;
+        }
+
+        listed_rows(
+            image,
+            list_x..quantity_x,
+            quantity_x..width,
+            LIST_ROWS,
+            self.min_text_edges,
+        )
+    }
+}
+
+impl Default for BagMenuDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for BagMenuDetector {
+    fn name(&self) -> &'static str {
+        "bag_menu"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        self.count_items(image)?;
+        Some(SceneAnalysis::new(SceneType::Bag, 0.85))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+
+    #[test]
+    fn counts_listed_items() {
+        let detector = BagMenuDetector::new();
+        for items in [1, 4, 8] {
+            let image = SyntheticFrame::bag(items).build();
+            assert_eq!(detector.count_items(&image), Some(items as u8));
+            assert_eq!(
+                detector.detect(&image).unwrap().scene_type(),
+                SceneType::Bag
+            );
+        }
+    }
+
+    #[test]
+    fn empty_pockets_and_other_menus_are_not_the_bag() {
+        let detector = BagMenuDetector::new();
+        assert!(detector.detect(&SyntheticFrame::bag(0).build()).is_none());
+        assert!(detector.detect(&SyntheticFrame::party(6).build()).is_none());
+        assert!(
+            detector
+                .detect(&SyntheticFrame::trainer_card(8).build())
+                .is_none()
+        );
+        let white = RgbImage::from_pixel(256, 192, Rgb([255, 255, 255]));
+        assert!(detector.detect(&white).is_none());
+    }
+}
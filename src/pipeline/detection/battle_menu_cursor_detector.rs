@@ -0,0 +1,139 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{luma, pixel_ratio};
+use crate::pipeline::domain::scene_analysis::BattleMenuSelection;
+
+// The battle menu is a light 2x2 panel in the bottom-right corner. The cursor
+// is a dark arrow in the left strip of whichever quadrant is selected, so the
+// quadrant whose strip is clearly the darkest is the selection.
+pub struct BattleMenuCursorDetector {
+    min_panel_light_ratio: f32,
+    min_cursor_ratio: f32,
+    thresholds: ColorThresholds,
+}
+
+impl BattleMenuCursorDetector {
+    const QUADRANTS: [BattleMenuSelection; 4] = [
+        BattleMenuSelection::Fight,
+        BattleMenuSelection::Bag,
+        BattleMenuSelection::Pokemon,
+        BattleMenuSelection::Run,
+    ];
+
+    pub fn new() -> Self {
+        Self {
+            min_panel_light_ratio: 0.5,
+            min_cursor_ratio: 0.05,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn detect(&self, image: &RgbImage) -> Option<BattleMenuSelection> {
+        let (width, height) = image.dimensions();
+        let panel_x = width / 2;
+        let panel_y = height * 2 / 3;
+        let quadrant_w = (width - panel_x) / 2;
+        let quadrant_h = (height - panel_y) / 2;
+        let strip_w = quadrant_w / 4;
+        if strip_w == 0 || quadrant_h == 0 {
+            return None;
+        }
+
+        let panel_light = pixel_ratio(image, panel_x..width, panel_y..height, |p| {
+            luma(p) >= self.thresholds.panel_luma
+        });
+        if panel_light < self.min_panel_light_ratio {
+            return None;
+        }
+
+        let mut scores = Self::QUADRANTS.map(|selection| {
+            let (column, row) = match selection {
+                BattleMenuSelection::Fight => (0, 0),
+                BattleMenuSelection::Bag => (1, 0),
+                BattleMenuSelection::Pokemon => (0, 1),
+                BattleMenuSelection::Run => (1, 1),
+            };
+            let x = panel_x + column * quadrant_w;
+            let y = panel_y + row * quadrant_h;
+            let dark = pixel_ratio(image, x..x + strip_w, y..y + quadrant_h, |p| {
+                luma(p) < self.thresholds.cursor_luma
+            });
+            (selection, dark)
+        });
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let (best, best_score) = scores[0];
+        let runner_up = scores[1].1;
+        (best_score >= self.min_cursor_ratio && runner_up < best_score * 0.5).then_some(best)
+    }
+}
+
+impl Default for BattleMenuCursorDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    const WIDTH: u32 = 160;
+    const HEIGHT: u32 = 144;
+
+    fn fill(image: &mut RgbImage, x: u32, y: u32, w: u32, h: u32, color: Rgb<u8>) {
+        for py in y..y + h {
+            for px in x..x + w {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+
+    // White menu panel with a label in every quadrant and the arrow next to `cursor`.
+    fn battle_menu_frame(cursor: (u32, u32)) -> RgbImage {
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([248, 248, 248]));
+        for (column, row) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let x = 80 + column * 40;
+            let y = 96 + row * 24;
+            fill(&mut image, x + 12, y + 9, 24, 6, Rgb([40, 40, 40]));
+            if (column, row) == cursor {
+                fill(&mut image, x + 3, y + 9, 4, 6, Rgb([16, 16, 16]));
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn reports_the_quadrant_under_the_cursor() {
+        let detector = BattleMenuCursorDetector::new();
+        let cases = [
+            ((0, 0), BattleMenuSelection::Fight),
+            ((1, 0), BattleMenuSelection::Bag),
+            ((0, 1), BattleMenuSelection::Pokemon),
+            ((1, 1), BattleMenuSelection::Run),
+        ];
+        for (cursor, expected) in cases {
+            assert_eq!(detector.detect(&battle_menu_frame(cursor)), Some(expected));
+        }
+    }
+
+    #[test]
+    fn no_cursor_yields_none() {
+        let image = battle_menu_frame((9, 9));
+        assert_eq!(BattleMenuCursorDetector::new().detect(&image), None);
+    }
+
+    #[test]
+    fn dark_frame_yields_none() {
+        let image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([0, 0, 0]));
+        assert_eq!(BattleMenuCursorDetector::new().detect(&image), None);
+    }
+}
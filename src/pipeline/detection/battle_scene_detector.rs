@@ -0,0 +1,81 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::battle_menu_cursor_detector::BattleMenuCursorDetector;
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Recognizes a battle waiting for a command by the light 2x2 command panel with the arrow on
+// one of its entries. Battle animations and messages without the panel are left to the others.
+pub struct BattleSceneDetector {
+    cursor: BattleMenuCursorDetector,
+}
+
+impl BattleSceneDetector {
+    pub fn new() -> Self {
+        Self {
+            cursor: BattleMenuCursorDetector::new(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.cursor = self.cursor.with_color_thresholds(thresholds);
+        self
+    }
+}
+
+impl Default for BattleSceneDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for BattleSceneDetector {
+    fn name(&self) -> &'static str {
+        "battle"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let selection = self.cursor.detect(image)?;
+        Some(
+            SceneAnalysis::new(SceneType::Battle, 0.85).with_battle_menu_selection(Some(selection)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+    use crate::pipeline::domain::scene_analysis::BattleMenuSelection;
+
+    #[test]
+    fn command_panels_are_battles() {
+        let frame = SyntheticFrame::battle_menu(BattleMenuSelection::Run).build();
+        let analysis = BattleSceneDetector::new().detect(&frame).unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Battle);
+        assert_eq!(
+            analysis.battle_menu_selection(),
+            Some(BattleMenuSelection::Run)
+        );
+    }
+
+    #[test]
+    fn menus_and_blank_screens_are_not_battles() {
+        let detector = BattleSceneDetector::new();
+        for frame in [
+            SyntheticFrame::bag(8),
+            SyntheticFrame::shop(8),
+            SyntheticFrame::party(6),
+            SyntheticFrame::fade(Rgb([255, 255, 255])),
+        ] {
+            assert!(detector.detect(&frame.build()).is_none());
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// How strongly a border darker than `dark_luma` frames a lighter center, from 0.0 to 1.0.
+// Cave tilesets wall off the walkable floor with dark rock, while a dim night
+// overworld is dark everywhere and scores close to zero.
+pub fn is_enclosed_dark(image: &RgbImage, dark_luma: u8) -> f32 {
+    let (width, height) = image.dimensions();
+    let border_w = width * 15 / 100;
+    let border_h = height * 15 / 100;
+    if border_w == 0 || border_h == 0 {
+        return 0.0;
+    }
+
+    let mut border_pixels = 0usize;
+    let mut border_dark = 0usize;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let in_border =
+            x < border_w || x >= width - border_w || y < border_h || y >= height - border_h;
+        if in_border {
+            border_pixels += 1;
+            if luma(pixel) < dark_luma {
+                border_dark += 1;
+            }
+        }
+    }
+    let border_ratio = border_dark as f32 / border_pixels as f32;
+    let center_ratio = pixel_ratio(
+        image,
+        width / 4..width * 3 / 4,
+        height / 4..height * 3 / 4,
+        |p| luma(p) < dark_luma,
+    );
+    (border_ratio - center_ratio).clamp(0.0, 1.0)
+}
+
+// Recognizes cave interiors as an overworld scene by combining overall darkness
+// with the dark-border/lighter-center framing.
+pub struct CaveSceneDetector {
+    min_dark_ratio: f32,
+    min_enclosure: f32,
+    thresholds: ColorThresholds,
+}
+
+impl CaveSceneDetector {
+    pub fn new() -> Self {
+        Self {
+            min_dark_ratio: 0.35,
+            min_enclosure: 0.4,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+}
+
+impl Default for CaveSceneDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for CaveSceneDetector {
+    fn name(&self) -> &'static str {
+        "cave"
+    }
+
+    fn priority(&self) -> u8 {
+        40
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (width, height) = image.dimensions();
+        let rock_luma = self.thresholds.rock_luma;
+        let dark = pixel_ratio(image, 0..width, 0..height, |p| luma(p) < rock_luma);
+        if dark < self.min_dark_ratio {
+            return None;
+        }
+        let enclosure = is_enclosed_dark(image, rock_luma);
+        if enclosure < self.min_enclosure {
+            return None;
+        }
+        let confidence = (0.6 + 0.35 * enclosure).min(0.95);
+        Some(SceneAnalysis::new(SceneType::Overworld, confidence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    const WIDTH: u32 = 256;
+    const HEIGHT: u32 = 192;
+
+    fn cave_frame() -> RgbImage {
+        RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            let rock = !(40..WIDTH - 40).contains(&x) || !(40..HEIGHT - 40).contains(&y);
+            if rock {
+                Rgb([12, 10, 8])
+            } else {
+                Rgb([110, 90, 70])
+            }
+        })
+    }
+
+    fn night_overworld_frame() -> RgbImage {
+        RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            if (x / 16 + y / 16) % 2 == 0 {
+                Rgb([20, 30, 50])
+            } else {
+                Rgb([30, 45, 40])
+            }
+        })
+    }
+
+    #[test]
+    fn detects_enclosed_cave() {
+        let analysis = CaveSceneDetector::new().detect(&cave_frame()).unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Overworld);
+        assert!(analysis.confidence() >= 0.9);
+    }
+
+    #[test]
+    fn stricter_rock_threshold_rejects_dim_gray_walls() {
+        let dim_walls = RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+            let rock = !(40..WIDTH - 40).contains(&x) || !(40..HEIGHT - 40).contains(&y);
+            if rock {
+                Rgb([50, 50, 50])
+            } else {
+                Rgb([110, 90, 70])
+            }
+        });
+        assert!(CaveSceneDetector::new().detect(&dim_walls).is_some());
+
+        let strict = CaveSceneDetector::new().with_color_thresholds(ColorThresholds {
+            rock_luma: 40,
+            ..ColorThresholds::default()
+        });
+        assert!(strict.detect(&dim_walls).is_none());
+        assert!(strict.detect(&cave_frame()).is_some());
+    }
+
+    #[test]
+    fn dark_night_overworld_is_not_a_cave() {
+        let frame = night_overworld_frame();
+        assert!(is_enclosed_dark(&frame, ColorThresholds::default().rock_luma) < 0.1);
+        assert!(CaveSceneDetector::new().detect(&frame).is_none());
+    }
+}
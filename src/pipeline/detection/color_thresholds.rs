@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+// Luma and saturation cut-offs the pixel-counting detectors classify pixels with. Palettes
+// shift between ROM releases, emulator filters and colorblind modes, so they are tuned here
+// in one place and handed to every detector by the GameRegistry. Missing keys in a
+// configuration file keep the defaults below.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorThresholds {
+    // Cave rock and other unlit scenery is darker than this.
+    pub rock_luma: u8,
+    // Menu backgrounds, e.g. behind the party slots, are darker than this.
+    pub menu_background_luma: u8,
+    // Filled party slots are at least this bright.
+    pub menu_slot_luma: u8,
+    // The battle command panel is at least this bright.
+    pub panel_luma: u8,
+    // The battle menu arrow is darker than this.
+    pub cursor_luma: u8,
+    // Evolution flashes and glowing sprites are at least this bright.
+    pub flash_luma: u8,
+    // Earned badges are at least this saturated.
+    pub badge_saturation: f32,
+    // Empty badge slots are gray: less saturated than this, with a luma in `gray_luma`.
+    pub gray_saturation: f32,
+    pub gray_luma: (u8, u8),
+}
+
+impl Default for ColorThresholds {
+    fn default() -> Self {
+        Self {
+            rock_luma: 60,
+            menu_background_luma: 100,
+            menu_slot_luma: 150,
+            panel_luma: 160,
+            cursor_luma: 80,
+            flash_luma: 230,
+            badge_saturation: 0.4,
+            gray_saturation: 0.15,
+            gray_luma: (60, 200),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use crate::error::AppError;
+
+// Monotonic piecewise-linear remapping of a detector's raw confidence, given as (raw,
+// calibrated) points. Confidences between two points are interpolated, outside the points the
+// nearest one's calibrated value holds. Lets an over-confident detector be toned down without
+// touching its code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfidenceCurve {
+    points: Vec<(f32, f32)>,
+}
+
+impl ConfidenceCurve {
+    // Points are sorted by raw confidence, the calibrated values must not decrease along them.
+    pub fn new(mut points: Vec<(f32, f32)>) -> Result<Self, AppError> {
+        if points.is_empty() {
+            return Err(AppError::Config(
+                "A confidence curve needs at least one point".to_string(),
+            ));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if points.windows(2).any(|pair| pair[1].1 < pair[0].1) {
+            return Err(AppError::Config(
+                "Confidence curve points must not decrease".to_string(),
+            ));
+        }
+        Ok(Self { points })
+    }
+
+    // Multiplies every confidence by `factor`.
+    pub fn scaled(factor: f32) -> Self {
+        Self {
+            points: vec![(0.0, 0.0), (1.0, factor.max(0.0))],
+        }
+    }
+
+    pub fn apply(&self, raw: f32) -> f32 {
+        let upper = self.points.partition_point(|(x, _)| *x < raw);
+        match (upper.checked_sub(1), self.points.get(upper)) {
+            (Some(lower), Some(&(x1, y1))) => {
+                let (x0, y0) = self.points[lower];
+                if x1 == x0 {
+                    y1
+                } else {
+                    y0 + (y1 - y0) * (raw - x0) / (x1 - x0)
+                }
+            }
+            (None, Some(&(_, y))) => y,
+            (Some(lower), None) => self.points[lower].1,
+            (None, None) => raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_between_points_and_clamps_outside() {
+        let curve = ConfidenceCurve::new(vec![(0.9, 0.8), (0.5, 0.2)]).unwrap();
+        assert_eq!(curve.apply(0.0), 0.2);
+        assert!((curve.apply(0.7) - 0.5).abs() < 1e-6);
+        assert_eq!(curve.apply(0.9), 0.8);
+        assert_eq!(curve.apply(1.0), 0.8);
+        assert!((ConfidenceCurve::scaled(0.5).apply(0.6) - 0.3).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_curves_that_are_not_monotonic() {
+        assert!(ConfidenceCurve::new(vec![]).is_err());
+        assert!(ConfidenceCurve::new(vec![(0.2, 0.6), (0.8, 0.4)]).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use image::{Rgb, RgbImage};
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// During evolution the Pokemon glows white in the middle of an otherwise dark screen while its
+// sprite morphs. A full-screen flash is a fade, so the screen edges have to stay dark.
+pub struct EvolutionDetector {
+    min_glow: f32,
+    min_dark_edges: f32,
+    thresholds: ColorThresholds,
+}
+
+impl EvolutionDetector {
+    pub fn new() -> Self {
+        Self {
+            min_glow: 0.4,
+            min_dark_edges: 0.8,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+}
+
+impl Default for EvolutionDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for EvolutionDetector {
+    fn name(&self) -> &'static str {
+        "evolution"
+    }
+
+    fn priority(&self) -> u8 {
+        85
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (width, height) = image.dimensions();
+        if width < 6 || height < 6 {
+            return None;
+        }
+        let glow = pixel_ratio(
+            image,
+            width / 3..width * 2 / 3,
+            height / 3..height * 2 / 3,
+            |p| luma(p) >= self.thresholds.flash_luma,
+        );
+        let is_dark = |p: &Rgb<u8>| luma(p) < self.thresholds.rock_luma;
+        let dark_edges = pixel_ratio(image, 0..width / 6, 0..height, is_dark).min(pixel_ratio(
+            image,
+            width - width / 6..width,
+            0..height,
+            is_dark,
+        ));
+        // The dark frame around a lit center also reads as a cave, but no cave floor glows, so
+        // this has to outrank even a fully enclosed cave.
+        (glow >= self.min_glow && dark_edges >= self.min_dark_edges)
+            .then(|| SceneAnalysis::new(SceneType::Evolution, 0.95))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+
+    #[test]
+    fn a_glowing_sprite_on_black_is_an_evolution() {
+        let analysis = EvolutionDetector::new()
+            .detect(&SyntheticFrame::evolution().build())
+            .unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Evolution);
+    }
+
+    #[test]
+    fn fades_caves_and_title_screens_are_not_evolutions() {
+        let detector = EvolutionDetector::new();
+        for frame in [
+            SyntheticFrame::fade(Rgb([255, 255, 255])),
+            SyntheticFrame::fade(Rgb([0, 0, 0])),
+            SyntheticFrame::cave(),
+            SyntheticFrame::title_screen(),
+        ] {
+            assert!(detector.detect(&frame.build()).is_none());
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::image_stats::luma;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Recognizes the near-uniform black or white frames of a fade, e.g. the whiteout after
+// the whole party faints. Without it those frames read as a dark cave or as nothing.
+pub struct FadeScreenDetector {
+    max_dark_mean: f32,
+    min_light_mean: f32,
+    max_std_dev: f32,
+}
+
+impl FadeScreenDetector {
+    pub fn new() -> Self {
+        Self {
+            max_dark_mean: 16.0,
+            min_light_mean: 239.0,
+            max_std_dev: 6.0,
+        }
+    }
+}
+
+impl Default for FadeScreenDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for FadeScreenDetector {
+    fn name(&self) -> &'static str {
+        "fade_screen"
+    }
+
+    fn priority(&self) -> u8 {
+        90
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let pixels = image.pixels().len();
+        if pixels == 0 {
+            return None;
+        }
+        let (sum, sum_sq) = image.pixels().fold((0f64, 0f64), |(sum, sum_sq), pixel| {
+            let value = luma(pixel) as f64;
+            (sum + value, sum_sq + value * value)
+        });
+        let mean = sum / pixels as f64;
+        let std_dev = (sum_sq / pixels as f64 - mean * mean).max(0.0).sqrt() as f32;
+        let mean = mean as f32;
+
+        let faded = mean <= self.max_dark_mean || mean >= self.min_light_mean;
+        (faded && std_dev <= self.max_std_dev)
+            .then(|| SceneAnalysis::new(SceneType::FadeTransition, 0.9))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    fn noisy(base: u8) -> RgbImage {
+        RgbImage::from_fn(256, 192, |x, y| {
+            let value = base.saturating_add(((x * 7 + y * 13) % 5) as u8);
+            Rgb([value, value, value])
+        })
+    }
+
+    #[test]
+    fn near_uniform_white_and_black_frames_are_fades() {
+        let detector = FadeScreenDetector::new();
+        for frame in [noisy(250), noisy(2)] {
+            let analysis = detector.detect(&frame).unwrap();
+            assert_eq!(analysis.scene_type(), SceneType::FadeTransition);
+        }
+    }
+
+    #[test]
+    fn normal_frames_are_not_fades() {
+        let detector = FadeScreenDetector::new();
+        let overworld = RgbImage::from_fn(256, 192, |x, y| {
+            if (x / 16 + y / 16) % 2 == 0 {
+                Rgb([40, 160, 60])
+            } else {
+                Rgb([200, 190, 120])
+            }
+        });
+        assert!(detector.detect(&overworld).is_none());
+        assert!(detector.detect(&noisy(128)).is_none());
+    }
+}
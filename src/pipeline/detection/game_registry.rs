@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::pipeline::detection::bag_menu_detector::BagMenuDetector;
+use crate::pipeline::detection::battle_scene_detector::BattleSceneDetector;
+use crate::pipeline::detection::cave_scene_detector::CaveSceneDetector;
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::evolution_detector::EvolutionDetector;
+use crate::pipeline::detection::fade_screen_detector::FadeScreenDetector;
+use crate::pipeline::detection::mario_level_detector::MarioLevelDetector;
+use crate::pipeline::detection::move_learn_detector::MoveLearnDetector;
+use crate::pipeline::detection::overworld_detector::OverworldDetector;
+use crate::pipeline::detection::party_screen_detector::PartyScreenDetector;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::detection::shop_detector::ShopDetector;
+use crate::pipeline::detection::title_screen_detector::TitleScreenDetector;
+use crate::pipeline::detection::trainer_card_detector::{BadgeGridLayout, TrainerCardDetector};
+
+const INES_MAGIC: &[u8] = b"NES\x1A";
+// The NDS header starts with a 12 byte upper-case game title.
+const NDS_TITLE_LEN: usize = 12;
+// The Pokemon trainer card's badge case: two rows of four along the bottom of the card.
+pub const POKEMON_BADGE_GRID: BadgeGridLayout = BadgeGridLayout {
+    left: 0.1,
+    top: 0.55,
+    right: 0.9,
+    bottom: 0.95,
+    rows: 2,
+    columns: 4,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameKind {
+    #[default]
+    Pokemon,
+    Mario,
+}
+
+impl GameKind {
+    pub fn from_rom_header(header: &[u8]) -> Option<GameKind> {
+        if header.starts_with(INES_MAGIC) {
+            return Some(GameKind::Mario);
+        }
+        let title = header.get(..NDS_TITLE_LEN)?;
+        String::from_utf8_lossy(title)
+            .starts_with("POKEMON")
+            .then_some(GameKind::Pokemon)
+    }
+
+    // Reads the ROM header, returns None when the game isn't recognized.
+    pub fn detect(rom_path: &Path) -> Result<Option<GameKind>, AppError> {
+        let mut header = [0u8; NDS_TITLE_LEN];
+        let read = File::open(rom_path)?.read(&mut header)?;
+        Ok(Self::from_rom_header(&header[..read]))
+    }
+}
+
+type DetectorFactory = fn(ColorThresholds) -> Vec<Box<dyn SceneDetector>>;
+
+// Maps each supported game to the scene detectors that understand its screens.
+pub struct GameRegistry {
+    factories: HashMap<GameKind, DetectorFactory>,
+    thresholds: ColorThresholds,
+}
+
+impl GameRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            factories: HashMap::new(),
+            thresholds: ColorThresholds::default(),
+        };
+        registry.register(GameKind::Pokemon, |thresholds| {
+            vec![
+                Box::new(TitleScreenDetector::new()),
+                Box::new(PartyScreenDetector::new().with_color_thresholds(thresholds)),
+                Box::new(CaveSceneDetector::new().with_color_thresholds(thresholds)),
+                Box::new(OverworldDetector::new().with_color_thresholds(thresholds)),
+                Box::new(
+                    TrainerCardDetector::new(POKEMON_BADGE_GRID).with_color_thresholds(thresholds),
+                ),
+                Box::new(BagMenuDetector::new().with_color_thresholds(thresholds)),
+                Box::new(BattleSceneDetector::new().with_color_thresholds(thresholds)),
+                Box::new(ShopDetector::new().with_color_thresholds(thresholds)),
+                Box::new(MoveLearnDetector::new().with_color_thresholds(thresholds)),
+                Box::new(EvolutionDetector::new().with_color_thresholds(thresholds)),
+                Box::new(FadeScreenDetector::new()),
+            ]
+        });
+        registry.register(GameKind::Mario, |_| {
+            vec![Box::new(MarioLevelDetector::new())]
+        });
+        registry
+    }
+
+    // Thresholds handed to every detector built from now on.
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn register(&mut self, kind: GameKind, factory: DetectorFactory) {
+        self.factories.insert(kind, factory);
+    }
+
+    pub fn detectors_for(&self, kind: GameKind) -> Vec<Box<dyn SceneDetector>> {
+        self.factories
+            .get(&kind)
+            .map(|factory| factory(self.thresholds))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for GameRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector_names(kind: GameKind) -> Vec<&'static str> {
+        GameRegistry::new()
+            .detectors_for(kind)
+            .iter()
+            .map(|detector| detector.name())
+            .collect()
+    }
+
+    #[test]
+    fn each_game_gets_its_own_detectors() {
+        assert_eq!(
+            detector_names(GameKind::Pokemon),
+            vec![
+                "title_screen",
+                "party_screen",
+                "cave",
+                "overworld",
+                "trainer_card",
+                "bag_menu",
+                "battle",
+                "shop",
+                "move_learn",
+                "evolution",
+                "fade_screen"
+            ]
+        );
+        assert_eq!(detector_names(GameKind::Mario), vec!["mario_level"]);
+    }
+
+    #[test]
+    fn recognizes_rom_headers() {
+        assert_eq!(
+            GameKind::from_rom_header(b"NES\x1A\x10\x10"),
+            Some(GameKind::Mario)
+        );
+        assert_eq!(
+            GameKind::from_rom_header(b"POKEMON B\0\0\0IRBO"),
+            Some(GameKind::Pokemon)
+        );
+        assert_eq!(GameKind::from_rom_header(b"SOMETHING ELSE"), None);
+    }
+}
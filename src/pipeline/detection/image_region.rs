@@ -0,0 +1,137 @@
+use std::ops::Range;
+
+use image::{GenericImageView, Rgb, RgbImage, SubImage};
+
+// Axis-aligned rectangle in pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageRegion {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full_image(image_width: u32, image_height: u32) -> Self {
+        Self::new(0, 0, image_width, image_height)
+    }
+
+    // The screen a game draws its main view on: the top screen of a DS frame, which arrives
+    // with both screens stacked (256x384), or the whole frame of a single-screen console.
+    pub fn main_screen(image_width: u32, image_height: u32) -> Self {
+        let stacked = image_height == image_width * 3 / 2;
+        let height = if stacked {
+            image_height / 2
+        } else {
+            image_height
+        };
+        Self::new(0, 0, image_width, height)
+    }
+
+    // Region given as fractions of the image size, e.g. (0.5, 0.5, 0.5, 0.5) is the bottom-right
+    // quarter. Fractions are clamped to 0.0..=1.0 and the result is clamped to the image.
+    pub fn from_fractions(
+        image_width: u32,
+        image_height: u32,
+        fx: f32,
+        fy: f32,
+        fw: f32,
+        fh: f32,
+    ) -> Self {
+        let scale = |size: u32, fraction: f32| (size as f32 * fraction.clamp(0.0, 1.0)) as u32;
+        Self::new(
+            scale(image_width, fx),
+            scale(image_height, fy),
+            scale(image_width, fw),
+            scale(image_height, fh),
+        )
+        .clamp_to(image_width, image_height)
+    }
+
+    // Shrinks the region so it lies inside an image of the given size. A region starting
+    // outside the image becomes empty.
+    pub fn clamp_to(self, image_width: u32, image_height: u32) -> Self {
+        let x = self.x.min(image_width);
+        let y = self.y.min(image_height);
+        Self::new(
+            x,
+            y,
+            self.width.min(image_width - x),
+            self.height.min(image_height - y),
+        )
+    }
+
+    pub fn area(&self) -> u32 {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.area() == 0
+    }
+
+    pub fn columns(&self) -> Range<u32> {
+        self.x..self.x.saturating_add(self.width)
+    }
+
+    pub fn rows(&self) -> Range<u32> {
+        self.y..self.y.saturating_add(self.height)
+    }
+
+    // Blacks out the region so detectors ignore whatever is drawn there, e.g. emulator overlays.
+    pub fn mask(&self, image: &mut RgbImage) {
+        let region = self.clamp_to(image.width(), image.height());
+        for y in region.rows() {
+            for x in region.columns() {
+                image.put_pixel(x, y, Rgb([0, 0, 0]));
+            }
+        }
+    }
+
+    // View of the region clamped to the image, without copying pixels.
+    pub fn crop<'a>(&self, image: &'a RgbImage) -> SubImage<&'a RgbImage> {
+        let region = self.clamp_to(image.width(), image.height());
+        image.view(region.x, region.y, region.width, region.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_regions_exceeding_the_image() {
+        let region = ImageRegion::new(120, 100, 80, 80).clamp_to(160, 144);
+        assert_eq!(region, ImageRegion::new(120, 100, 40, 44));
+        assert_eq!(region.area(), 40 * 44);
+        assert_eq!(region.columns(), 120..160);
+        assert_eq!(region.rows(), 100..144);
+
+        let outside = ImageRegion::new(200, 200, 10, 10).clamp_to(160, 144);
+        assert!(outside.is_empty());
+    }
+
+    #[test]
+    fn from_fractions_clamps_to_the_image() {
+        let region = ImageRegion::from_fractions(160, 144, 0.75, 0.5, 0.5, 1.5);
+        assert_eq!(region, ImageRegion::new(120, 72, 40, 72));
+    }
+
+    #[test]
+    fn crop_views_the_clamped_region() {
+        let mut image = RgbImage::from_pixel(160, 144, Rgb([0, 0, 0]));
+        image.put_pixel(150, 140, Rgb([255, 0, 0]));
+
+        let view = ImageRegion::new(140, 130, 40, 40).crop(&image);
+        assert_eq!(view.dimensions(), (20, 14));
+        assert_eq!(view.get_pixel(10, 10), Rgb([255, 0, 0]));
+    }
+}
@@ -0,0 +1,124 @@
+use std::ops::Range;
+
+use image::{DynamicImage, Rgb, RgbImage};
+
+// Luminance difference between horizontal neighbours that counts as an edge.
+const EDGE_LUMA_DELTA: i32 = 48;
+
+pub fn luma(pixel: &Rgb<u8>) -> u8 {
+    let [r, g, b] = pixel.0;
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+// Mean luma of each tile of a `side` x `side` grid over the image, row-major. Averaged exactly
+// rather than resized so a change in one tile doesn't bleed into its neighbours.
+pub fn luma_thumbnail(image: &DynamicImage, side: u32) -> Vec<u8> {
+    let side = side.max(1);
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let mut sums = vec![(0u64, 0u64); (side * side) as usize];
+    for (x, y, pixel) in luma.enumerate_pixels() {
+        let tile = (y * side / height.max(1)) * side + x * side / width.max(1);
+        let (sum, count) = &mut sums[tile as usize];
+        *sum += pixel.0[0] as u64;
+        *count += 1;
+    }
+    sums.into_iter()
+        .map(|(sum, count)| sum.checked_div(count).unwrap_or(0) as u8)
+        .collect()
+}
+
+// Fraction of pixels in the given rows/columns that sit on a sharp horizontal luminance edge.
+// Ranges are clamped to the image, an empty area yields 0.0.
+pub fn edge_density(image: &RgbImage, columns: Range<u32>, rows: Range<u32>) -> f32 {
+    let (columns, rows) = clamp_to_image(image, columns, rows);
+    if columns.len() < 2 || rows.is_empty() {
+        return 0.0;
+    }
+
+    let mut edges = 0usize;
+    for y in rows.clone() {
+        for x in columns.start + 1..columns.end {
+            let left = luma(image.get_pixel(x - 1, y)) as i32;
+            let right = luma(image.get_pixel(x, y)) as i32;
+            if (left - right).abs() > EDGE_LUMA_DELTA {
+                edges += 1;
+            }
+        }
+    }
+    edges as f32 / ((columns.len() - 1) * rows.len()) as f32
+}
+
+// Number of sharp vertical luminance edges met walking down `column` through `rows`.
+// Ranges are clamped to the image, a column outside it yields 0.
+pub fn column_edges(image: &RgbImage, column: u32, rows: Range<u32>) -> usize {
+    let (columns, rows) = clamp_to_image(image, column..column + 1, rows);
+    if columns.is_empty() || rows.len() < 2 {
+        return 0;
+    }
+    (rows.start + 1..rows.end)
+        .filter(|&y| {
+            let above = luma(image.get_pixel(column, y - 1)) as i32;
+            let below = luma(image.get_pixel(column, y)) as i32;
+            (above - below).abs() > EDGE_LUMA_DELTA
+        })
+        .count()
+}
+
+// Fraction of pixels in the given rows/columns that satisfy `predicate`.
+// Ranges are clamped to the image, an empty area yields 0.0.
+pub fn pixel_ratio(
+    image: &RgbImage,
+    columns: Range<u32>,
+    rows: Range<u32>,
+    predicate: impl Fn(&Rgb<u8>) -> bool,
+) -> f32 {
+    let (columns, rows) = clamp_to_image(image, columns, rows);
+    if columns.is_empty() || rows.is_empty() {
+        return 0.0;
+    }
+
+    let mut matching = 0usize;
+    for y in rows.clone() {
+        for x in columns.clone() {
+            if predicate(image.get_pixel(x, y)) {
+                matching += 1;
+            }
+        }
+    }
+    matching as f32 / (columns.len() * rows.len()) as f32
+}
+
+// Number of list rows, out of `rows` equal bands down the image, with text edges in both the
+// `text` and the right-aligned `number` columns, like an item name and its quantity or price.
+// Lists fill from the top, so None unless the listed rows are contiguous from the first one.
+pub fn listed_rows(
+    image: &RgbImage,
+    text: Range<u32>,
+    number: Range<u32>,
+    rows: u32,
+    min_edges: f32,
+) -> Option<u8> {
+    let row_h = image.height() / rows.max(1);
+    let listed: Vec<bool> = (0..rows)
+        .map(|row| {
+            let band = row * row_h..(row + 1) * row_h;
+            edge_density(image, text.clone(), band.clone()) >= min_edges
+                && edge_density(image, number.clone(), band) >= min_edges
+        })
+        .collect();
+
+    let count = listed.iter().take_while(|listed| **listed).count();
+    (count > 0 && listed[count..].iter().all(|listed| !listed)).then_some(count as u8)
+}
+
+fn clamp_to_image(
+    image: &RgbImage,
+    columns: Range<u32>,
+    rows: Range<u32>,
+) -> (Range<u32>, Range<u32>) {
+    (
+        columns.start..columns.end.min(image.width()),
+        rows.start..rows.end.min(image.height()),
+    )
+}
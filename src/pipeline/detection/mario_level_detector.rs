@@ -0,0 +1,38 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::image_stats::pixel_ratio;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Stub platformer detector: Mario levels are dominated by a light blue sky in the upper half.
+pub struct MarioLevelDetector {
+    min_sky_ratio: f32,
+}
+
+impl MarioLevelDetector {
+    pub fn new() -> Self {
+        Self { min_sky_ratio: 0.4 }
+    }
+}
+
+impl Default for MarioLevelDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for MarioLevelDetector {
+    fn name(&self) -> &'static str {
+        "mario_level"
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (width, height) = image.dimensions();
+        let sky = pixel_ratio(image, 0..width, 0..height / 2, |p| {
+            let [r, g, b] = p.0;
+            r < 160 && g > 140 && b > 200
+        });
+        (sky >= self.min_sky_ratio)
+            .then(|| SceneAnalysis::new(SceneType::Overworld, (0.6 + sky * 0.3).min(0.9)))
+    }
+}
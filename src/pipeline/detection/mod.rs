@@ -0,0 +1,22 @@
+pub mod bag_menu_detector;
+pub mod battle_menu_cursor_detector;
+pub mod battle_scene_detector;
+pub mod cave_scene_detector;
+pub mod color_thresholds;
+pub mod confidence_curve;
+pub mod evolution_detector;
+pub mod fade_screen_detector;
+pub mod game_registry;
+pub mod image_region;
+pub mod image_stats;
+pub mod mario_level_detector;
+pub mod move_learn_detector;
+pub mod overworld_detector;
+pub mod overworld_kind_classifier;
+pub mod party_screen_detector;
+pub mod scene_detector;
+pub mod shop_detector;
+#[cfg(test)]
+pub mod synthetic_frames;
+pub mod title_screen_detector;
+pub mod trainer_card_detector;
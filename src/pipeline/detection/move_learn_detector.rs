@@ -0,0 +1,149 @@
+use image::{Rgb, RgbImage};
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{edge_density, luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Four known moves and the new one.
+const MOVE_ROWS: u32 = 5;
+
+// The move-learn prompt lists the Pokemon's four moves and, below them, the move it wants to
+// learn in a differently colored row, all as light labelled rows on the right half of a dark
+// background. The Pokemon itself is shown on a panel on the left.
+pub struct MoveLearnDetector {
+    min_row_fill: f32,
+    min_text_edges: f32,
+    min_new_move_contrast: f32,
+    thresholds: ColorThresholds,
+}
+
+impl MoveLearnDetector {
+    pub fn new() -> Self {
+        Self {
+            min_row_fill: 0.5,
+            min_text_edges: 0.03,
+            min_new_move_contrast: 40.0,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    fn mean_color(
+        image: &RgbImage,
+        columns: std::ops::Range<u32>,
+        rows: std::ops::Range<u32>,
+    ) -> [f32; 3] {
+        let mut sums = [0u64; 3];
+        let mut count = 0u64;
+        for y in rows {
+            for x in columns.clone() {
+                let Rgb(channels) = image.get_pixel(x, y);
+                for (sum, channel) in sums.iter_mut().zip(channels) {
+                    *sum += *channel as u64;
+                }
+                count += 1;
+            }
+        }
+        sums.map(|sum| sum as f32 / count.max(1) as f32)
+    }
+}
+
+impl Default for MoveLearnDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for MoveLearnDetector {
+    fn name(&self) -> &'static str {
+        "move_learn"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (width, height) = image.dimensions();
+        let row_h = height / MOVE_ROWS;
+        if row_h < 8 || width < 40 {
+            return None;
+        }
+        let list_x = width / 2;
+        let is_dark = |p: &Rgb<u8>| luma(p) < self.thresholds.menu_background_luma;
+        let is_light = |p: &Rgb<u8>| luma(p) >= self.thresholds.menu_slot_luma;
+
+        // Dark background between the rows, every row light and labelled.
+        for row in 1..MOVE_ROWS {
+            let gap = row * row_h;
+            if pixel_ratio(image, list_x..width, gap..gap + 1, is_dark) < 0.6 {
+                return None;
+            }
+        }
+        let inset_y = row_h / 4;
+        let labelled = (0..MOVE_ROWS).all(|row| {
+            let rows = row * row_h + inset_y..(row + 1) * row_h - inset_y;
+            pixel_ratio(image, list_x..width, rows.clone(), is_light) >= self.min_row_fill
+                && edge_density(image, list_x..width, rows) >= self.min_text_edges
+        });
+        if !labelled {
+            return None;
+        }
+
+        // The new move stands out from the known ones.
+        let row_color = |row: u32| {
+            Self::mean_color(
+                image,
+                list_x..width,
+                row * row_h + inset_y..(row + 1) * row_h - inset_y,
+            )
+        };
+        let (known, new) = (row_color(0), row_color(MOVE_ROWS - 1));
+        let contrast = known
+            .iter()
+            .zip(new)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        if contrast < self.min_new_move_contrast {
+            return None;
+        }
+
+        // Something is drawn on the Pokemon's panel.
+        if edge_density(image, 0..list_x, 0..height) < self.min_text_edges / 3.0 {
+            return None;
+        }
+        Some(SceneAnalysis::new(SceneType::MoveLearn, 0.85))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+    use crate::pipeline::domain::scene_analysis::BattleMenuSelection;
+
+    #[test]
+    fn recognizes_the_move_learn_prompt() {
+        let analysis = MoveLearnDetector::new()
+            .detect(&SyntheticFrame::move_learn().build())
+            .unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::MoveLearn);
+    }
+
+    #[test]
+    fn ordinary_menus_and_battles_are_not_move_prompts() {
+        let detector = MoveLearnDetector::new();
+        for frame in [
+            SyntheticFrame::party(5),
+            SyntheticFrame::bag(5),
+            SyntheticFrame::battle_menu(BattleMenuSelection::Fight),
+        ] {
+            assert!(detector.detect(&frame.build()).is_none());
+        }
+    }
+}
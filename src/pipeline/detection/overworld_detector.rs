@@ -0,0 +1,82 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::overworld_kind_classifier::OverworldKindClassifier;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Recognizes the overworld outside caves by a single lit ground, grass, water or floor,
+// covering most of the frame. Menus drawn over the overworld cover enough of it to fall
+// short, and at equal confidence their detectors outrank this one.
+pub struct OverworldDetector {
+    classifier: OverworldKindClassifier,
+    min_ground_ratio: f32,
+}
+
+impl OverworldDetector {
+    pub fn new() -> Self {
+        Self {
+            classifier: OverworldKindClassifier::new(),
+            min_ground_ratio: 0.5,
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.classifier = self.classifier.with_color_thresholds(thresholds);
+        self
+    }
+}
+
+impl Default for OverworldDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for OverworldDetector {
+    fn name(&self) -> &'static str {
+        "overworld"
+    }
+
+    fn priority(&self) -> u8 {
+        30
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (_, ratio) = self.classifier.dominant_ground(image)?;
+        (ratio >= self.min_ground_ratio)
+            .then(|| SceneAnalysis::new(SceneType::Overworld, (0.5 + 0.4 * ratio).min(0.85)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::{HEIGHT, SyntheticFrame, WIDTH};
+
+    #[test]
+    fn grass_and_water_are_overworld() {
+        let detector = OverworldDetector::new();
+        for ground in [Rgb([96, 200, 88]), Rgb([56, 120, 232])] {
+            let frame = SyntheticFrame::blank(WIDTH, HEIGHT, ground).build();
+            let analysis = detector.detect(&frame).unwrap();
+            assert_eq!(analysis.scene_type(), SceneType::Overworld);
+            assert!(analysis.confidence() >= 0.8);
+        }
+    }
+
+    #[test]
+    fn menus_and_dark_screens_are_not_overworld() {
+        let detector = OverworldDetector::new();
+        for frame in [
+            SyntheticFrame::title_screen(),
+            SyntheticFrame::party(3),
+            SyntheticFrame::shop(4),
+            SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([128, 128, 128])),
+        ] {
+            assert!(detector.detect(&frame.build()).is_none());
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use image::{Rgb, RgbImage};
+
+use crate::pipeline::detection::cave_scene_detector::is_enclosed_dark;
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{luma, pixel_ratio};
+use crate::pipeline::domain::scene_analysis::OverworldKind;
+
+// Tells overworld places apart by their dominant ground: dark rock walling in a cave, blue
+// water when surfing, green grass and trees outside, and the warm pink or wooden floors of
+// buildings like the Pokemon Center.
+pub struct OverworldKindClassifier {
+    min_enclosure: f32,
+    min_ground_ratio: f32,
+    // How far the dominant channel has to lead the others for a pixel to count as its ground.
+    channel_margin: u8,
+    thresholds: ColorThresholds,
+}
+
+impl OverworldKindClassifier {
+    pub fn new() -> Self {
+        Self {
+            min_enclosure: 0.4,
+            min_ground_ratio: 0.3,
+            channel_margin: 30,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn classify(&self, image: &RgbImage) -> Option<OverworldKind> {
+        if is_enclosed_dark(image, self.thresholds.rock_luma) >= self.min_enclosure {
+            return Some(OverworldKind::Cave);
+        }
+        self.dominant_ground(image)
+            .filter(|(_, ratio)| *ratio >= self.min_ground_ratio)
+            .map(|(kind, _)| kind)
+    }
+
+    // The lit water, grass or floor covering most of the frame and the fraction it covers,
+    // without looking for cave walls. None when the frame shows none of them.
+    pub fn dominant_ground(&self, image: &RgbImage) -> Option<(OverworldKind, f32)> {
+        let (width, height) = image.dimensions();
+        let rock_luma = self.thresholds.rock_luma;
+        let margin = self.channel_margin as i16;
+        let leads = |lead: u8, others: [u8; 2]| {
+            others
+                .iter()
+                .all(|other| lead as i16 - *other as i16 >= margin)
+        };
+        let is_water = |p: &Rgb<u8>| {
+            let Rgb([r, g, b]) = *p;
+            leads(b, [r, g]) && luma(p) >= rock_luma
+        };
+        let is_grass = |p: &Rgb<u8>| {
+            let Rgb([r, g, b]) = *p;
+            leads(g, [r, b]) && luma(p) >= rock_luma
+        };
+        let is_floor = |p: &Rgb<u8>| {
+            let Rgb([r, g, b]) = *p;
+            leads(r, [g, b]) && luma(p) >= self.thresholds.menu_background_luma
+        };
+
+        [
+            (
+                OverworldKind::Water,
+                pixel_ratio(image, 0..width, 0..height, is_water),
+            ),
+            (
+                OverworldKind::Outdoor,
+                pixel_ratio(image, 0..width, 0..height, is_grass),
+            ),
+            (
+                OverworldKind::Indoor,
+                pixel_ratio(image, 0..width, 0..height, is_floor),
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, ratio)| *ratio > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+impl Default for OverworldKindClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::detection::image_region::ImageRegion;
+    use crate::pipeline::detection::synthetic_frames::{HEIGHT, SyntheticFrame, WIDTH};
+
+    #[test]
+    fn pokemon_center_floors_are_indoors() {
+        // Pink floor with the counter and a wall strip along the top.
+        let center = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([248, 176, 200]))
+            .with_fill(ImageRegion::new(0, 0, WIDTH, 40), Rgb([230, 230, 220]))
+            .with_fill(ImageRegion::new(64, 48, 128, 16), Rgb([200, 80, 60]))
+            .build();
+        assert_eq!(
+            OverworldKindClassifier::new().classify(&center),
+            Some(OverworldKind::Indoor)
+        );
+    }
+
+    #[test]
+    fn grass_and_trees_are_outdoors() {
+        let route = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([96, 200, 88]))
+            .with_stripes(
+                ImageRegion::new(0, 0, WIDTH, 32),
+                Rgb([32, 104, 48]),
+                Rgb([96, 200, 88]),
+                8,
+            )
+            .with_fill(ImageRegion::new(112, 0, 32, HEIGHT), Rgb([216, 192, 144]))
+            .build();
+        assert_eq!(
+            OverworldKindClassifier::new().classify(&route),
+            Some(OverworldKind::Outdoor)
+        );
+    }
+
+    #[test]
+    fn caves_and_water_have_their_own_kinds() {
+        let classifier = OverworldKindClassifier::new();
+        assert_eq!(
+            classifier.classify(&SyntheticFrame::cave().build()),
+            Some(OverworldKind::Cave)
+        );
+        let sea = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([56, 120, 232])).build();
+        assert_eq!(classifier.classify(&sea), Some(OverworldKind::Water));
+    }
+
+    #[test]
+    fn gray_frames_are_left_unclassified() {
+        let gray = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([128, 128, 128])).build();
+        assert_eq!(OverworldKindClassifier::new().classify(&gray), None);
+    }
+}
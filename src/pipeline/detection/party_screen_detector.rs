@@ -0,0 +1,132 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+const MAX_PARTY_SIZE: u32 = 6;
+
+// The party screen stacks up to six light, rounded member slots in the right two-thirds of a
+// dark background. Members always fill from the top, so the filled slots must be contiguous.
+pub struct PartyScreenDetector {
+    min_slot_fill: f32,
+    min_background_dark: f32,
+    thresholds: ColorThresholds,
+}
+
+impl PartyScreenDetector {
+    pub fn new() -> Self {
+        Self {
+            min_slot_fill: 0.5,
+            min_background_dark: 0.6,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    // Counts filled member slots, returns None when the frame doesn't look like the party screen.
+    pub fn count_members(&self, image: &RgbImage) -> Option<u8> {
+        let (width, height) = image.dimensions();
+        let slot_h = height / MAX_PARTY_SIZE;
+        if slot_h < 4 || width < 6 {
+            return None;
+        }
+        let slots_x = width / 3;
+        let is_dark = |p: &image::Rgb<u8>| luma(p) < self.thresholds.menu_background_luma;
+
+        // The left third and the gaps between slots show the dark background.
+        if pixel_ratio(image, 0..slots_x, 0..height, is_dark) < self.min_background_dark {
+            return None;
+        }
+        for slot in 1..MAX_PARTY_SIZE {
+            let gap = slot * slot_h;
+            if pixel_ratio(image, slots_x..width, gap..gap + 1, is_dark) < self.min_background_dark
+            {
+                return None;
+            }
+        }
+
+        let inset_x = (width - slots_x) / 8;
+        let inset_y = slot_h / 4;
+        let filled: Vec<bool> = (0..MAX_PARTY_SIZE)
+            .map(|slot| {
+                let top = slot * slot_h;
+                pixel_ratio(
+                    image,
+                    slots_x + inset_x..width - inset_x,
+                    top + inset_y..top + slot_h - inset_y,
+                    |p| luma(p) >= self.thresholds.menu_slot_luma,
+                ) >= self.min_slot_fill
+            })
+            .collect();
+
+        let count = filled.iter().take_while(|filled| **filled).count();
+        (count > 0 && filled[count..].iter().all(|filled| !filled)).then_some(count as u8)
+    }
+}
+
+impl Default for PartyScreenDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for PartyScreenDetector {
+    fn name(&self) -> &'static str {
+        "party_screen"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let count = self.count_members(image)?;
+        Some(SceneAnalysis::new(SceneType::PartyMenu, 0.85).with_pokemon_count(Some(count)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    const WIDTH: u32 = 256;
+    const HEIGHT: u32 = 192;
+
+    fn party_frame(members: u32) -> RgbImage {
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([20, 30, 60]));
+        for slot in 0..members {
+            for y in slot * 32 + 3..slot * 32 + 29 {
+                for x in 90..250 {
+                    image.put_pixel(x, y, Rgb([120, 200, 240]));
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn counts_party_members() {
+        let detector = PartyScreenDetector::new();
+        for members in [1, 3, 6] {
+            let analysis = detector.detect(&party_frame(members)).unwrap();
+            assert_eq!(analysis.scene_type(), SceneType::PartyMenu);
+            assert_eq!(analysis.pokemon_count(), Some(members as u8));
+        }
+    }
+
+    #[test]
+    fn empty_or_bright_frames_are_not_party_screens() {
+        let detector = PartyScreenDetector::new();
+        assert!(detector.detect(&party_frame(0)).is_none());
+        let white = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([255, 255, 255]));
+        assert!(detector.detect(&white).is_none());
+    }
+}
@@ -0,0 +1,17 @@
+use image::RgbImage;
+
+use crate::pipeline::domain::scene_analysis::SceneAnalysis;
+
+// Trait for detectors that recognize a single kind of scene from the raw pixels.
+pub trait SceneDetector: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    // Higher runs first when the analyzer exits early. Decisive, cheap full-screen checks
+    // should rank above broad environment heuristics.
+    fn priority(&self) -> u8 {
+        50
+    }
+
+    // Returns the detected scene and its confidence, or None when the scene is not present.
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis>;
+}
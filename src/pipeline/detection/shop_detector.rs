@@ -0,0 +1,107 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_stats::{edge_density, listed_rows, luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+const LIST_ROWS: u32 = 8;
+
+// The Poke Mart shows the player's money in a light box top left and the wares as a light
+// list on the right, each row an item name with its price right-aligned. Unlike the bag there
+// are no pocket tabs, and the money box has digits in its right half.
+pub struct ShopDetector {
+    min_panel_fill: f32,
+    min_text_edges: f32,
+    thresholds: ColorThresholds,
+}
+
+impl ShopDetector {
+    pub fn new() -> Self {
+        Self {
+            min_panel_fill: 0.5,
+            min_text_edges: 0.03,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    // Counts the wares on sale, returns None when the frame doesn't look like a shop.
+    pub fn count_wares(&self, image: &RgbImage) -> Option<u8> {
+        let (width, height) = image.dimensions();
+        if height / LIST_ROWS < 4 || width < 40 {
+            return None;
+        }
+        let list_x = width * 2 / 5;
+        let price_x = width - width / 6;
+        let money_h = height / 6;
+        let is_light = |p: &image::Rgb<u8>| luma(p) >= self.thresholds.menu_slot_luma;
+
+        if pixel_ratio(image, 0..list_x, 0..money_h, is_light) < self.min_panel_fill
+            || edge_density(image, list_x / 2..list_x, 0..money_h) < self.min_text_edges
+        {
+            return None;
+        }
+        if pixel_ratio(image, list_x..width, 0..height, is_light) < self.min_panel_fill {
+            return None;
+        }
+        listed_rows(
+            image,
+            list_x..price_x,
+            price_x..width,
+            LIST_ROWS,
+            self.min_text_edges,
+        )
+    }
+}
+
+impl Default for ShopDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for ShopDetector {
+    fn name(&self) -> &'static str {
+        "shop"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        self.count_wares(image)?;
+        Some(SceneAnalysis::new(SceneType::Shop, 0.85))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+
+    #[test]
+    fn classifies_shop_frames() {
+        let detector = ShopDetector::new();
+        for wares in [1, 5] {
+            let image = SyntheticFrame::shop(wares).build();
+            assert_eq!(detector.count_wares(&image), Some(wares as u8));
+            assert_eq!(
+                detector.detect(&image).unwrap().scene_type(),
+                SceneType::Shop
+            );
+        }
+    }
+
+    #[test]
+    fn the_bag_is_not_a_shop() {
+        let detector = ShopDetector::new();
+        assert!(detector.detect(&SyntheticFrame::bag(5).build()).is_none());
+        assert!(detector.detect(&SyntheticFrame::shop(0).build()).is_none());
+    }
+}
@@ -0,0 +1,381 @@
+use image::{Rgb, RgbImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::pipeline::detection::game_registry::POKEMON_BADGE_GRID;
+use crate::pipeline::detection::image_region::ImageRegion;
+use crate::pipeline::domain::scene_analysis::BattleMenuSelection;
+
+pub const WIDTH: u32 = 256;
+pub const HEIGHT: u32 = 192;
+
+const NIGHT_SKY: Rgb<u8> = Rgb([20, 30, 90]);
+const LOGO_YELLOW: Rgb<u8> = Rgb([250, 220, 40]);
+const INK: Rgb<u8> = Rgb([10, 10, 10]);
+const PANEL_WHITE: Rgb<u8> = Rgb([248, 248, 248]);
+const BADGE_COLORS: [Rgb<u8>; 8] = [
+    Rgb([230, 40, 40]),
+    Rgb([40, 200, 60]),
+    Rgb([40, 90, 230]),
+    Rgb([240, 200, 30]),
+    Rgb([200, 40, 200]),
+    Rgb([30, 200, 210]),
+    Rgb([240, 120, 20]),
+    Rgb([150, 60, 230]),
+];
+
+// Test frames drawn from flat rectangles and stripes, laid out the way the detectors in this
+// module expect. The scene constructors give a recognizable starting point, the drawing
+// methods add to it, and seeded noise keeps "slightly off" frames reproducible.
+pub struct SyntheticFrame {
+    image: RgbImage,
+    noise: Option<(u64, u8)>,
+}
+
+impl SyntheticFrame {
+    pub fn blank(width: u32, height: u32, color: Rgb<u8>) -> Self {
+        Self {
+            image: RgbImage::from_pixel(width, height, color),
+            noise: None,
+        }
+    }
+
+    // Busy logo in the upper half and a "PRESS START" band centered near the bottom.
+    pub fn title_screen() -> Self {
+        Self::blank(WIDTH, HEIGHT, NIGHT_SKY)
+            .with_stripes(ImageRegion::new(40, 20, 176, 60), LOGO_YELLOW, INK, 4)
+            .with_stripes(ImageRegion::new(96, 150, 64, 15), LOGO_YELLOW, INK, 4)
+    }
+
+    // Light member slots stacked on the right of a dark background, filled from the top.
+    pub fn party(members: u32) -> Self {
+        let slot_h = HEIGHT / 6;
+        (0..members.min(6)).fold(
+            Self::blank(WIDTH, HEIGHT, Rgb([20, 30, 60])),
+            |frame, slot| {
+                frame.with_fill(
+                    ImageRegion::new(90, slot * slot_h + 3, 160, slot_h - 6),
+                    Rgb([120, 200, 240]),
+                )
+            },
+        )
+    }
+
+    // Alternating pocket tabs down the left edge and `items` listed rows on a light panel,
+    // each with a name and a right-aligned quantity.
+    pub fn bag(items: u32) -> Self {
+        let tab_h = HEIGHT / 6;
+        let row_h = HEIGHT / 8;
+        let frame = (0..6).fold(Self::blank(WIDTH, HEIGHT, PANEL_WHITE), |frame, tab| {
+            let color = if tab % 2 == 0 {
+                Rgb([200, 60, 60])
+            } else {
+                Rgb([240, 220, 80])
+            };
+            frame.with_fill(ImageRegion::new(0, tab * tab_h, WIDTH / 5, tab_h), color)
+        });
+        (0..items.min(8)).fold(frame, |frame, row| {
+            let text_y = row * row_h + row_h / 3;
+            frame
+                .with_stripes(
+                    ImageRegion::new(120, text_y, 80, row_h / 3),
+                    INK,
+                    PANEL_WHITE,
+                    4,
+                )
+                .with_stripes(
+                    ImageRegion::new(222, text_y, 24, row_h / 3),
+                    INK,
+                    PANEL_WHITE,
+                    4,
+                )
+        })
+    }
+
+    // Money box with right-aligned digits top left and `wares` listed rows on a light panel to
+    // the right, each with a name and a right-aligned price.
+    pub fn shop(wares: u32) -> Self {
+        let row_h = HEIGHT / 8;
+        let frame = Self::blank(WIDTH, HEIGHT, Rgb([80, 140, 200]))
+            .with_fill(ImageRegion::new(8, 6, 88, 24), PANEL_WHITE)
+            .with_stripes(ImageRegion::new(56, 12, 36, 12), INK, PANEL_WHITE, 4)
+            .with_fill(
+                ImageRegion::new(WIDTH * 2 / 5, 0, WIDTH - WIDTH * 2 / 5, HEIGHT),
+                PANEL_WHITE,
+            );
+        (0..wares.min(8)).fold(frame, |frame, row| {
+            let text_y = row * row_h + row_h / 3;
+            frame
+                .with_stripes(
+                    ImageRegion::new(120, text_y, 80, row_h / 3),
+                    INK,
+                    PANEL_WHITE,
+                    4,
+                )
+                .with_stripes(
+                    ImageRegion::new(222, text_y, 24, row_h / 3),
+                    INK,
+                    PANEL_WHITE,
+                    4,
+                )
+        })
+    }
+
+    // The Pokemon's panel on the left and, on the right, its four moves as light labelled rows
+    // above the yellow row of the move it wants to learn.
+    pub fn move_learn() -> Self {
+        let row_h = HEIGHT / 5;
+        let frame = Self::blank(WIDTH, HEIGHT, Rgb([40, 50, 90]))
+            .with_fill(ImageRegion::new(8, 16, 112, 160), Rgb([120, 160, 200]))
+            .with_stripes(
+                ImageRegion::new(24, 40, 80, 80),
+                INK,
+                Rgb([120, 160, 200]),
+                4,
+            );
+        (0..5).fold(frame, |frame, row| {
+            let color = if row == 4 {
+                Rgb([250, 220, 60])
+            } else {
+                PANEL_WHITE
+            };
+            frame
+                .with_fill(
+                    ImageRegion::new(WIDTH / 2, row * row_h + 4, WIDTH / 2, row_h - 8),
+                    color,
+                )
+                .with_stripes(
+                    ImageRegion::new(WIDTH / 2 + 12, row * row_h + row_h / 3, 80, row_h / 3),
+                    INK,
+                    color,
+                    4,
+                )
+        })
+    }
+
+    // A sprite glowing white in the middle of a black screen, halfway through evolving.
+    pub fn evolution() -> Self {
+        Self::blank(WIDTH, HEIGHT, Rgb([0, 0, 0]))
+            .with_fill(ImageRegion::new(80, 56, 96, 80), Rgb([255, 255, 255]))
+    }
+
+    // Lit cave floor walled in by dark rock on every side.
+    pub fn cave() -> Self {
+        Self::blank(WIDTH, HEIGHT, Rgb([12, 10, 8])).with_fill(
+            ImageRegion::new(40, 40, WIDTH - 80, HEIGHT - 80),
+            Rgb([110, 90, 70]),
+        )
+    }
+
+    // Trainer card with the first `badges` slots of the Pokemon badge grid lit.
+    pub fn trainer_card(badges: usize) -> Self {
+        let layout = POKEMON_BADGE_GRID;
+        let grid = ImageRegion::from_fractions(
+            WIDTH,
+            HEIGHT,
+            layout.left,
+            layout.top,
+            layout.right - layout.left,
+            layout.bottom - layout.top,
+        );
+        let cell_w = grid.width / layout.columns;
+        let cell_h = grid.height / layout.rows;
+        (0..layout.rows * layout.columns).fold(
+            Self::blank(WIDTH, HEIGHT, Rgb([236, 236, 220])),
+            |frame, slot| {
+                let (row, column) = (slot / layout.columns, slot % layout.columns);
+                let color = BADGE_COLORS
+                    .get(slot as usize)
+                    .filter(|_| (slot as usize) < badges)
+                    .copied()
+                    .unwrap_or(Rgb([128, 128, 128]));
+                frame.with_fill(
+                    ImageRegion::new(
+                        grid.x + column * cell_w + 2,
+                        grid.y + row * cell_h + 2,
+                        cell_w - 4,
+                        cell_h - 4,
+                    ),
+                    color,
+                )
+            },
+        )
+    }
+
+    // Battle field on top, the white 2x2 command panel bottom right with the arrow on `cursor`.
+    pub fn battle_menu(cursor: BattleMenuSelection) -> Self {
+        let (panel_x, panel_y) = (WIDTH / 2, HEIGHT * 2 / 3);
+        let (quadrant_w, quadrant_h) = ((WIDTH - panel_x) / 2, (HEIGHT - panel_y) / 2);
+        let mut frame = Self::blank(WIDTH, HEIGHT, Rgb([120, 180, 110])).with_fill(
+            ImageRegion::new(panel_x, panel_y, WIDTH - panel_x, HEIGHT - panel_y),
+            PANEL_WHITE,
+        );
+        for (selection, column, row) in [
+            (BattleMenuSelection::Fight, 0, 0),
+            (BattleMenuSelection::Bag, 1, 0),
+            (BattleMenuSelection::Pokemon, 0, 1),
+            (BattleMenuSelection::Run, 1, 1),
+        ] {
+            let x = panel_x + column * quadrant_w;
+            let y = panel_y + row * quadrant_h;
+            frame = frame.with_fill(ImageRegion::new(x + 20, y + 12, 32, 8), INK);
+            if selection == cursor {
+                frame = frame.with_fill(ImageRegion::new(x + 4, y + 12, 8, 8), INK);
+            }
+        }
+        frame
+    }
+
+    // Both DS screens stacked like the emulator sends them: the overworld on the top screen
+    // above a white dialog box holding the first `characters` of a line of text, and a plain
+    // touch screen below.
+    pub fn dialog(characters: u32, overworld: Rgb<u8>) -> Self {
+        (0..characters).fold(
+            Self::blank(WIDTH, HEIGHT * 2, Rgb([200, 200, 208]))
+                .with_fill(ImageRegion::new(0, 0, WIDTH, 144), overworld)
+                .with_fill(ImageRegion::new(0, 144, WIDTH, 48), PANEL_WHITE),
+            |frame, character| {
+                frame.with_fill(ImageRegion::new(8 + character * 10, 152, 8, 8), INK)
+            },
+        )
+    }
+
+    // A screen fading through a single color, usually black or white.
+    pub fn fade(color: Rgb<u8>) -> Self {
+        Self::blank(WIDTH, HEIGHT, color)
+    }
+
+    pub fn with_fill(mut self, region: ImageRegion, color: Rgb<u8>) -> Self {
+        let region = region.clamp_to(self.image.width(), self.image.height());
+        for y in region.rows() {
+            for x in region.columns() {
+                self.image.put_pixel(x, y, color);
+            }
+        }
+        self
+    }
+
+    // Vertical stripes alternating every `period` pixels, dense enough to register as edges.
+    pub fn with_stripes(
+        mut self,
+        region: ImageRegion,
+        a: Rgb<u8>,
+        b: Rgb<u8>,
+        period: u32,
+    ) -> Self {
+        let region = region.clamp_to(self.image.width(), self.image.height());
+        for y in region.rows() {
+            for x in region.columns() {
+                let color = if (x / period.max(1)).is_multiple_of(2) {
+                    a
+                } else {
+                    b
+                };
+                self.image.put_pixel(x, y, color);
+            }
+        }
+        self
+    }
+
+    // Adds up to +-`amplitude` to every channel when the frame is built, the same for a given seed.
+    pub fn with_noise(mut self, seed: u64, amplitude: u8) -> Self {
+        self.noise = Some((seed, amplitude));
+        self
+    }
+
+    pub fn build(self) -> RgbImage {
+        let mut image = self.image;
+        if let Some((seed, amplitude)) = self.noise {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let amplitude = amplitude as i16;
+            for pixel in image.pixels_mut() {
+                for channel in pixel.0.iter_mut() {
+                    let offset = rng.random_range(-amplitude..=amplitude);
+                    *channel = (*channel as i16 + offset).clamp(0, 255) as u8;
+                }
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::detection::battle_menu_cursor_detector::BattleMenuCursorDetector;
+    use crate::pipeline::domain::scene_analysis::SceneType;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    fn scenes() -> Vec<(SyntheticFrame, SceneType)> {
+        vec![
+            (SyntheticFrame::title_screen(), SceneType::TitleScreen),
+            (SyntheticFrame::party(3), SceneType::PartyMenu),
+            (SyntheticFrame::cave(), SceneType::Overworld),
+            (SyntheticFrame::trainer_card(5), SceneType::TrainerCard),
+            (SyntheticFrame::bag(3), SceneType::Bag),
+            (SyntheticFrame::shop(4), SceneType::Shop),
+            (
+                SyntheticFrame::battle_menu(BattleMenuSelection::Bag),
+                SceneType::Battle,
+            ),
+            (SyntheticFrame::move_learn(), SceneType::MoveLearn),
+            (SyntheticFrame::evolution(), SceneType::Evolution),
+            (
+                SyntheticFrame::fade(Rgb([0, 0, 0])),
+                SceneType::FadeTransition,
+            ),
+            (
+                SyntheticFrame::fade(Rgb([255, 255, 255])),
+                SceneType::FadeTransition,
+            ),
+        ]
+    }
+
+    #[test]
+    fn scene_frames_are_classified_as_their_scene() {
+        let analyzer = SceneAnalyzer::new();
+        for (frame, expected) in scenes() {
+            let analysis = analyzer.detect_best_scene(&frame.build());
+            assert_eq!(analysis.scene_type(), expected);
+        }
+    }
+
+    #[test]
+    fn lightly_noised_frames_keep_their_scene() {
+        let analyzer = SceneAnalyzer::new();
+        for (seed, (frame, expected)) in scenes().into_iter().enumerate() {
+            let analysis = analyzer.detect_best_scene(&frame.with_noise(seed as u64, 3).build());
+            assert_eq!(analysis.scene_type(), expected);
+        }
+    }
+
+    #[test]
+    fn battle_menu_frames_point_at_their_selection() {
+        let detector = BattleMenuCursorDetector::new();
+        for selection in [
+            BattleMenuSelection::Fight,
+            BattleMenuSelection::Bag,
+            BattleMenuSelection::Pokemon,
+            BattleMenuSelection::Run,
+        ] {
+            let image = SyntheticFrame::battle_menu(selection).build();
+            assert_eq!(detector.detect(&image), Some(selection));
+        }
+    }
+
+    #[test]
+    fn detector_counts_match_the_requested_frames() {
+        let analyzer = SceneAnalyzer::new();
+        let party = analyzer.detect_best_scene(&SyntheticFrame::party(4).build());
+        assert_eq!(party.pokemon_count(), Some(4));
+        let card = analyzer.detect_best_scene(&SyntheticFrame::trainer_card(2).build());
+        assert_eq!(card.badges_earned(), Some(2));
+    }
+
+    #[test]
+    fn noise_is_reproducible_per_seed() {
+        let noisy = |seed| SyntheticFrame::cave().with_noise(seed, 10).build();
+        assert_eq!(noisy(7), noisy(7));
+        assert_ne!(noisy(7), noisy(8));
+    }
+}
@@ -0,0 +1,124 @@
+use image::RgbImage;
+
+use crate::pipeline::detection::image_stats::edge_density;
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// The title screen shows a large, busy logo in the upper half and a short
+// "PRESS START" band centered near the bottom. Dialog boxes also sit at the
+// bottom, but they span the full width, so the band's side margins must stay quiet.
+pub struct TitleScreenDetector {
+    min_logo_edges: f32,
+    min_prompt_edges: f32,
+    max_margin_edges: f32,
+}
+
+impl TitleScreenDetector {
+    pub fn new() -> Self {
+        Self {
+            min_logo_edges: 0.04,
+            min_prompt_edges: 0.04,
+            max_margin_edges: 0.01,
+        }
+    }
+}
+
+impl Default for TitleScreenDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector for TitleScreenDetector {
+    fn name(&self) -> &'static str {
+        "title_screen"
+    }
+
+    fn priority(&self) -> u8 {
+        70
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let (width, height) = image.dimensions();
+        if width < 16 || height < 16 {
+            return None;
+        }
+
+        let logo_edges = edge_density(image, width / 10..width * 9 / 10, 0..height / 2);
+        let band = height * 3 / 4..height * 9 / 10;
+        let prompt_edges = edge_density(image, width / 4..width * 3 / 4, band.clone());
+        let margin_edges = edge_density(image, 0..width * 3 / 20, band.clone()).max(edge_density(
+            image,
+            width * 17 / 20..width,
+            band,
+        ));
+
+        if logo_edges < self.min_logo_edges
+            || prompt_edges < self.min_prompt_edges
+            || margin_edges > self.max_margin_edges
+        {
+            return None;
+        }
+
+        let confidence = (0.8 + logo_edges.min(0.15)).min(0.95);
+        Some(SceneAnalysis::new(SceneType::TitleScreen, confidence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+
+    const WIDTH: u32 = 256;
+    const HEIGHT: u32 = 192;
+
+    fn fill_striped(
+        image: &mut RgbImage,
+        columns: std::ops::Range<u32>,
+        rows: std::ops::Range<u32>,
+    ) {
+        for y in rows {
+            for x in columns.clone() {
+                let color = if (x / 4) % 2 == 0 {
+                    Rgb([250, 220, 40])
+                } else {
+                    Rgb([10, 10, 10])
+                };
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    fn title_frame() -> RgbImage {
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([20, 30, 90]));
+        fill_striped(&mut image, 40..216, 20..80);
+        fill_striped(&mut image, 96..160, 150..165);
+        image
+    }
+
+    fn dialog_frame() -> RgbImage {
+        let mut image = title_frame();
+        fill_striped(&mut image, 0..WIDTH, 144..176);
+        image
+    }
+
+    #[test]
+    fn detects_logo_with_press_start_band() {
+        let analysis = TitleScreenDetector::new().detect(&title_frame()).unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::TitleScreen);
+        assert!(analysis.confidence() >= 0.8);
+    }
+
+    #[test]
+    fn ignores_full_width_dialog_box() {
+        assert!(TitleScreenDetector::new().detect(&dialog_frame()).is_none());
+    }
+
+    #[test]
+    fn ignores_blank_frame() {
+        let image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([255, 255, 255]));
+        assert!(TitleScreenDetector::new().detect(&image).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use image::{Rgb, RgbImage};
+
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::image_region::ImageRegion;
+use crate::pipeline::detection::image_stats::{edge_density, luma, pixel_ratio};
+use crate::pipeline::detection::scene_detector::SceneDetector;
+use crate::pipeline::domain::scene_analysis::{SceneAnalysis, SceneType};
+
+// Where the badge case sits on the trainer card, as fractions of the frame.
+// Each game lays its card out differently, so the GameRegistry hands one in per game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BadgeGridLayout {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub rows: u32,
+    pub columns: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BadgeSlot {
+    Lit,
+    Empty,
+}
+
+// The badge grid sits on the light, unsaturated card, framed by it on every side, with the
+// cells standing out as sharp edges. Every cell is either a colorful badge or a gray empty
+// slot. If the frame around the grid isn't card or any cell is neither, it isn't a trainer
+// card, so grass, water or a gray screen filling the grid area don't count as badges.
+pub struct TrainerCardDetector {
+    layout: BadgeGridLayout,
+    min_lit_ratio: f32,
+    min_empty_ratio: f32,
+    min_card_ratio: f32,
+    min_grid_edges: f32,
+    thresholds: ColorThresholds,
+}
+
+impl TrainerCardDetector {
+    pub fn new(layout: BadgeGridLayout) -> Self {
+        Self {
+            layout,
+            min_lit_ratio: 0.5,
+            min_empty_ratio: 0.6,
+            min_card_ratio: 0.8,
+            min_grid_edges: 0.015,
+            thresholds: ColorThresholds::default(),
+        }
+    }
+
+    pub fn with_color_thresholds(mut self, thresholds: ColorThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    pub fn count_badges(&self, image: &RgbImage) -> Option<u8> {
+        let layout = self.layout;
+        let grid = ImageRegion::from_fractions(
+            image.width(),
+            image.height(),
+            layout.left,
+            layout.top,
+            layout.right - layout.left,
+            layout.bottom - layout.top,
+        );
+        let cell_w = grid.width / layout.columns.max(1);
+        let cell_h = grid.height / layout.rows.max(1);
+        if cell_w < 4 || cell_h < 4 {
+            return None;
+        }
+        if !self.framed_by_card(image, grid, cell_w.min(cell_h) / 4)
+            || edge_density(image, grid.columns(), grid.rows()) < self.min_grid_edges
+        {
+            return None;
+        }
+
+        let mut lit = 0u8;
+        for row in 0..layout.rows {
+            for column in 0..layout.columns {
+                let slot = ImageRegion::new(
+                    grid.x + column * cell_w + cell_w / 5,
+                    grid.y + row * cell_h + cell_h / 5,
+                    cell_w * 3 / 5,
+                    cell_h * 3 / 5,
+                );
+                match self.classify(image, slot)? {
+                    BadgeSlot::Lit => lit += 1,
+                    BadgeSlot::Empty => {}
+                }
+            }
+        }
+        Some(lit)
+    }
+
+    // Bands of `margin` pixels above, below, left and right of the grid must all be card
+    // background: lighter than an empty slot and unsaturated.
+    fn framed_by_card(&self, image: &RgbImage, grid: ImageRegion, margin: u32) -> bool {
+        let margin = margin.max(1);
+        let top = grid.y.saturating_sub(margin);
+        let left = grid.x.saturating_sub(margin);
+        let bottom = grid.y + grid.height;
+        let right = grid.x + grid.width;
+        let bands = [
+            (grid.columns(), top..grid.y),
+            (grid.columns(), bottom..bottom + margin),
+            (left..grid.x, grid.rows()),
+            (right..right + margin, grid.rows()),
+        ];
+        let card = |p: &Rgb<u8>| {
+            saturation(p) < self.thresholds.gray_saturation && luma(p) > self.thresholds.gray_luma.1
+        };
+        bands.into_iter().all(|(columns, rows)| {
+            !columns.is_empty()
+                && !rows.is_empty()
+                && pixel_ratio(image, columns, rows, card) >= self.min_card_ratio
+        })
+    }
+
+    fn classify(&self, image: &RgbImage, slot: ImageRegion) -> Option<BadgeSlot> {
+        if pixel_ratio(image, slot.columns(), slot.rows(), |p| {
+            saturation(p) >= self.thresholds.badge_saturation
+        }) >= self.min_lit_ratio
+        {
+            return Some(BadgeSlot::Lit);
+        }
+        let (min_gray, max_gray) = self.thresholds.gray_luma;
+        let gray = |p: &Rgb<u8>| {
+            saturation(p) < self.thresholds.gray_saturation
+                && (min_gray..=max_gray).contains(&luma(p))
+        };
+        (pixel_ratio(image, slot.columns(), slot.rows(), gray) >= self.min_empty_ratio)
+            .then_some(BadgeSlot::Empty)
+    }
+}
+
+impl SceneDetector for TrainerCardDetector {
+    fn name(&self) -> &'static str {
+        "trainer_card"
+    }
+
+    fn priority(&self) -> u8 {
+        80
+    }
+
+    fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+        let badges = self.count_badges(image)?;
+        Some(SceneAnalysis::new(SceneType::TrainerCard, 0.85).with_badges_earned(Some(badges)))
+    }
+}
+
+fn saturation(pixel: &Rgb<u8>) -> f32 {
+    let max = *pixel.0.iter().max().unwrap_or(&0);
+    let min = *pixel.0.iter().min().unwrap_or(&0);
+    if max == 0 {
+        0.0
+    } else {
+        (max - min) as f32 / max as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: u32 = 256;
+    const HEIGHT: u32 = 192;
+    const LAYOUT: BadgeGridLayout = BadgeGridLayout {
+        left: 0.1,
+        top: 0.55,
+        right: 0.9,
+        bottom: 0.95,
+        rows: 2,
+        columns: 4,
+    };
+    const BADGE_COLORS: [Rgb<u8>; 8] = [
+        Rgb([230, 40, 40]),
+        Rgb([40, 200, 60]),
+        Rgb([40, 90, 230]),
+        Rgb([240, 200, 30]),
+        Rgb([200, 40, 200]),
+        Rgb([30, 200, 210]),
+        Rgb([240, 120, 20]),
+        Rgb([150, 60, 230]),
+    ];
+
+    fn card_frame(badges: usize) -> RgbImage {
+        let layout = LAYOUT;
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([236, 236, 220]));
+        let grid_x = (WIDTH as f32 * layout.left) as u32;
+        let grid_y = (HEIGHT as f32 * layout.top) as u32;
+        let cell_w = ((WIDTH as f32 * (layout.right - layout.left)) as u32) / layout.columns;
+        let cell_h = ((HEIGHT as f32 * (layout.bottom - layout.top)) as u32) / layout.rows;
+        for slot in 0..8u32 {
+            let (row, column) = (slot / 4, slot % 4);
+            let color = if (slot as usize) < badges {
+                BADGE_COLORS[slot as usize]
+            } else {
+                Rgb([128, 128, 128])
+            };
+            for y in grid_y + row * cell_h + 2..grid_y + (row + 1) * cell_h - 2 {
+                for x in grid_x + column * cell_w + 2..grid_x + (column + 1) * cell_w - 2 {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn counts_lit_badges() {
+        let detector = TrainerCardDetector::new(LAYOUT);
+        for badges in [0, 3, 8] {
+            let analysis = detector.detect(&card_frame(badges)).unwrap();
+            assert_eq!(analysis.scene_type(), SceneType::TrainerCard);
+            assert_eq!(analysis.badges_earned(), Some(badges as u8));
+        }
+    }
+
+    #[test]
+    fn plain_frame_is_not_a_trainer_card() {
+        let white = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([255, 255, 255]));
+        assert!(TrainerCardDetector::new(LAYOUT).detect(&white).is_none());
+    }
+
+    #[test]
+    fn grass_and_gray_screens_have_no_badges() {
+        let detector = TrainerCardDetector::new(LAYOUT);
+        for color in [Rgb([96, 200, 88]), Rgb([128, 128, 128])] {
+            let screens = RgbImage::from_pixel(WIDTH, HEIGHT * 2, color);
+            assert!(detector.detect(&screens).is_none());
+            assert_eq!(detector.count_badges(&screens), None);
+        }
+    }
+
+    #[test]
+    fn a_gray_block_on_a_light_screen_is_not_a_badge_grid() {
+        let mut image = RgbImage::from_pixel(WIDTH, HEIGHT, Rgb([236, 236, 220]));
+        for y in 110..180 {
+            for x in 30..226 {
+                image.put_pixel(x, y, Rgb([128, 128, 128]));
+            }
+        }
+        assert!(TrainerCardDetector::new(LAYOUT).detect(&image).is_none());
+    }
+}
@@ -1,18 +1,56 @@
 use std::time::Instant;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SceneType {
     Battle,
     Menu,
     Overworld,
     Cutscene,
+    TitleScreen,
+    PartyMenu,
+    TrainerCard,
+    Bag,
+    Shop,
+    // "Forget a move to learn ...?" with the four known moves and the new one listed.
+    MoveLearn,
+    // The evolution animation, a glowing sprite in the middle of a dark screen.
+    Evolution,
+    // Near-uniform black or white screen while the game fades between scenes.
+    FadeTransition,
     Unknown,
 }
 
+// Entries of the FIGHT/BAG/POKEMON/RUN grid shown during a battle turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BattleMenuSelection {
+    Fight,
+    Bag,
+    Pokemon,
+    Run,
+}
+
+// What kind of place an overworld scene shows, walking and encounters differ between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverworldKind {
+    Indoor,
+    Outdoor,
+    Cave,
+    Water,
+}
+
+#[derive(Clone)]
 pub struct SceneAnalysis {
     scene_type: SceneType,
     confidence: f32,
     timestamp: Instant,
+    battle_menu_selection: Option<BattleMenuSelection>,
+    pokemon_count: Option<u8>,
+    badges_earned: Option<u8>,
+    overworld_kind: Option<OverworldKind>,
 }
 
 impl SceneAnalysis {
@@ -21,9 +59,38 @@ impl SceneAnalysis {
             scene_type,
             confidence,
             timestamp: Instant::now(),
+            battle_menu_selection: None,
+            pokemon_count: None,
+            badges_earned: None,
+            overworld_kind: None,
         }
     }
 
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn with_battle_menu_selection(mut self, selection: Option<BattleMenuSelection>) -> Self {
+        self.battle_menu_selection = selection;
+        self
+    }
+
+    pub fn with_pokemon_count(mut self, count: Option<u8>) -> Self {
+        self.pokemon_count = count;
+        self
+    }
+
+    pub fn with_badges_earned(mut self, badges: Option<u8>) -> Self {
+        self.badges_earned = badges;
+        self
+    }
+
+    pub fn with_overworld_kind(mut self, kind: Option<OverworldKind>) -> Self {
+        self.overworld_kind = kind;
+        self
+    }
+
     pub fn scene_type(&self) -> SceneType {
         self.scene_type
     }
@@ -35,4 +102,24 @@ impl SceneAnalysis {
     pub fn timestamp(&self) -> Instant {
         self.timestamp
     }
+
+    pub fn battle_menu_selection(&self) -> Option<BattleMenuSelection> {
+        self.battle_menu_selection
+    }
+
+    // Number of party members, only known while the party menu is shown or when the emulator
+    // reports it.
+    pub fn pokemon_count(&self) -> Option<u8> {
+        self.pokemon_count
+    }
+
+    // Number of lit badges, only known while the trainer card is shown or when the emulator
+    // reports it.
+    pub fn badges_earned(&self) -> Option<u8> {
+        self.badges_earned
+    }
+    // Indoor, outdoor, cave or water, only known in the overworld.
+    pub fn overworld_kind(&self) -> Option<OverworldKind> {
+        self.overworld_kind
+    }
 }
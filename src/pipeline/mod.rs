@@ -1,3 +1,4 @@
 pub mod context;
+pub mod detection;
 pub mod domain;
 pub mod orchestration;
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+
+use crate::common::{ButtonPress, ButtonSet, GameAction};
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+// Buttons that must never be pressed in a scene, e.g. Start in the bag where it opens the
+// wrong submenu. Cheap domain knowledge applied to every input whoever chose it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionMask {
+    forbidden: HashMap<SceneType, ButtonSet>,
+}
+
+impl ActionMask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(forbidden: &HashMap<SceneType, Vec<GameAction>>) -> Self {
+        forbidden
+            .iter()
+            .fold(Self::new(), |mask, (scene_type, actions)| {
+                mask.forbid(*scene_type, actions.iter().copied().collect::<ButtonSet>())
+            })
+    }
+
+    pub fn forbid(mut self, scene_type: SceneType, buttons: impl Into<ButtonSet>) -> Self {
+        let forbidden = self.forbidden.entry(scene_type).or_default();
+        *forbidden = buttons.into().iter().fold(*forbidden, ButtonSet::with);
+        self
+    }
+
+    pub fn allows(&self, scene_type: SceneType, action: GameAction) -> bool {
+        self.forbidden
+            .get(&scene_type)
+            .is_none_or(|forbidden| !forbidden.contains(action))
+    }
+
+    // The press without its forbidden buttons, None when nothing is left of it. Waits have no
+    // buttons to forbid and always pass.
+    pub fn apply(&self, scene_type: SceneType, press: ButtonPress) -> Option<ButtonPress> {
+        let Some(forbidden) = self.forbidden.get(&scene_type) else {
+            return Some(press);
+        };
+        let allowed = press.buttons().without(*forbidden);
+        (press.is_wait() || !allowed.is_empty()).then(|| press.with_buttons(allowed))
+    }
+}
+
+// Action channel that masks every press against the scene it answers before sending it.
+#[derive(Clone)]
+pub struct MaskedActionSender {
+    action_tx: Sender<ButtonPress>,
+    mask: Arc<ActionMask>,
+}
+
+impl MaskedActionSender {
+    pub fn new(action_tx: Sender<ButtonPress>, mask: Arc<ActionMask>) -> Self {
+        Self { action_tx, mask }
+    }
+
+    // Presses masked down to nothing are dropped and count as sent.
+    pub fn try_send(
+        &self,
+        scene_type: SceneType,
+        press: ButtonPress,
+    ) -> Result<(), TrySendError<ButtonPress>> {
+        match self.mask.apply(scene_type, press) {
+            Some(press) => self.action_tx.try_send(press),
+            None => {
+                tracing::debug!("Dropped {:?}, forbidden in {:?}", press, scene_type);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu_mask() -> ActionMask {
+        ActionMask::new().forbid(
+            SceneType::Bag,
+            ButtonSet::from(GameAction::Start).with(GameAction::Select),
+        )
+    }
+
+    #[test]
+    fn forbidden_buttons_are_stripped_in_their_scene_only() {
+        let mask = menu_mask();
+        let start = ButtonPress::tap(GameAction::Start);
+        assert_eq!(mask.apply(SceneType::Bag, start), None);
+        assert_eq!(mask.apply(SceneType::Overworld, start), Some(start));
+        assert!(!mask.allows(SceneType::Bag, GameAction::Start));
+        assert!(mask.allows(SceneType::Bag, GameAction::B));
+
+        let combo = ButtonPress::tap(ButtonSet::from(GameAction::B).with(GameAction::Start));
+        assert_eq!(
+            mask.apply(SceneType::Bag, combo),
+            Some(ButtonPress::tap(GameAction::B))
+        );
+        let wait = ButtonPress::wait(std::time::Duration::from_millis(100));
+        assert_eq!(mask.apply(SceneType::Bag, wait), Some(wait));
+    }
+
+    #[test]
+    fn masked_sender_never_delivers_forbidden_buttons() {
+        let (action_tx, mut action_rx) = tokio::sync::mpsc::channel(8);
+        let sender = MaskedActionSender::new(action_tx, Arc::new(menu_mask()));
+        for _ in 0..3 {
+            sender
+                .try_send(SceneType::Bag, ButtonPress::tap(GameAction::Start))
+                .unwrap();
+        }
+        sender
+            .try_send(SceneType::Bag, ButtonPress::tap(GameAction::B))
+            .unwrap();
+
+        assert_eq!(action_rx.try_recv(), Ok(ButtonPress::tap(GameAction::B)));
+        assert!(action_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::common::{ButtonPress, GameAction};
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+// Spots a client that has fallen back into the game's attract/demo loop. The screen keeps
+// changing on its own, so the stuck watchdog never fires, but the scenes repeat in a fixed
+// cycle through the title screen no matter what is pressed. Start leaves the demo.
+pub struct AttractModeWatch {
+    min_repeats: usize,
+    max_cycle_len: usize,
+    cooldown: Duration,
+    clients: HashMap<Uuid, SceneHistory>,
+}
+
+#[derive(Default)]
+struct SceneHistory {
+    scenes: VecDeque<SceneType>,
+    last_press: Option<Instant>,
+}
+
+impl AttractModeWatch {
+    pub fn new() -> Self {
+        Self {
+            min_repeats: 2,
+            max_cycle_len: 4,
+            cooldown: Duration::from_secs(5),
+            clients: HashMap::new(),
+        }
+    }
+
+    // Minimum time between two Start presses, the title screen needs a moment to react.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    // Records the latest scene of a client and returns Start once a demo loop is recognized.
+    pub fn observe(
+        &mut self,
+        client_id: Uuid,
+        scene_type: SceneType,
+        now: Instant,
+    ) -> Option<ButtonPress> {
+        // Fades separate the demo's scenes and unknown frames say nothing either way.
+        if matches!(scene_type, SceneType::Unknown | SceneType::FadeTransition) {
+            return None;
+        }
+        let history = self.clients.entry(client_id).or_default();
+        if history.scenes.back() == Some(&scene_type) {
+            return None;
+        }
+        history.scenes.push_back(scene_type);
+        if history.scenes.len() > self.max_cycle_len * self.min_repeats {
+            history.scenes.pop_front();
+        }
+
+        if history
+            .last_press
+            .is_some_and(|last| now.saturating_duration_since(last) < self.cooldown)
+        {
+            return None;
+        }
+        let cycle_len = Self::cycle_len(&history.scenes, self.min_repeats, self.max_cycle_len)?;
+        history.scenes.clear();
+        history.last_press = Some(now);
+        tracing::warn!(
+            "Client {} is repeating a {}-scene cycle through the title screen, pressing Start to leave the demo",
+            client_id,
+            cycle_len
+        );
+        Some(ButtonPress::tap(GameAction::Start))
+    }
+
+    pub fn is_cycling(&self, client_id: Uuid) -> bool {
+        self.clients.get(&client_id).is_some_and(|history| {
+            Self::cycle_len(&history.scenes, self.min_repeats, self.max_cycle_len).is_some()
+        })
+    }
+
+    // Length of the shortest cycle that the most recent scenes repeat `min_repeats` times.
+    fn cycle_len(
+        scenes: &VecDeque<SceneType>,
+        min_repeats: usize,
+        max_cycle_len: usize,
+    ) -> Option<usize> {
+        (2..=max_cycle_len).find(|&len| {
+            let needed = len * min_repeats;
+            if scenes.len() < needed {
+                return false;
+            }
+            let tail: Vec<SceneType> = scenes.iter().skip(scenes.len() - needed).copied().collect();
+            tail[..len].contains(&SceneType::TitleScreen)
+                && tail
+                    .iter()
+                    .enumerate()
+                    .all(|(i, scene)| *scene == tail[i % len])
+        })
+    }
+}
+
+impl Default for AttractModeWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(
+        watch: &mut AttractModeWatch,
+        client: Uuid,
+        scenes: &[SceneType],
+        start: Instant,
+    ) -> Vec<Option<ButtonPress>> {
+        scenes
+            .iter()
+            .enumerate()
+            .map(|(i, scene)| watch.observe(client, *scene, start + Duration::from_secs(i as u64)))
+            .collect()
+    }
+
+    #[test]
+    fn demo_loop_through_the_title_screen_presses_start() {
+        let mut watch = AttractModeWatch::new();
+        let client = Uuid::new_v4();
+        let demo = [
+            SceneType::TitleScreen,
+            SceneType::FadeTransition,
+            SceneType::Overworld,
+            SceneType::Battle,
+            SceneType::TitleScreen,
+            SceneType::Overworld,
+            SceneType::Overworld,
+            SceneType::Battle,
+        ];
+
+        let presses = play(&mut watch, client, &demo, Instant::now());
+        assert!(presses[..7].iter().all(Option::is_none));
+        assert_eq!(presses[7], Some(ButtonPress::tap(GameAction::Start)));
+        assert!(!watch.is_cycling(client));
+    }
+
+    #[test]
+    fn gameplay_cycles_away_from_the_title_screen_are_left_alone() {
+        let mut watch = AttractModeWatch::new();
+        let client = Uuid::new_v4();
+        let grinding = [SceneType::Overworld, SceneType::Battle].repeat(4);
+
+        let presses = play(&mut watch, client, &grinding, Instant::now());
+        assert!(presses.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn start_is_not_repeated_within_the_cooldown() {
+        let mut watch = AttractModeWatch::new().with_cooldown(Duration::from_secs(60));
+        let client = Uuid::new_v4();
+        let demo = [SceneType::TitleScreen, SceneType::Cutscene].repeat(4);
+
+        let presses = play(&mut watch, client, &demo, Instant::now());
+        assert_eq!(presses.iter().flatten().count(), 1);
+        assert!(watch.is_cycling(client));
+    }
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::common::Frame;
+use crate::error::AppError;
+use crate::pipeline::context::frame_context::FrameContext;
+use crate::pipeline::domain::scene_analysis::SceneAnalysis;
+use crate::pipeline::orchestration::processing_pipeline::AnalyzerStep;
+
+// Runs the analyzer over every image in a directory, sorted by file name, without an
+// emulator. Files that can't be decoded as images are skipped with a warning.
+pub async fn analyze_directory(
+    directory: &Path,
+    analyzer: &dyn AnalyzerStep,
+) -> Result<Vec<(PathBuf, SceneAnalysis)>, AppError> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+
+    // All screenshots are treated as coming from the same client.
+    let client_id = Uuid::new_v4();
+    let mut results = Vec::with_capacity(paths.len());
+    for path in paths {
+        let image = match image::open(&path) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::warn!("Skipping {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let frame = Frame::new(client_id, image, Utc::now(), Uuid::new_v4());
+        let analysis = analyzer.analyze(&FrameContext::new(frame)).await?;
+        results.push((path, analysis));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    #[tokio::test]
+    async fn classifies_every_image_and_skips_other_files() {
+        let directory = std::env::temp_dir().join(format!("pokebot-batch-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        RgbImage::from_pixel(64, 48, Rgb([255, 255, 255]))
+            .save(directory.join("a.png"))
+            .unwrap();
+        RgbImage::from_pixel(64, 48, Rgb([0, 0, 0]))
+            .save(directory.join("b.png"))
+            .unwrap();
+        fs::write(directory.join("notes.txt"), "not an image").unwrap();
+
+        let results = analyze_directory(&directory, &SceneAnalyzer::new())
+            .await
+            .unwrap();
+        let names: Vec<_> = results
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["a.png", "b.png"]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
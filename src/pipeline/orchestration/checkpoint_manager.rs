@@ -0,0 +1,422 @@
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::emulator_client::{EmulatorControl, SaveStateCommand};
+use crate::pipeline::domain::scene_analysis::{OverworldKind, SceneAnalysis, SceneType};
+use crate::pipeline::orchestration::stuck_watchdog::Escalation;
+
+// Progress that earns a checkpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointTrigger {
+    // A trainer card shows more badges than any seen before.
+    Badge,
+    // The party screen lists more members than any seen before.
+    PartyGrowth,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckpointPolicy {
+    // Save-state slots used in rotation, the oldest checkpoint is overwritten first.
+    pub slots: u16,
+    pub triggers: Vec<CheckpointTrigger>,
+    // Analyses in a row that have to read the same count before it counts as progress, so a
+    // single misread frame doesn't checkpoint or raise the count to beat.
+    pub confirm_frames: u32,
+    // Rewinds once the stuck watchdog escalates this far on a client.
+    pub rewind_after: Escalation,
+    // Rewinds when the party blacks out and the client wakes up in a Pokemon Center. Needs the
+    // battle detector and the overworld detector's indoor floors, both in the Pokemon registry.
+    pub rewind_on_whiteout: bool,
+}
+
+impl Default for CheckpointPolicy {
+    fn default() -> Self {
+        Self {
+            slots: 3,
+            triggers: vec![CheckpointTrigger::Badge, CheckpointTrigger::PartyGrowth],
+            confirm_frames: 3,
+            rewind_after: Escalation::StartOrB,
+            rewind_on_whiteout: true,
+        }
+    }
+}
+
+// Saves a state whenever the analyses show progress and rewinds to the latest one when the
+// game gets stuck or whites out, so a softlock or a lost battle costs the time since the last
+// checkpoint instead of a reset. Each checkpoint is rewound to once, a client stuck again
+// right after goes to the watchdog.
+pub struct CheckpointManager {
+    policy: CheckpointPolicy,
+    control: EmulatorControl,
+    next_slot: u16,
+    latest: Option<u16>,
+    rewound: bool,
+    badges: ProgressCount,
+    party_size: ProgressCount,
+    // Kind of the last overworld seen, and of the one the current battle started from.
+    overworld: Option<OverworldKind>,
+    battle_from: Option<OverworldKind>,
+}
+
+impl CheckpointManager {
+    pub fn new(policy: CheckpointPolicy, control: EmulatorControl) -> Self {
+        Self {
+            policy,
+            control,
+            next_slot: 0,
+            latest: None,
+            rewound: false,
+            badges: ProgressCount::default(),
+            party_size: ProgressCount::default(),
+            overworld: None,
+            battle_from: None,
+        }
+    }
+
+    pub fn latest_checkpoint(&self) -> Option<u16> {
+        self.latest
+    }
+
+    // Saves a checkpoint when `analysis` shows progress on one of the configured triggers, or
+    // rewinds when it shows a whiteout and the policy asks for it.
+    // The first confirmed count for a trigger is the baseline, not progress.
+    pub fn observe(&mut self, analysis: &SceneAnalysis) -> Option<SaveStateCommand> {
+        if self.whited_out(analysis) && self.policy.rewind_on_whiteout {
+            tracing::warn!("Party whited out, rewinding to the latest checkpoint");
+            return self.rewind();
+        }
+        let confirm_frames = self.policy.confirm_frames;
+        let progressed = self
+            .policy
+            .triggers
+            .iter()
+            .filter(|trigger| match trigger {
+                CheckpointTrigger::Badge => self
+                    .badges
+                    .advance(analysis.badges_earned(), confirm_frames),
+                CheckpointTrigger::PartyGrowth => self
+                    .party_size
+                    .advance(analysis.pokemon_count(), confirm_frames),
+            })
+            .count()
+            > 0;
+        if !progressed || self.policy.slots == 0 {
+            return None;
+        }
+
+        let slot = self.next_slot;
+        self.next_slot = (slot + 1) % self.policy.slots;
+        self.latest = Some(slot);
+        self.rewound = false;
+        tracing::info!(
+            "Progress in {:?}, checkpointing to slot {}",
+            analysis.scene_type(),
+            slot
+        );
+        Some(self.send(SaveStateCommand::Save(slot)))
+    }
+
+    // Rewinds when the watchdog escalated at least as far as the policy allows.
+    pub fn on_stuck(&mut self, escalation: Escalation) -> Option<SaveStateCommand> {
+        if escalation < self.policy.rewind_after {
+            return None;
+        }
+        self.rewind()
+    }
+
+    // Loads the latest checkpoint, None when there is none or it was already rewound to.
+    pub fn rewind(&mut self) -> Option<SaveStateCommand> {
+        let slot = self.latest.filter(|_| !self.rewound)?;
+        self.rewound = true;
+        Some(self.send(SaveStateCommand::Load(slot)))
+    }
+
+    // A battle started outside that ends, past any fades, indoors is the blackout warp to the
+    // last Pokemon Center. Battles won or run from return to where they started.
+    fn whited_out(&mut self, analysis: &SceneAnalysis) -> bool {
+        match analysis.scene_type() {
+            SceneType::Battle => {
+                if self.battle_from.is_none() {
+                    self.battle_from = self.overworld;
+                }
+                false
+            }
+            SceneType::Overworld => {
+                let kind = analysis.overworld_kind();
+                let from = self.battle_from.take();
+                self.overworld = kind.or(self.overworld);
+                kind == Some(OverworldKind::Indoor)
+                    && from.is_some_and(|from| from != OverworldKind::Indoor)
+            }
+            _ => false,
+        }
+    }
+
+    fn send(&self, command: SaveStateCommand) -> SaveStateCommand {
+        self.control.request_save_state(command);
+        command
+    }
+}
+
+// High-water mark of a count read off the screen, e.g. the badges on the trainer card.
+#[derive(Default)]
+struct ProgressCount {
+    best: Option<u8>,
+    // The count read on the latest analyses and how many in a row read it.
+    pending: Option<(u8, u32)>,
+}
+
+impl ProgressCount {
+    // Raises the high-water mark to `seen` once it was read on `confirm_frames` analyses in a
+    // row, returns whether that beat an earlier count. An analysis without a count breaks the row.
+    fn advance(&mut self, seen: Option<u8>, confirm_frames: u32) -> bool {
+        let Some(seen) = seen else {
+            self.pending = None;
+            return false;
+        };
+        let streak = match self.pending {
+            Some((count, streak)) if count == seen => streak.saturating_add(1),
+            _ => 1,
+        };
+        self.pending = Some((seen, streak));
+        if streak < confirm_frames.max(1) {
+            return false;
+        }
+        let progressed = self.best.is_some_and(|best| seen > best);
+        if self.best.is_none_or(|best| seen > best) {
+            self.best = Some(seen);
+        }
+        progressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use image::{DynamicImage, Rgb};
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::common::frame::Frame;
+    use crate::pipeline::context::frame_context::FrameContext;
+    use crate::pipeline::detection::image_region::ImageRegion;
+    use crate::pipeline::detection::synthetic_frames::{HEIGHT, SyntheticFrame, WIDTH};
+    use crate::pipeline::domain::scene_analysis::{BattleMenuSelection, SceneType};
+    use crate::pipeline::orchestration::processing_pipeline::AnalyzerStep;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    fn card(badges: u8) -> SceneAnalysis {
+        SceneAnalysis::new(SceneType::TrainerCard, 0.9).with_badges_earned(Some(badges))
+    }
+
+    fn party(members: u8) -> SceneAnalysis {
+        SceneAnalysis::new(SceneType::PartyMenu, 0.85).with_pokemon_count(Some(members))
+    }
+
+    fn overworld(kind: OverworldKind) -> SceneAnalysis {
+        SceneAnalysis::new(SceneType::Overworld, 0.8).with_overworld_kind(Some(kind))
+    }
+
+    fn scene(scene_type: SceneType) -> SceneAnalysis {
+        SceneAnalysis::new(scene_type, 0.9)
+    }
+
+    // Observes `analysis` on as many frames in a row as the default policy needs to trust a
+    // count, returning the last result.
+    fn observe_steadily(
+        checkpoints: &mut CheckpointManager,
+        analysis: &SceneAnalysis,
+    ) -> Option<SaveStateCommand> {
+        let confirm_frames = CheckpointPolicy::default().confirm_frames;
+        (0..confirm_frames)
+            .map(|_| checkpoints.observe(analysis))
+            .last()
+            .flatten()
+    }
+
+    #[test]
+    fn new_badges_save_to_rotating_slots() {
+        let control = EmulatorControl::new();
+        let policy = CheckpointPolicy {
+            slots: 2,
+            triggers: vec![CheckpointTrigger::Badge],
+            ..CheckpointPolicy::default()
+        };
+        let mut checkpoints = CheckpointManager::new(policy, control.clone());
+
+        for analysis in [card(1), card(1), card(2), party(6), card(3), card(4)] {
+            observe_steadily(&mut checkpoints, &analysis);
+        }
+        assert_eq!(
+            control.take_save_states(),
+            vec![
+                SaveStateCommand::Save(0),
+                SaveStateCommand::Save(1),
+                SaveStateCommand::Save(0)
+            ]
+        );
+        assert_eq!(checkpoints.latest_checkpoint(), Some(0));
+    }
+
+    #[test]
+    fn party_growth_only_counts_when_configured() {
+        let control = EmulatorControl::new();
+        let mut checkpoints = CheckpointManager::new(CheckpointPolicy::default(), control.clone());
+        observe_steadily(&mut checkpoints, &party(1));
+        assert_eq!(
+            observe_steadily(&mut checkpoints, &party(2)),
+            Some(SaveStateCommand::Save(0))
+        );
+
+        let policy = CheckpointPolicy {
+            triggers: vec![CheckpointTrigger::Badge],
+            ..CheckpointPolicy::default()
+        };
+        let mut badges_only = CheckpointManager::new(policy, EmulatorControl::new());
+        observe_steadily(&mut badges_only, &party(1));
+        assert_eq!(observe_steadily(&mut badges_only, &party(2)), None);
+    }
+
+    #[test]
+    fn counts_read_on_too_few_frames_in_a_row_are_ignored() {
+        let control = EmulatorControl::new();
+        let mut checkpoints = CheckpointManager::new(CheckpointPolicy::default(), control.clone());
+        observe_steadily(&mut checkpoints, &card(1));
+
+        // A single misread doesn't checkpoint nor raise the count to beat.
+        for analysis in [
+            card(8),
+            card(8),
+            scene(SceneType::Overworld),
+            card(8),
+            card(1),
+        ] {
+            assert_eq!(checkpoints.observe(&analysis), None);
+        }
+        assert_eq!(
+            observe_steadily(&mut checkpoints, &card(2)),
+            Some(SaveStateCommand::Save(0))
+        );
+        assert_eq!(control.take_save_states(), vec![SaveStateCommand::Save(0)]);
+    }
+
+    #[test]
+    fn stuck_clients_rewind_once_per_checkpoint() {
+        let control = EmulatorControl::new();
+        let mut checkpoints = CheckpointManager::new(CheckpointPolicy::default(), control.clone());
+        assert_eq!(checkpoints.rewind(), None);
+
+        observe_steadily(&mut checkpoints, &card(0));
+        observe_steadily(&mut checkpoints, &card(1));
+        assert_eq!(checkpoints.rewind(), Some(SaveStateCommand::Load(0)));
+        assert_eq!(checkpoints.rewind(), None);
+        observe_steadily(&mut checkpoints, &card(2));
+        assert_eq!(checkpoints.rewind(), Some(SaveStateCommand::Load(1)));
+        assert_eq!(
+            control.take_save_states(),
+            vec![
+                SaveStateCommand::Save(0),
+                SaveStateCommand::Load(0),
+                SaveStateCommand::Save(1),
+                SaveStateCommand::Load(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn rewinds_once_the_watchdog_escalates_far_enough() {
+        let policy = CheckpointPolicy {
+            rewind_after: Escalation::SoftReset,
+            ..CheckpointPolicy::default()
+        };
+        let mut checkpoints = CheckpointManager::new(policy, EmulatorControl::new());
+        observe_steadily(&mut checkpoints, &card(0));
+        observe_steadily(&mut checkpoints, &card(1));
+
+        assert_eq!(checkpoints.on_stuck(Escalation::StartOrB), None);
+        assert_eq!(
+            checkpoints.on_stuck(Escalation::SoftReset),
+            Some(SaveStateCommand::Load(0))
+        );
+    }
+
+    #[test]
+    fn whiteouts_rewind_but_battles_that_end_outside_do_not() {
+        let control = EmulatorControl::new();
+        let mut checkpoints = CheckpointManager::new(CheckpointPolicy::default(), control.clone());
+        observe_steadily(&mut checkpoints, &card(0));
+        observe_steadily(&mut checkpoints, &card(1));
+
+        for analysis in [
+            overworld(OverworldKind::Outdoor),
+            scene(SceneType::Battle),
+            overworld(OverworldKind::Outdoor),
+            overworld(OverworldKind::Indoor),
+            overworld(OverworldKind::Cave),
+            scene(SceneType::Battle),
+            scene(SceneType::FadeTransition),
+        ] {
+            assert_eq!(checkpoints.observe(&analysis), None);
+        }
+        assert_eq!(
+            checkpoints.observe(&overworld(OverworldKind::Indoor)),
+            Some(SaveStateCommand::Load(0))
+        );
+        assert_eq!(
+            control.take_save_states(),
+            vec![SaveStateCommand::Save(0), SaveStateCommand::Load(0)]
+        );
+
+        let policy = CheckpointPolicy {
+            rewind_on_whiteout: false,
+            ..CheckpointPolicy::default()
+        };
+        let mut checkpoints = CheckpointManager::new(policy, EmulatorControl::new());
+        for analysis in [
+            card(0),
+            card(1),
+            overworld(OverworldKind::Water),
+            scene(SceneType::Battle),
+            overworld(OverworldKind::Indoor),
+        ] {
+            assert_ne!(
+                checkpoints.observe(&analysis),
+                Some(SaveStateCommand::Load(0))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn the_pokemon_detectors_see_a_whiteout() {
+        let analyzer = SceneAnalyzer::new();
+        let route = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([96, 200, 88]));
+        let center = SyntheticFrame::blank(WIDTH, HEIGHT, Rgb([248, 176, 200]))
+            .with_fill(ImageRegion::new(64, 48, 128, 16), Rgb([200, 80, 60]));
+        let mut checkpoints =
+            CheckpointManager::new(CheckpointPolicy::default(), EmulatorControl::new());
+        observe_steadily(&mut checkpoints, &card(0));
+        observe_steadily(&mut checkpoints, &card(1));
+
+        let mut results = Vec::new();
+        for frame in [
+            route,
+            SyntheticFrame::battle_menu(BattleMenuSelection::Fight),
+            SyntheticFrame::fade(Rgb([255, 255, 255])),
+            center,
+        ] {
+            let context = FrameContext::new(Frame::new(
+                Uuid::new_v4(),
+                DynamicImage::ImageRgb8(frame.build()),
+                Utc::now(),
+                Uuid::new_v4(),
+            ));
+            let analysis = analyzer.analyze(&context).await.unwrap();
+            results.push(checkpoints.observe(&analysis));
+        }
+        assert_eq!(
+            results,
+            vec![None, None, None, Some(SaveStateCommand::Load(0))]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use image::DynamicImage;
+use uuid::Uuid;
+
+use crate::pipeline::detection::image_region::ImageRegion;
+use crate::pipeline::orchestration::frame_decimator::ChangeHeatmap;
+
+// Tiles per side the dialog box is compared on, fine enough that a single new character
+// moves its tile's mean.
+const DIALOG_TILES: u32 = 8;
+
+// Tells whether dialog text is still printing by comparing the dialog box between a client's
+// consecutive frames: characters appearing one by one keep part of the box changing. Pressing A
+// before the box is static skips the rest of the text.
+pub struct DialogPrintWatch {
+    // Dialog box as fractions of the main screen: left, top, width, height. On DS frames that
+    // is the top screen, the touch screen below is ignored.
+    region: (f32, f32, f32, f32),
+    min_change: f32,
+    clients: HashMap<Uuid, (DynamicImage, bool)>,
+}
+
+impl DialogPrintWatch {
+    pub fn new() -> Self {
+        Self {
+            region: (0.0, 0.75, 1.0, 0.25),
+            min_change: 0.02,
+            clients: HashMap::new(),
+        }
+    }
+
+    // Records the latest frame of a client and returns whether its dialog text is printing.
+    pub fn observe(&mut self, client_id: Uuid, image: &DynamicImage) -> bool {
+        let (left, top, width, height) = self.region;
+        let screen = ImageRegion::main_screen(image.width(), image.height());
+        let region =
+            ImageRegion::from_fractions(screen.width, screen.height, left, top, width, height);
+        let dialog = DynamicImage::ImageRgb8(region.crop(&image.to_rgb8()).to_image());
+        let printing = self.clients.get(&client_id).is_some_and(|(previous, _)| {
+            ChangeHeatmap::between(previous, &dialog, DIALOG_TILES).max() >= self.min_change
+        });
+        self.clients.insert(client_id, (dialog, printing));
+        printing
+    }
+
+    pub fn is_printing(&self, client_id: Uuid) -> bool {
+        self.clients
+            .get(&client_id)
+            .is_some_and(|(_, printing)| *printing)
+    }
+
+    // Whether advancing the dialog now is safe, i.e. the text finished printing.
+    pub fn allows_advance(&self, client_id: Uuid) -> bool {
+        !self.is_printing(client_id)
+    }
+}
+
+impl Default for DialogPrintWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+
+    use super::*;
+    use crate::pipeline::detection::image_region::ImageRegion;
+    use crate::pipeline::detection::synthetic_frames::{SyntheticFrame, WIDTH};
+
+    const GRASS: Rgb<u8> = Rgb([90, 180, 90]);
+
+    fn dialog_frame(characters: u32, overworld: Rgb<u8>) -> DynamicImage {
+        DynamicImage::ImageRgb8(SyntheticFrame::dialog(characters, overworld).build())
+    }
+
+    #[test]
+    fn appearing_characters_hold_the_advance() {
+        let mut watch = DialogPrintWatch::new();
+        let client = Uuid::new_v4();
+
+        assert!(!watch.observe(client, &dialog_frame(0, GRASS)));
+        for characters in 1..=4 {
+            assert!(watch.observe(client, &dialog_frame(characters, GRASS)));
+            assert!(!watch.allows_advance(client));
+        }
+        assert!(!watch.observe(client, &dialog_frame(4, GRASS)));
+        assert!(watch.allows_advance(client));
+    }
+
+    #[test]
+    fn changes_outside_the_dialog_box_are_ignored() {
+        let mut watch = DialogPrintWatch::new();
+        let client = Uuid::new_v4();
+        watch.observe(client, &dialog_frame(12, GRASS));
+        assert!(!watch.observe(client, &dialog_frame(12, Rgb([20, 20, 60]))));
+        assert!(watch.allows_advance(client));
+        assert!(watch.allows_advance(Uuid::new_v4()));
+    }
+
+    #[test]
+    fn the_touch_screen_is_not_the_dialog_box() {
+        let mut watch = DialogPrintWatch::new();
+        let client = Uuid::new_v4();
+        let touched = SyntheticFrame::dialog(12, GRASS)
+            .with_fill(ImageRegion::new(0, 300, WIDTH, 84), Rgb([20, 20, 20]))
+            .build();
+        watch.observe(client, &dialog_frame(12, GRASS));
+        assert!(!watch.observe(client, &DynamicImage::ImageRgb8(touched)));
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::common::{ButtonPress, GameAction};
+
+// Shortest hold handed out, one frame at 60 frames per second.
+const MIN_HOLD: Duration = Duration::from_micros(16_667);
+// Weight of each new measurement, from 0.0 (never adapt) to 1.0 (only trust the latest).
+const SMOOTHING: f32 = 0.3;
+// Walks that change nothing for this long ran into a wall and are not measured.
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+// Learns, per client, how long it takes from sending a walk to the screen starting to move, and
+// sizes walk holds to match. A fixed hold that takes a step on one setup falls short on a slower
+// one and overshoots on a faster one. Measured in time rather than frames, since a client that
+// drops frames would otherwise look faster than it is.
+pub struct EffectLatencyEstimator {
+    default_hold: Duration,
+    clients: HashMap<Uuid, ClientLatency>,
+}
+
+struct ClientLatency {
+    estimate: Duration,
+    // When the last walk was sent, None when no walk is waiting for its effect.
+    waiting: Option<Instant>,
+}
+
+impl EffectLatencyEstimator {
+    pub fn new(default_hold: Duration) -> Self {
+        Self {
+            default_hold,
+            clients: HashMap::new(),
+        }
+    }
+
+    // Starts timing the effect of a walk just sent by a client.
+    pub fn on_walk_sent(&mut self, client_id: Uuid, now: Instant) {
+        let default_hold = self.default_hold;
+        self.clients
+            .entry(client_id)
+            .or_insert_with(|| ClientLatency {
+                estimate: default_hold,
+                waiting: None,
+            })
+            .waiting = Some(now);
+    }
+
+    // Records a frame from a client, `changed` being the decimator's change signal for it.
+    pub fn on_frame(&mut self, client_id: Uuid, changed: bool, now: Instant) {
+        let Some(client) = self.clients.get_mut(&client_id) else {
+            return;
+        };
+        let Some(sent) = client.waiting else {
+            return;
+        };
+        let elapsed = now.saturating_duration_since(sent);
+        if elapsed >= MAX_WAIT {
+            client.waiting = None;
+        } else if changed {
+            client.estimate = client.estimate.mul_f32(1.0 - SMOOTHING) + elapsed.mul_f32(SMOOTHING);
+            client.waiting = None;
+        }
+    }
+
+    // How long a walk by this client should be held for.
+    pub fn hold(&self, client_id: Uuid) -> Duration {
+        self.clients
+            .get(&client_id)
+            .map_or(self.default_hold, |client| client.estimate)
+            .max(MIN_HOLD)
+    }
+
+    pub fn walk(&self, client_id: Uuid, direction: GameAction) -> ButtonPress {
+        ButtonPress::hold(direction, self.hold(client_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_HOLD: Duration = Duration::from_millis(100);
+
+    // Sends a walk and reports `frames` frames evenly spread over `latency`, only the last one
+    // changed.
+    fn walk_with_latency(
+        estimator: &mut EffectLatencyEstimator,
+        client: Uuid,
+        latency: Duration,
+        frames: u32,
+    ) {
+        let sent = Instant::now();
+        estimator.on_walk_sent(client, sent);
+        for frame in 1..=frames {
+            estimator.on_frame(client, frame == frames, sent + latency * frame / frames);
+        }
+    }
+
+    #[test]
+    fn slow_effects_raise_the_hold_over_several_walks() {
+        let mut estimator = EffectLatencyEstimator::new(DEFAULT_HOLD);
+        let client = Uuid::new_v4();
+        assert_eq!(estimator.hold(client), DEFAULT_HOLD);
+
+        let latency = Duration::from_millis(170);
+        let mut previous = DEFAULT_HOLD;
+        for _ in 0..10 {
+            walk_with_latency(&mut estimator, client, latency, 10);
+            assert!(estimator.hold(client) >= previous);
+            previous = estimator.hold(client);
+        }
+        assert!(latency - estimator.hold(client) < Duration::from_millis(5));
+        assert_eq!(
+            estimator.walk(client, GameAction::Up),
+            ButtonPress::hold(GameAction::Up, estimator.hold(client))
+        );
+        assert_eq!(estimator.hold(Uuid::new_v4()), DEFAULT_HOLD);
+    }
+
+    #[test]
+    fn dropped_frames_do_not_shorten_the_hold() {
+        let mut estimator = EffectLatencyEstimator::new(DEFAULT_HOLD);
+        let (smooth, choppy) = (Uuid::new_v4(), Uuid::new_v4());
+        let latency = Duration::from_millis(200);
+        walk_with_latency(&mut estimator, smooth, latency, 12);
+        walk_with_latency(&mut estimator, choppy, latency, 3);
+        assert_eq!(estimator.hold(smooth), estimator.hold(choppy));
+    }
+
+    #[test]
+    fn walks_into_walls_are_not_measured() {
+        let mut estimator = EffectLatencyEstimator::new(DEFAULT_HOLD);
+        let client = Uuid::new_v4();
+        let sent = Instant::now();
+        estimator.on_walk_sent(client, sent);
+        estimator.on_frame(client, false, sent + MAX_WAIT);
+        // The screen changing long after the walk gave up is something else moving.
+        estimator.on_frame(client, true, sent + MAX_WAIT * 2);
+        assert_eq!(estimator.hold(client), DEFAULT_HOLD);
+    }
+}
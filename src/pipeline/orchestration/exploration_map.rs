@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::common::GameAction;
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+// Time the player takes to walk one tile, 16 frames at 60fps.
+const TILE_WALK_TIME: Duration = Duration::from_millis(267);
+
+// Coarse map of where each client has walked: one cell per tile walked in the overworld, keyed
+// by the displacement summed from those steps. A tap or a hold shorter than a tile's walk counts
+// as one tile, longer holds as one tile per TILE_WALK_TIME. It is an estimate, walking into a
+// wall still counts as a move, but it tells new ground from walking in circles. Directions
+// pressed outside the overworld move menu cursors, not the player, and are ignored.
+#[derive(Default)]
+pub struct ExplorationMap {
+    clients: HashMap<Uuid, ClientMap>,
+}
+
+#[derive(Default)]
+struct ClientMap {
+    position: (i32, i32),
+    visits: HashMap<(i32, i32), u32>,
+    steps: u32,
+}
+
+impl ExplorationMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Records a press by a client in `scene_type`, held for `held` (zero for a tap). Returns true
+    // when it walked onto at least one new cell.
+    pub fn record_move(
+        &mut self,
+        client_id: Uuid,
+        action: GameAction,
+        scene_type: SceneType,
+        held: Duration,
+    ) -> bool {
+        let step = match action {
+            GameAction::Up => (0, -1),
+            GameAction::Down => (0, 1),
+            GameAction::Left => (-1, 0),
+            GameAction::Right => (1, 0),
+            _ => return false,
+        };
+        if scene_type != SceneType::Overworld {
+            return false;
+        }
+        let map = self.clients.entry(client_id).or_insert_with(|| ClientMap {
+            visits: HashMap::from([((0, 0), 1)]),
+            ..ClientMap::default()
+        });
+        let tiles = (held.as_millis() / TILE_WALK_TIME.as_millis()).max(1) as u32;
+        let mut new_ground = false;
+        for _ in 0..tiles {
+            map.position = (map.position.0 + step.0, map.position.1 + step.1);
+            map.steps += 1;
+            let visits = map.visits.entry(map.position).or_default();
+            *visits += 1;
+            new_ground |= *visits == 1;
+        }
+        new_ground
+    }
+
+    pub fn position(&self, client_id: Uuid) -> (i32, i32) {
+        self.clients
+            .get(&client_id)
+            .map_or((0, 0), |map| map.position)
+    }
+
+    pub fn visited_cells(&self, client_id: Uuid) -> usize {
+        self.clients
+            .get(&client_id)
+            .map_or(0, |map| map.visits.len())
+    }
+
+    // Share of the client's steps that reached a cell for the first time, 1.0 before any step.
+    pub fn coverage(&self, client_id: Uuid) -> f32 {
+        match self.clients.get(&client_id) {
+            Some(map) if map.steps > 0 => (map.visits.len() - 1) as f32 / map.steps as f32,
+            _ => 1.0,
+        }
+    }
+
+    // Whether the client stands on a cell it had already visited.
+    pub fn is_revisiting(&self, client_id: Uuid) -> bool {
+        self.clients
+            .get(&client_id)
+            .and_then(|map| map.visits.get(&map.position))
+            .is_some_and(|visits| *visits > 1)
+    }
+
+    pub fn forget(&mut self, client_id: Uuid) {
+        self.clients.remove(&client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(map: &mut ExplorationMap, client: Uuid, path: &[GameAction]) -> Vec<bool> {
+        path.iter()
+            .map(|action| map.record_move(client, *action, SceneType::Overworld, Duration::ZERO))
+            .collect()
+    }
+
+    #[test]
+    fn new_cells_are_marked_and_loops_detected() {
+        let mut map = ExplorationMap::new();
+        let client = Uuid::new_v4();
+
+        let new_cells = walk(
+            &mut map,
+            client,
+            &[
+                GameAction::Up,
+                GameAction::Up,
+                GameAction::Right,
+                GameAction::Down,
+            ],
+        );
+        assert_eq!(new_cells, [true, true, true, true]);
+        assert!(!map.is_revisiting(client));
+        assert_eq!(map.position(client), (1, -1));
+
+        // Back left onto the cell walked through on the way up.
+        assert!(!map.record_move(
+            client,
+            GameAction::Left,
+            SceneType::Overworld,
+            Duration::ZERO
+        ));
+        assert!(map.is_revisiting(client));
+        assert_eq!(map.visited_cells(client), 5);
+        assert_eq!(map.coverage(client), 0.8);
+    }
+
+    #[test]
+    fn only_overworld_directions_move_the_player() {
+        let mut map = ExplorationMap::new();
+        let client = Uuid::new_v4();
+        assert!(!map.record_move(client, GameAction::Down, SceneType::Menu, Duration::ZERO));
+        assert!(!map.record_move(client, GameAction::A, SceneType::Overworld, Duration::ZERO));
+        assert_eq!(map.position(client), (0, 0));
+        assert_eq!(map.coverage(client), 1.0);
+
+        // Pacing back and forth covers little ground.
+        walk(
+            &mut map,
+            client,
+            &[GameAction::Left, GameAction::Right].repeat(5),
+        );
+        assert!(map.is_revisiting(client));
+        assert_eq!(map.coverage(client), 0.1);
+        assert_eq!(map.visited_cells(Uuid::new_v4()), 0);
+    }
+
+    #[test]
+    fn long_holds_walk_several_tiles() {
+        let mut map = ExplorationMap::new();
+        let client = Uuid::new_v4();
+        let held = TILE_WALK_TIME * 3;
+        assert!(map.record_move(client, GameAction::Right, SceneType::Overworld, held));
+        assert_eq!(map.position(client), (3, 0));
+        assert_eq!(map.visited_cells(client), 4);
+
+        // Walking back over the same tiles is no new ground, however long it is held.
+        assert!(!map.record_move(client, GameAction::Left, SceneType::Overworld, held));
+        assert_eq!(map.coverage(client), 0.5);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, GrayImage, Luma};
+use uuid::Uuid;
+
+use crate::common::Frame;
+use crate::pipeline::detection::image_stats::luma_thumbnail;
+
+// Which frames are worth analyzing at all, most consecutive frames at 60fps look the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameDecimation {
+    // Forward one out of every N frames of each client.
+    EveryNth(usize),
+    // Forward a frame only if it differs from the client's last forwarded frame by at least
+    // this much, as the mean luma difference of 8x8 thumbnails in 0.0..=1.0.
+    MinChange(f32),
+    // Compare each frame with the client's previous one on the same thumbnails. The client
+    // starts changing once a difference exceeds `rising` and settles again only below
+    // `falling`, so differences hovering around one threshold don't flip it every frame.
+    // Frames are forwarded while changing, plus the first settled frame.
+    Hysteresis { rising: f32, falling: f32 },
+}
+
+// Where two frames differ, as the luma difference of each tile of a `tiles` x `tiles` grid in
+// 0.0..=1.0. Shows which part of the screen drives the change signal, e.g. a blinking cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeHeatmap {
+    heat: Vec<Vec<f32>>,
+}
+
+impl ChangeHeatmap {
+    pub fn between(previous: &DynamicImage, current: &DynamicImage, tiles: u32) -> Self {
+        let tiles = tiles.max(1);
+        Self::from_thumbnails(
+            &luma_thumbnail(previous, tiles),
+            &luma_thumbnail(current, tiles),
+            tiles as usize,
+        )
+    }
+
+    fn from_thumbnails(previous: &[u8], current: &[u8], tiles: usize) -> Self {
+        let heat = previous
+            .chunks(tiles)
+            .zip(current.chunks(tiles))
+            .map(|(previous, current)| {
+                previous
+                    .iter()
+                    .zip(current)
+                    .map(|(a, b)| a.abs_diff(*b) as f32 / 255.0)
+                    .collect()
+            })
+            .collect();
+        Self { heat }
+    }
+
+    // Rows of tiles from the top, each from left to right.
+    pub fn rows(&self) -> &[Vec<f32>] {
+        &self.heat
+    }
+
+    pub fn max(&self) -> f32 {
+        self.heat.iter().flatten().copied().fold(0.0, f32::max)
+    }
+
+    // Grayscale rendering with `tile_size` pixels per tile, white where the change is largest.
+    pub fn to_image(&self, tile_size: u32) -> DynamicImage {
+        let rows = self.heat.len() as u32;
+        let columns = self.heat.first().map_or(0, Vec::len) as u32;
+        let tile_size = tile_size.max(1);
+        DynamicImage::ImageLuma8(GrayImage::from_fn(
+            columns * tile_size,
+            rows * tile_size,
+            |x, y| {
+                let heat = self.heat[(y / tile_size) as usize][(x / tile_size) as usize];
+                Luma([(heat * 255.0).round() as u8])
+            },
+        ))
+    }
+}
+
+// Applies a FrameDecimation per client before the expensive analysis.
+pub struct FrameDecimator {
+    decimation: FrameDecimation,
+    received: HashMap<Uuid, u64>,
+    last_forwarded: HashMap<Uuid, [u8; 64]>,
+    previous: HashMap<Uuid, ([u8; 64], bool)>,
+}
+
+impl FrameDecimator {
+    pub fn new(decimation: FrameDecimation) -> Self {
+        Self {
+            decimation,
+            received: HashMap::new(),
+            last_forwarded: HashMap::new(),
+            previous: HashMap::new(),
+        }
+    }
+
+    // Returns true if the frame should be analyzed.
+    pub fn forward(&mut self, frame: &Frame) -> bool {
+        let client_id = frame.get_client_id();
+        match self.decimation {
+            FrameDecimation::EveryNth(n) => {
+                let received = self.received.entry(client_id).or_default();
+                let forward = received.is_multiple_of(n.max(1) as u64);
+                *received += 1;
+                forward
+            }
+            FrameDecimation::MinChange(min_change) => {
+                let thumbnail = Self::thumbnail(frame.get_image());
+                let changed = self
+                    .last_forwarded
+                    .get(&client_id)
+                    .is_none_or(|last| Self::difference(last, &thumbnail) >= min_change);
+                if changed {
+                    self.last_forwarded.insert(client_id, thumbnail);
+                }
+                changed
+            }
+            FrameDecimation::Hysteresis { rising, falling } => {
+                let thumbnail = Self::thumbnail(frame.get_image());
+                let Some((previous, changing)) = self.previous.get_mut(&client_id) else {
+                    self.previous.insert(client_id, (thumbnail, false));
+                    self.last_forwarded.insert(client_id, thumbnail);
+                    return true;
+                };
+                let difference = Self::difference(previous, &thumbnail);
+                let was_changing = *changing;
+                *changing = if was_changing {
+                    difference >= falling
+                } else {
+                    difference > rising
+                };
+                let forward = *changing || was_changing;
+                *previous = thumbnail;
+                if forward {
+                    self.last_forwarded.insert(client_id, thumbnail);
+                }
+                forward
+            }
+        }
+    }
+
+    // Whether a client is currently changing under Hysteresis decimation.
+    pub fn is_changing(&self, client_id: Uuid) -> bool {
+        self.previous
+            .get(&client_id)
+            .is_some_and(|(_, changing)| *changing)
+    }
+
+    // Heatmap of the frame against the client's last forwarded frame on the same 8x8 grid
+    // MinChange compares. None in EveryNth mode or before the client's first frame.
+    pub fn heatmap(&self, frame: &Frame) -> Option<ChangeHeatmap> {
+        let last = self.last_forwarded.get(&frame.get_client_id())?;
+        Some(ChangeHeatmap::from_thumbnails(
+            last,
+            &Self::thumbnail(frame.get_image()),
+            8,
+        ))
+    }
+
+    fn thumbnail(image: &DynamicImage) -> [u8; 64] {
+        let mut thumbnail = [0u8; 64];
+        thumbnail.copy_from_slice(&luma_thumbnail(image, 8));
+        thumbnail
+    }
+
+    fn difference(a: &[u8; 64], b: &[u8; 64]) -> f32 {
+        let total: u32 = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b) as u32).sum();
+        total as f32 / (64.0 * 255.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    fn frame(client_id: Uuid, shade: u8) -> Frame {
+        Frame::new(
+            client_id,
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, Rgb([shade, shade, shade]))),
+            Utc::now(),
+            Uuid::new_v4(),
+        )
+    }
+
+    #[test]
+    fn identical_frames_only_pass_once() {
+        let mut decimator = FrameDecimator::new(FrameDecimation::MinChange(0.05));
+        let client = Uuid::new_v4();
+        let forwarded: Vec<bool> = (0..5)
+            .map(|_| decimator.forward(&frame(client, 100)))
+            .collect();
+        assert_eq!(forwarded, [true, false, false, false, false]);
+    }
+
+    #[test]
+    fn changing_frames_pass() {
+        let mut decimator = FrameDecimator::new(FrameDecimation::MinChange(0.05));
+        let client = Uuid::new_v4();
+        assert!(
+            [0, 60, 120, 180, 240]
+                .into_iter()
+                .all(|shade| decimator.forward(&frame(client, shade)))
+        );
+    }
+
+    #[test]
+    fn heatmap_is_hot_only_where_the_frame_changed() {
+        let previous = DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 64, Rgb([0, 0, 0])));
+        let current = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, y| {
+            if x < 16 && y < 16 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        }));
+
+        let heatmap = ChangeHeatmap::between(&previous, &current, 4);
+        assert_eq!(heatmap.rows().len(), 4);
+        assert!(heatmap.rows()[0][0] > 0.9);
+        assert!(heatmap.rows()[3][3] < 0.01);
+        assert!(heatmap.rows()[2][1] < 0.01);
+        assert!(heatmap.max() > 0.9);
+
+        let image = heatmap.to_image(2).to_luma8();
+        assert_eq!(image.dimensions(), (8, 8));
+        assert!(image.get_pixel(0, 0).0[0] > 230);
+        assert_eq!(image.get_pixel(7, 7).0[0], 0);
+    }
+
+    #[test]
+    fn decimator_heatmap_compares_against_the_last_forwarded_frame() {
+        let mut decimator = FrameDecimator::new(FrameDecimation::MinChange(0.05));
+        let client = Uuid::new_v4();
+        assert!(decimator.heatmap(&frame(client, 100)).is_none());
+
+        decimator.forward(&frame(client, 100));
+        let unchanged = decimator.heatmap(&frame(client, 100)).unwrap();
+        assert_eq!(unchanged.max(), 0.0);
+        let brighter = decimator.heatmap(&frame(client, 200)).unwrap();
+        assert!(brighter.rows().iter().flatten().all(|heat| *heat > 0.3));
+    }
+
+    #[test]
+    fn hysteresis_keeps_the_change_flag_from_chattering() {
+        let mut decimator = FrameDecimator::new(FrameDecimation::Hysteresis {
+            rising: 0.06,
+            falling: 0.03,
+        });
+        let client = Uuid::new_v4();
+        let mut feed = |shade| {
+            let forwarded = decimator.forward(&frame(client, shade));
+            (forwarded, decimator.is_changing(client))
+        };
+
+        assert_eq!(feed(100), (true, false));
+        assert_eq!(feed(120), (true, true));
+        // Differences of ~0.043 sit between the thresholds and keep whatever state holds.
+        for shade in [131, 120, 131, 120] {
+            assert_eq!(feed(shade), (true, true));
+        }
+        assert_eq!(feed(120), (true, false));
+        for shade in [131, 120, 131, 120] {
+            assert_eq!(feed(shade), (false, false));
+        }
+    }
+
+    #[test]
+    fn every_nth_counts_per_client() {
+        let mut decimator = FrameDecimator::new(FrameDecimation::EveryNth(3));
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let forwarded: Vec<bool> = (0..6)
+            .map(|_| decimator.forward(&frame(first, 0)))
+            .collect();
+        assert_eq!(forwarded, [true, false, false, true, false, false]);
+        assert!(decimator.forward(&frame(second, 0)));
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use crate::common::Frame;
+
+// Decides which frames the pipeline consumer processes when it can't keep up.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameDropPolicy {
+    // Process every frame in arrival order.
+    #[default]
+    ProcessAll,
+    // Skip everything queued behind the received frame and process only the latest.
+    AlwaysNewest,
+    // Process one out of every N received frames.
+    EveryNth(usize),
+    // Skip as many queued frames as the average processing time overruns the frame budget,
+    // based on the pipeline's total frame EWMA (requires metrics to be enabled).
+    AdaptiveToLatency {
+        frame_budget: Duration,
+    },
+}
+
+// Applies a FrameDropPolicy to the frames coming off the frame channel.
+pub struct FrameDropper {
+    policy: FrameDropPolicy,
+    received: u64,
+    dropped: u64,
+}
+
+impl FrameDropper {
+    pub fn new(policy: FrameDropPolicy) -> Self {
+        Self {
+            policy,
+            received: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // Whether a frame still being processed should be abandoned as soon as a newer one arrives.
+    pub fn preempts_stale(&self) -> bool {
+        self.policy == FrameDropPolicy::AlwaysNewest
+    }
+
+    // Returns the frame that should be processed, or None if this one should be skipped.
+    pub fn select(
+        &mut self,
+        frame: Frame,
+        queue: &mut Receiver<Frame>,
+        avg_frame_us: f64,
+    ) -> Option<Frame> {
+        self.received += 1;
+        match self.policy {
+            FrameDropPolicy::ProcessAll => Some(frame),
+            FrameDropPolicy::AlwaysNewest => Some(self.skip_queued(frame, queue, usize::MAX)),
+            FrameDropPolicy::EveryNth(n) => {
+                if (self.received - 1).is_multiple_of(n.max(1) as u64) {
+                    Some(frame)
+                } else {
+                    self.dropped += 1;
+                    None
+                }
+            }
+            FrameDropPolicy::AdaptiveToLatency { frame_budget } => {
+                let budget_us = frame_budget.as_micros() as f64;
+                let to_skip = if budget_us > 0.0 {
+                    ((avg_frame_us / budget_us).ceil() as usize).saturating_sub(1)
+                } else {
+                    0
+                };
+                Some(self.skip_queued(frame, queue, to_skip))
+            }
+        }
+    }
+
+    // Replaces `frame` with up to `max_skipped` newer frames already waiting in the queue.
+    fn skip_queued(
+        &mut self,
+        mut frame: Frame,
+        queue: &mut Receiver<Frame>,
+        max_skipped: usize,
+    ) -> Frame {
+        let mut skipped = 0;
+        while skipped < max_skipped {
+            match queue.try_recv() {
+                Ok(newer) => {
+                    frame = newer;
+                    skipped += 1;
+                }
+                Err(_) => break,
+            }
+        }
+        if skipped > 0 {
+            tracing::debug!("Dropped {} stale frames to catch up", skipped);
+        }
+        self.received += skipped as u64;
+        self.dropped += skipped as u64;
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use image::{DynamicImage, ImageBuffer, Rgb};
+    use tokio::sync::mpsc;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn frame() -> Frame {
+        Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, Vec<u8>>::from_pixel(
+                4,
+                4,
+                Rgb([0, 0, 0]),
+            )),
+            Utc::now(),
+            Uuid::new_v4(),
+        )
+    }
+
+    // Queues `count` frames as if they piled up behind a slow processor.
+    fn backlog(count: usize) -> (Receiver<Frame>, Vec<Uuid>) {
+        let (tx, rx) = mpsc::channel(count);
+        let frames: Vec<Frame> = (0..count).map(|_| frame()).collect();
+        let ids = frames.iter().map(Frame::get_frame_id).collect();
+        for frame in frames {
+            tx.try_send(frame).unwrap();
+        }
+        (rx, ids)
+    }
+
+    #[test]
+    fn always_newest_processes_the_latest_frame() {
+        let (mut rx, ids) = backlog(5);
+        let mut dropper = FrameDropper::new(FrameDropPolicy::AlwaysNewest);
+        let first = rx.try_recv().unwrap();
+
+        let selected = dropper.select(first, &mut rx, 0.0).unwrap();
+        assert_eq!(selected.get_frame_id(), ids[4]);
+        assert_eq!(dropper.dropped(), 4);
+    }
+
+    #[test]
+    fn adaptive_skips_in_proportion_to_latency() {
+        let (mut rx, ids) = backlog(6);
+        let mut dropper = FrameDropper::new(FrameDropPolicy::AdaptiveToLatency {
+            frame_budget: Duration::from_millis(16),
+        });
+        let first = rx.try_recv().unwrap();
+
+        // A 50ms average against a 16ms budget means three frames are stale.
+        let selected = dropper.select(first, &mut rx, 50_000.0).unwrap();
+        assert_eq!(selected.get_frame_id(), ids[3]);
+    }
+
+    #[test]
+    fn every_nth_processes_one_frame_per_window() {
+        let (mut rx, ids) = backlog(6);
+        let mut dropper = FrameDropper::new(FrameDropPolicy::EveryNth(3));
+        let mut processed = Vec::new();
+        while let Ok(frame) = rx.try_recv() {
+            if let Some(frame) = dropper.select(frame, &mut rx, 0.0) {
+                processed.push(frame.get_frame_id());
+            }
+        }
+        assert_eq!(processed, vec![ids[0], ids[3]]);
+    }
+}
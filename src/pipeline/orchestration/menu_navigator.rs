@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use crate::common::{ButtonPress, GameAction};
+use crate::pipeline::domain::scene_analysis::{BattleMenuSelection, SceneType};
+
+// Plans the exact presses that move a vertical menu's cursor from its detected row to a
+// target row and confirm it, instead of pressing a direction and checking the next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuNavigator {
+    rows: usize,
+    wraps: bool,
+}
+
+impl MenuNavigator {
+    pub fn new(rows: usize) -> Self {
+        Self { rows, wraps: false }
+    }
+
+    // Menus whose cursor jumps from the last row back to the first (and the other way round)
+    // can take the shorter way around.
+    pub fn with_wraparound(mut self, wraps: bool) -> Self {
+        self.wraps = wraps;
+        self
+    }
+
+    // Up/Down presses from `from` to `to` followed by A, None when either row is outside the menu.
+    pub fn presses_to(&self, from: usize, to: usize) -> Option<Vec<ButtonPress>> {
+        if from >= self.rows || to >= self.rows {
+            return None;
+        }
+        let down = (to + self.rows - from) % self.rows;
+        let up = (from + self.rows - to) % self.rows;
+        let (direction, steps) = if self.wraps {
+            if down <= up {
+                (GameAction::Down, down)
+            } else {
+                (GameAction::Up, up)
+            }
+        } else if to >= from {
+            (GameAction::Down, to - from)
+        } else {
+            (GameAction::Up, from - to)
+        };
+        Some(Self::steps_then_confirm([(direction, steps)]))
+    }
+
+    // Presses that move the 2x2 battle menu cursor to `to` and select it.
+    pub fn battle_menu(from: BattleMenuSelection, to: BattleMenuSelection) -> Vec<ButtonPress> {
+        let cell = |selection| -> (usize, usize) {
+            match selection {
+                BattleMenuSelection::Fight => (0, 0),
+                BattleMenuSelection::Bag => (1, 0),
+                BattleMenuSelection::Pokemon => (0, 1),
+                BattleMenuSelection::Run => (1, 1),
+            }
+        };
+        let ((from_column, from_row), (to_column, to_row)) = (cell(from), cell(to));
+        let horizontal = if to_column > from_column {
+            GameAction::Right
+        } else {
+            GameAction::Left
+        };
+        let vertical = if to_row > from_row {
+            GameAction::Down
+        } else {
+            GameAction::Up
+        };
+        Self::steps_then_confirm([
+            (horizontal, from_column.abs_diff(to_column)),
+            (vertical, from_row.abs_diff(to_row)),
+        ])
+    }
+
+    // Menus without a plan of their own are left with B, which backs out of every submenu.
+    pub fn back_out(scene: SceneType) -> Option<ButtonPress> {
+        matches!(
+            scene,
+            SceneType::Menu
+                | SceneType::PartyMenu
+                | SceneType::TrainerCard
+                | SceneType::Bag
+                | SceneType::Shop
+        )
+        .then(|| ButtonPress::tap(GameAction::B))
+    }
+
+    // Prompts that interrupt play and are safe to get past without a plan: the move-learn prompt
+    // is declined with B so the current moves are kept, while B during an evolution would cancel
+    // it, so that one is only waited out.
+    pub fn advance_prompt(scene: SceneType) -> Option<ButtonPress> {
+        match scene {
+            SceneType::MoveLearn => Some(ButtonPress::tap(GameAction::B)),
+            SceneType::Evolution => Some(ButtonPress::wait(Duration::from_millis(500))),
+            _ => None,
+        }
+    }
+
+    fn steps_then_confirm<const N: usize>(moves: [(GameAction, usize); N]) -> Vec<ButtonPress> {
+        moves
+            .into_iter()
+            .flat_map(|(direction, steps)| std::iter::repeat_n(ButtonPress::tap(direction), steps))
+            .chain(std::iter::once(ButtonPress::tap(GameAction::A)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taps(actions: &[GameAction]) -> Vec<ButtonPress> {
+        actions
+            .iter()
+            .map(|action| ButtonPress::tap(*action))
+            .collect()
+    }
+
+    #[test]
+    fn walks_up_to_the_target_row_then_confirms() {
+        let presses = MenuNavigator::new(4).presses_to(3, 0).unwrap();
+        assert_eq!(
+            presses,
+            taps(&[
+                GameAction::Up,
+                GameAction::Up,
+                GameAction::Up,
+                GameAction::A
+            ])
+        );
+    }
+
+    #[test]
+    fn wraparound_takes_the_shorter_way() {
+        let navigator = MenuNavigator::new(4).with_wraparound(true);
+        assert_eq!(
+            navigator.presses_to(3, 0).unwrap(),
+            taps(&[GameAction::Down, GameAction::A])
+        );
+        assert_eq!(
+            navigator.presses_to(1, 3).unwrap(),
+            taps(&[GameAction::Down, GameAction::Down, GameAction::A])
+        );
+    }
+
+    #[test]
+    fn already_on_target_only_confirms() {
+        let navigator = MenuNavigator::new(4);
+        assert_eq!(navigator.presses_to(2, 2).unwrap(), taps(&[GameAction::A]));
+        assert_eq!(
+            navigator.with_wraparound(true).presses_to(2, 2).unwrap(),
+            taps(&[GameAction::A])
+        );
+    }
+
+    #[test]
+    fn rows_outside_the_menu_have_no_plan() {
+        let navigator = MenuNavigator::new(4);
+        assert!(navigator.presses_to(4, 0).is_none());
+        assert!(navigator.presses_to(0, 4).is_none());
+    }
+
+    #[test]
+    fn battle_menu_moves_across_then_down() {
+        assert_eq!(
+            MenuNavigator::battle_menu(BattleMenuSelection::Fight, BattleMenuSelection::Run),
+            taps(&[GameAction::Right, GameAction::Down, GameAction::A])
+        );
+        assert_eq!(
+            MenuNavigator::battle_menu(BattleMenuSelection::Bag, BattleMenuSelection::Pokemon),
+            taps(&[GameAction::Left, GameAction::Down, GameAction::A])
+        );
+        assert_eq!(
+            MenuNavigator::battle_menu(BattleMenuSelection::Run, BattleMenuSelection::Run),
+            taps(&[GameAction::A])
+        );
+    }
+
+    #[test]
+    fn menus_are_backed_out_of_with_b() {
+        assert_eq!(
+            MenuNavigator::back_out(SceneType::Bag),
+            Some(ButtonPress::tap(GameAction::B))
+        );
+        // Buying on its own is risky, shops are left rather than browsed.
+        assert_eq!(
+            MenuNavigator::back_out(SceneType::Shop),
+            Some(ButtonPress::tap(GameAction::B))
+        );
+        assert_eq!(MenuNavigator::back_out(SceneType::Overworld), None);
+    }
+
+    #[test]
+    fn prompts_are_declined_or_waited_out() {
+        assert_eq!(
+            MenuNavigator::advance_prompt(SceneType::MoveLearn),
+            Some(ButtonPress::tap(GameAction::B))
+        );
+        let evolving = MenuNavigator::advance_prompt(SceneType::Evolution).unwrap();
+        assert!(evolving.is_wait());
+        assert_eq!(MenuNavigator::advance_prompt(SceneType::Battle), None);
+    }
+}
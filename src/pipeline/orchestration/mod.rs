@@ -1,3 +1,14 @@
+pub mod action_mask;
+pub mod attract_mode;
+pub mod batch_analysis;
+pub mod checkpoint_manager;
+pub mod dialog_print;
+pub mod effect_latency;
+pub mod exploration_map;
+pub mod frame_decimator;
+pub mod frame_drop_policy;
+pub mod menu_navigator;
 pub mod processing_pipeline;
 pub mod service;
 pub mod step;
+pub mod stuck_watchdog;
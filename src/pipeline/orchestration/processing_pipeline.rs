@@ -1,17 +1,35 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use crate::common::Frame;
 use crate::error::AppError;
 use crate::pipeline::context::frame_context::FrameContext;
+use crate::pipeline::context::metrics::PerformanceStats;
+use crate::pipeline::context::scene_transitions::SceneTransitionTracker;
 use crate::pipeline::context::state::{AnalyzedState, IngestedState};
 use crate::pipeline::domain::scene_analysis::SceneAnalysis;
+use crate::pipeline::orchestration::frame_decimator::{FrameDecimation, FrameDecimator};
+use crate::pipeline::orchestration::service::preprocess::FramePreprocessor;
 use crate::pipeline::orchestration::step::scene_analyzer::AnalyzerBuilder;
 use async_trait::async_trait;
+use chrono::Utc;
 use time::Duration;
+use tokio_util::sync::CancellationToken;
 use tower::Service;
+use uuid::Uuid;
 
 pub struct ProcessingPipeline {
     pub enable_metrics: bool,
+    pub stats: Arc<Mutex<PerformanceStats>>,
+    pub transitions: Arc<Mutex<SceneTransitionTracker>>,
+    // Frames smaller than this (width, height) are skipped before analysis.
+    pub min_frame_size: (u32, u32),
+    // Clients already warned about undersized frames, so the warning is logged once.
+    pub undersized_clients: HashSet<Uuid>,
+    pub decimator: Option<FrameDecimator>,
     pub analyzer_step: Box<
         dyn Service<
                 FrameContext<IngestedState>,
@@ -38,16 +56,140 @@ impl ProcessingPipeline {
     }
 
     pub async fn process(&mut self, frame: Frame) -> Result<FrameContext<AnalyzedState>, AppError> {
-        let frame_context = FrameContext::new(frame);
-        let response = self.analyzer_step.call(frame_context).await?;
+        self.process_until(frame, &CancellationToken::new()).await
+    }
+
+    // Same as process, but gives up between steps and during analysis once `cancel` fires,
+    // e.g. because a fresher frame arrived. Nothing after the step it gave up in runs.
+    pub async fn process_until(
+        &mut self,
+        frame: Frame,
+        cancel: &CancellationToken,
+    ) -> Result<FrameContext<AnalyzedState>, AppError> {
+        let started = Instant::now();
+        self.validate_frame_size(&frame)?;
+        let validated = started.elapsed();
+        Self::check_cancelled(cancel)?;
+        let decimation_started = Instant::now();
+        if let Some(decimator) = self.decimator.as_mut()
+            && !decimator.forward(&frame)
+        {
+            if self.enable_metrics
+                && let Ok(mut stats) = self.stats.lock()
+            {
+                stats.record_decimated();
+            }
+            return Err(AppError::FrameSkipped(
+                "frame decimated before analysis".to_string(),
+            ));
+        }
+        let size = format!(
+            "{}x{}",
+            frame.get_image().width(),
+            frame.get_image().height()
+        );
+        let mut frame_context = FrameContext::new(frame);
+        frame_context.set_cancel_token(cancel.clone());
+        frame_context.record_step("validate_frame_size", size, validated);
+        if self.decimator.is_some() {
+            frame_context.record_step("decimate", "forwarded", decimation_started.elapsed());
+        }
+        Self::check_cancelled(cancel)?;
+        let mut response = tokio::select! {
+            biased;
+            _ = cancel.cancelled() => return Err(Self::cancelled()),
+            response = self.analyzer_step.call(frame_context) => response?,
+        };
+        Self::check_cancelled(cancel)?;
+        if self.enable_metrics
+            && let Ok(mut stats) = self.stats.lock()
+        {
+            stats.record(response.metrics(), response.elapsed());
+            // A capture clock ahead of ours (clock skew) counts as zero latency.
+            let latency = (Utc::now() - response.frame().get_captured_at())
+                .to_std()
+                .unwrap_or_default();
+            stats.record_capture_latency(latency);
+        }
+        let transitions_started = Instant::now();
+        let transition = self.transitions.lock().ok().and_then(|mut transitions| {
+            transitions.record(
+                response.frame().get_client_id(),
+                response.analysis().scene_type(),
+                Instant::now(),
+            )
+        });
+        let outcome = match transition {
+            Some((from, to)) => {
+                tracing::debug!("Scene transition {:?} -> {:?}", from, to);
+                format!("{:?} -> {:?}", from, to)
+            }
+            None => "no transition".to_string(),
+        };
+        response.record_step("transitions", outcome, transitions_started.elapsed());
         Ok(response)
     }
+
+    fn check_cancelled(cancel: &CancellationToken) -> Result<(), AppError> {
+        if cancel.is_cancelled() {
+            return Err(Self::cancelled());
+        }
+        Ok(())
+    }
+
+    fn cancelled() -> AppError {
+        AppError::FrameSkipped("frame processing cancelled".to_string())
+    }
+
+    // Rejects empty, truncated or mock frames that are too small to analyze.
+    fn validate_frame_size(&mut self, frame: &Frame) -> Result<(), AppError> {
+        let (width, height) = (frame.get_image().width(), frame.get_image().height());
+        let (min_width, min_height) = self.min_frame_size;
+        if width >= min_width && height >= min_height {
+            return Ok(());
+        }
+        if self.undersized_clients.insert(frame.get_client_id()) {
+            tracing::warn!(
+                "Skipping {}x{} frames from client {}, minimum is {}x{}",
+                width,
+                height,
+                frame.get_client_id(),
+                min_width,
+                min_height
+            );
+        }
+        Err(AppError::FrameSkipped(format!(
+            "frame is {}x{}, minimum is {}x{}",
+            width, height, min_width, min_height
+        )))
+    }
+
+    // Shared handle to the aggregate stats, only updated when metrics are enabled.
+    pub fn stats(&self) -> Arc<Mutex<PerformanceStats>> {
+        self.stats.clone()
+    }
+
+    // Clears the stats in place, handles from stats() stay valid and see the fresh counts.
+    pub fn reset_stats(&self) {
+        if let Ok(mut stats) = self.stats.lock() {
+            stats.reset();
+        }
+    }
+
+    // Shared handle to the per-client scene history.
+    pub fn transitions(&self) -> Arc<Mutex<SceneTransitionTracker>> {
+        self.transitions.clone()
+    }
 }
 
 pub struct ProcessingPipelineBuilder {
     pub timeout: Option<Duration>,
     pub rate_limit: Option<(u64, Duration)>,
     pub enable_metrics: bool,
+    pub record_frames_dir: Option<PathBuf>,
+    pub preprocessors: Vec<Box<dyn FramePreprocessor>>,
+    pub min_frame_size: (u32, u32),
+    pub decimation: Option<FrameDecimation>,
 }
 
 impl ProcessingPipelineBuilder {
@@ -56,6 +198,10 @@ impl ProcessingPipelineBuilder {
             timeout: None,
             rate_limit: None,
             enable_metrics: false,
+            record_frames_dir: None,
+            preprocessors: Vec::new(),
+            min_frame_size: (32, 32),
+            decimation: None,
         }
     }
 
@@ -74,6 +220,30 @@ impl ProcessingPipelineBuilder {
         self
     }
 
+    // Records every incoming frame to this directory so it can be replayed with FrameReplayer.
+    pub fn record_frames(mut self, directory: PathBuf) -> Self {
+        self.record_frames_dir = Some(directory);
+        self
+    }
+
+    // Frames smaller than width x height are skipped instead of analyzed.
+    pub fn min_frame_size(mut self, width: u32, height: u32) -> Self {
+        self.min_frame_size = (width, height);
+        self
+    }
+
+    // Skips frames before analysis, e.g. near-duplicates of the previous frame.
+    pub fn decimate(mut self, decimation: FrameDecimation) -> Self {
+        self.decimation = Some(decimation);
+        self
+    }
+
+    // Appends a preprocessor, preprocessors run in the order they were added before analysis.
+    pub fn preprocess(mut self, preprocessor: Box<dyn FramePreprocessor>) -> Self {
+        self.preprocessors.push(preprocessor);
+        self
+    }
+
     pub fn add_analyzer(self, analyzer: Box<dyn AnalyzerStep>) -> AnalyzerBuilder {
         AnalyzerBuilder {
             config: self,
@@ -94,3 +264,225 @@ impl Default for ProcessingPipelineBuilder {
 pub trait AnalyzerStep: Send + Sync + 'static {
     async fn analyze(&self, ctx: &FrameContext<IngestedState>) -> Result<SceneAnalysis, AppError>;
 }
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, RgbImage};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::pipeline::detection::scene_detector::SceneDetector;
+    use crate::pipeline::domain::scene_analysis::SceneType;
+    use crate::pipeline::orchestration::service::preprocess::BorderCropper;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    fn frame(client_id: Uuid, width: u32, height: u32) -> Frame {
+        Frame::new(
+            client_id,
+            DynamicImage::ImageRgb8(RgbImage::new(width, height)),
+            Utc::now(),
+            Uuid::new_v4(),
+        )
+    }
+
+    #[tokio::test]
+    async fn undersized_frames_are_skipped() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+        let client = Uuid::new_v4();
+
+        for (width, height) in [(1, 1), (8, 8), (1, 1)] {
+            let result = pipeline.process(frame(client, width, height)).await;
+            assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+        }
+        assert_eq!(pipeline.undersized_clients.len(), 1);
+        assert!(pipeline.process(frame(client, 64, 64)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn records_latency_since_capture() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .enable_metrics(true)
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+        let old_frame = Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(RgbImage::new(64, 64)),
+            Utc::now() - chrono::Duration::milliseconds(250),
+            Uuid::new_v4(),
+        );
+
+        pipeline.process(old_frame).await.unwrap();
+        let latency = pipeline.stats().lock().unwrap().capture_latency();
+        assert!(latency.max_us() >= 250_000);
+        assert!(latency.ewma_us() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn trace_lists_every_step_in_order() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .decimate(FrameDecimation::EveryNth(1))
+            .preprocess(Box::new(BorderCropper::new()))
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+
+        let response = pipeline
+            .process(frame(Uuid::new_v4(), 64, 64))
+            .await
+            .unwrap();
+        let steps: Vec<&str> = response.trace().iter().map(|trace| trace.step).collect();
+        assert_eq!(
+            steps,
+            vec![
+                "validate_frame_size",
+                "decimate",
+                "preprocess",
+                "analyze",
+                "transitions"
+            ]
+        );
+        assert_eq!(response.trace()[0].outcome, "64x64");
+        assert_eq!(response.trace()[3].outcome, "FadeTransition (0.90)");
+    }
+
+    #[tokio::test]
+    async fn decimated_frames_are_counted() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .enable_metrics(true)
+            .decimate(FrameDecimation::MinChange(0.05))
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+        let client = Uuid::new_v4();
+
+        assert!(pipeline.process(frame(client, 64, 64)).await.is_ok());
+        for _ in 0..3 {
+            let result = pipeline.process(frame(client, 64, 64)).await;
+            assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+        }
+        let stats = pipeline.stats();
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.frames_processed(), 1);
+        assert_eq!(stats.frames_decimated(), 3);
+    }
+
+    #[tokio::test]
+    async fn reset_stats_start_over_behind_the_same_handle() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .enable_metrics(true)
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+        let client = Uuid::new_v4();
+        let stats = pipeline.stats();
+        for _ in 0..3 {
+            pipeline.process(frame(client, 64, 64)).await.unwrap();
+        }
+        assert_eq!(stats.lock().unwrap().frames_processed(), 3);
+
+        pipeline.reset_stats();
+        {
+            let stats = stats.lock().unwrap();
+            assert_eq!(stats.frames_processed(), 0);
+            assert_eq!(stats.total_frame().max_us(), 0);
+            assert_eq!(stats.fps(), 0.0);
+        }
+
+        pipeline.process(frame(client, 64, 64)).await.unwrap();
+        assert_eq!(stats.lock().unwrap().frames_processed(), 1);
+    }
+
+    // Takes longer to analyze than any test waits for.
+    struct SlowAnalyzer;
+
+    #[async_trait]
+    impl AnalyzerStep for SlowAnalyzer {
+        async fn analyze(
+            &self,
+            _ctx: &FrameContext<IngestedState>,
+        ) -> Result<SceneAnalysis, AppError> {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            Ok(SceneAnalysis::new(SceneType::Overworld, 1.0))
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_frames_stop_before_the_remaining_steps() {
+        let mut pipeline = ProcessingPipeline::builder()
+            .enable_metrics(true)
+            .add_analyzer(Box::new(SlowAnalyzer))
+            .build();
+        let client = Uuid::new_v4();
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = pipeline.process_until(frame(client, 64, 64), &cancel).await;
+        assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert_eq!(pipeline.stats().lock().unwrap().frames_processed(), 0);
+        assert!(
+            pipeline
+                .transitions()
+                .lock()
+                .unwrap()
+                .history(client)
+                .next()
+                .is_none()
+        );
+
+        // An already cancelled token stops the frame before analysis even starts.
+        let result = pipeline.process_until(frame(client, 64, 64), &cancel).await;
+        assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+    }
+
+    // Takes a while per frame and counts how often it ran.
+    struct SlowDetector(Arc<AtomicUsize>);
+
+    impl SceneDetector for SlowDetector {
+        fn name(&self) -> &'static str {
+            "slow"
+        }
+
+        fn detect(&self, _image: &RgbImage) -> Option<SceneAnalysis> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            None
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancelling_stops_a_running_scene_analysis() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let detectors = (0..50)
+            .map(|_| Box::new(SlowDetector(runs.clone())) as Box<dyn SceneDetector>)
+            .collect();
+        let mut pipeline = ProcessingPipeline::builder()
+            .add_analyzer(Box::new(SceneAnalyzer::new().with_detectors(detectors)))
+            .build();
+        let cancel = CancellationToken::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = pipeline
+            .process_until(frame(Uuid::new_v4(), 64, 64), &cancel)
+            .await;
+        assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+        assert!(started.elapsed() < std::time::Duration::from_millis(500));
+
+        // The analysis itself stops at the next detector instead of running on in the background.
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let ran = runs.load(Ordering::SeqCst);
+        assert!(ran < 50, "{} detectors ran", ran);
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), ran);
+    }
+}
@@ -1,5 +1,6 @@
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 
 use crate::pipeline::context::frame_context::FrameContext;
 use crate::pipeline::context::state::AnalyzedState;
@@ -8,6 +9,7 @@ use crate::pipeline::orchestration::processing_pipeline::AnalyzerStep;
 use futures::Future;
 use futures::task::Context;
 use futures::task::Poll;
+use tokio::runtime::Handle;
 use tower::Service;
 
 #[derive(Clone)]
@@ -36,8 +38,23 @@ impl Service<FrameContext<IngestedState>> for AnalyzerService {
         let inner = self.inner.clone();
 
         let future = Box::pin(async move {
-            let analysis = inner.analyze(&req).await?;
-            Ok(req.into_analyzed(analysis))
+            let started = Instant::now();
+            // Analysis is CPU bound, so it runs on the blocking pool instead of stalling a
+            // runtime worker. Dropping this future, e.g. on an outer timeout, cancels the frame's
+            // token so the analysis stops at its next check instead of running on unobserved.
+            let cancel_on_drop = req.cancel_token().clone().drop_guard();
+            let runtime = Handle::current();
+            let (req, analysis) = tokio::task::spawn_blocking(move || {
+                let analysis = runtime.block_on(inner.analyze(&req));
+                (req, analysis)
+            })
+            .await?;
+            cancel_on_drop.disarm();
+            let analysis = analysis?;
+            let outcome = format!("{:?} ({:.2})", analysis.scene_type(), analysis.confidence());
+            let mut analyzed = req.into_analyzed(analysis);
+            analyzed.record_step("analyze", outcome, started.elapsed());
+            Ok(analyzed)
         });
 
         future
@@ -46,23 +63,47 @@ impl Service<FrameContext<IngestedState>> for AnalyzerService {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use async_trait::async_trait;
     use chrono::Utc;
     use image::{DynamicImage, ImageBuffer, Rgb};
+    use tokio_util::sync::CancellationToken;
     use uuid::Uuid;
 
     use crate::{
         common::Frame,
+        error::AppError,
         pipeline::{
-            domain::scene_analysis::SceneType, orchestration::step::scene_analyzer::SceneAnalyzer,
+            domain::scene_analysis::{SceneAnalysis, SceneType},
+            orchestration::step::scene_analyzer::SceneAnalyzer,
         },
     };
 
     use super::*;
 
-    #[tokio::test]
-    async fn test_analyzer_service() {
-        let mut analyzer_service = AnalyzerService::new(Box::new(SceneAnalyzer::new()));
-        let frame_context = FrameContext::new(Frame::new(
+    // Spins until its frame is cancelled, like a long analysis checking its token.
+    struct UntilCancelled(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl AnalyzerStep for UntilCancelled {
+        async fn analyze(
+            &self,
+            ctx: &FrameContext<IngestedState>,
+        ) -> Result<SceneAnalysis, AppError> {
+            while !ctx.cancel_token().is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            self.0.store(true, Ordering::SeqCst);
+            Err(AppError::FrameSkipped(
+                "frame processing cancelled".to_string(),
+            ))
+        }
+    }
+
+    fn white_frame() -> FrameContext<IngestedState> {
+        FrameContext::new(Frame::new(
             Uuid::new_v4(),
             DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, Vec<u8>>::from_pixel(
                 100,
@@ -71,8 +112,38 @@ mod tests {
             )),
             Utc::now(),
             Uuid::new_v4(),
-        ));
-        let response = analyzer_service.call(frame_context).await.unwrap();
-        assert!(response.analysis().scene_type() == SceneType::Unknown);
+        ))
+    }
+
+    #[tokio::test]
+    async fn timed_out_analyses_are_cancelled() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let mut analyzer_service = AnalyzerService::new(Box::new(UntilCancelled(stopped.clone())));
+        let cancel = CancellationToken::new();
+        let mut frame_context = white_frame();
+        frame_context.set_cancel_token(cancel.clone());
+
+        let call = analyzer_service.call(frame_context);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), call)
+                .await
+                .is_err()
+        );
+        assert!(cancel.is_cancelled());
+        for _ in 0..100 {
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_analyzer_service() {
+        let mut analyzer_service = AnalyzerService::new(Box::new(SceneAnalyzer::new()));
+        let response = analyzer_service.call(white_frame()).await.unwrap();
+        // A blank white screen is what a whiteout fade looks like.
+        assert!(response.analysis().scene_type() == SceneType::FadeTransition);
     }
 }
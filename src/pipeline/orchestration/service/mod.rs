@@ -1 +1,3 @@
 pub mod analyzer_service;
+pub mod preprocess;
+pub mod replay;
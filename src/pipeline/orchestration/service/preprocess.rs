@@ -0,0 +1,403 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use futures::task::{Context, Poll};
+use image::{DynamicImage, Rgb, RgbImage, imageops};
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
+
+use crate::pipeline::context::frame_context::FrameContext;
+use crate::pipeline::context::state::IngestedState;
+use crate::pipeline::detection::image_stats::luma;
+
+// Cleans up a captured image before scene analysis. Detectors work on fractions of the
+// image size, so preprocessors are free to change the dimensions.
+pub trait FramePreprocessor: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+    fn process(&self, image: RgbImage) -> RgbImage;
+}
+
+// Removes uniform black margins (letterboxing) around the picture.
+pub struct BorderCropper {
+    max_border_luma: u8,
+}
+
+impl BorderCropper {
+    pub fn new() -> Self {
+        Self {
+            max_border_luma: 16,
+        }
+    }
+
+    fn is_dark_row(&self, image: &RgbImage, y: u32) -> bool {
+        (0..image.width()).all(|x| luma(image.get_pixel(x, y)) <= self.max_border_luma)
+    }
+
+    fn is_dark_column(&self, image: &RgbImage, x: u32, rows: std::ops::Range<u32>) -> bool {
+        rows.into_iter()
+            .all(|y| luma(image.get_pixel(x, y)) <= self.max_border_luma)
+    }
+}
+
+impl Default for BorderCropper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePreprocessor for BorderCropper {
+    fn name(&self) -> &'static str {
+        "border_cropper"
+    }
+
+    fn process(&self, image: RgbImage) -> RgbImage {
+        let (width, height) = image.dimensions();
+        let Some(top) = (0..height).find(|y| !self.is_dark_row(&image, *y)) else {
+            // Entirely black, e.g. a fade. There is no picture to crop to.
+            return image;
+        };
+        let bottom = (0..height)
+            .rev()
+            .find(|y| !self.is_dark_row(&image, *y))
+            .unwrap_or(top)
+            + 1;
+        let left = (0..width)
+            .find(|x| !self.is_dark_column(&image, *x, top..bottom))
+            .unwrap_or(0);
+        let right = (0..width)
+            .rev()
+            .find(|x| !self.is_dark_column(&image, *x, top..bottom))
+            .unwrap_or(width - 1)
+            + 1;
+        if (left, top, right, bottom) == (0, 0, width, height) {
+            return image;
+        }
+        imageops::crop_imm(&image, left, top, right - left, bottom - top).to_image()
+    }
+}
+
+// Undoes scanline filters that darken every other row by replacing the dark rows with the
+// average of their neighbours. Images without a scanline pattern pass through unchanged.
+pub struct ScanlineDenoiser {
+    max_dark_row_ratio: f32,
+}
+
+impl ScanlineDenoiser {
+    pub fn new() -> Self {
+        Self {
+            max_dark_row_ratio: 0.8,
+        }
+    }
+
+    fn mean_luma(image: &RgbImage, parity: u32) -> f32 {
+        let mut total = 0u64;
+        let mut count = 0u64;
+        for y in (parity..image.height()).step_by(2) {
+            for x in 0..image.width() {
+                total += luma(image.get_pixel(x, y)) as u64;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            0.0
+        } else {
+            total as f32 / count as f32
+        }
+    }
+}
+
+impl Default for ScanlineDenoiser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FramePreprocessor for ScanlineDenoiser {
+    fn name(&self) -> &'static str {
+        "scanline_denoiser"
+    }
+
+    fn process(&self, mut image: RgbImage) -> RgbImage {
+        let (even, odd) = (Self::mean_luma(&image, 0), Self::mean_luma(&image, 1));
+        let dark_parity = if odd < even * self.max_dark_row_ratio {
+            1
+        } else if even < odd * self.max_dark_row_ratio {
+            0
+        } else {
+            return image;
+        };
+
+        let (width, height) = image.dimensions();
+        for y in (dark_parity..height).step_by(2) {
+            let above = y.checked_sub(1);
+            let below = (y + 1 < height).then_some(y + 1);
+            for x in 0..width {
+                let pixel = match (above, below) {
+                    (Some(above), Some(below)) => {
+                        let (a, b) = (image.get_pixel(x, above).0, image.get_pixel(x, below).0);
+                        image::Rgb(std::array::from_fn(|c| {
+                            ((a[c] as u16 + b[c] as u16) / 2) as u8
+                        }))
+                    }
+                    (Some(neighbour), None) | (None, Some(neighbour)) => {
+                        *image.get_pixel(x, neighbour)
+                    }
+                    (None, None) => continue,
+                };
+                image.put_pixel(x, y, pixel);
+            }
+        }
+        image
+    }
+}
+
+// Halves captures that were upscaled 2x with nearest-neighbour scaling.
+pub struct Nearest2xDownscaler;
+
+impl FramePreprocessor for Nearest2xDownscaler {
+    fn name(&self) -> &'static str {
+        "nearest_2x_downscaler"
+    }
+
+    fn process(&self, image: RgbImage) -> RgbImage {
+        let (width, height) = (image.width() / 2, image.height() / 2);
+        if width == 0 || height == 0 {
+            return image;
+        }
+        imageops::resize(&image, width, height, imageops::FilterType::Nearest)
+    }
+}
+
+// Maps a shader's or colorblind filter's palette back to the game's own colors, which every
+// detector's hue and luma cut-offs are tuned for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteRemap {
+    // Each output channel is the weighted sum of the input's red, green and blue, one row
+    // of weights per output channel.
+    Matrix([[f32; 3]; 3]),
+    // Pairs of (filtered, canonical) colors. Pixels within `tolerance` on every channel of a
+    // filtered color take the canonical one, others are left alone.
+    Lookup {
+        colors: Vec<([u8; 3], [u8; 3])>,
+        tolerance: u8,
+    },
+}
+
+impl PaletteRemap {
+    fn remap(&self, pixel: Rgb<u8>) -> Rgb<u8> {
+        match self {
+            PaletteRemap::Matrix(rows) => Rgb(rows.map(|weights| {
+                let value: f32 = weights
+                    .iter()
+                    .zip(pixel.0)
+                    .map(|(weight, channel)| weight * channel as f32)
+                    .sum();
+                value.round().clamp(0.0, 255.0) as u8
+            })),
+            PaletteRemap::Lookup { colors, tolerance } => colors
+                .iter()
+                .map(|(filtered, canonical)| {
+                    let distance = filtered
+                        .iter()
+                        .zip(pixel.0)
+                        .map(|(a, b)| a.abs_diff(b))
+                        .max()
+                        .unwrap_or_default();
+                    (distance, canonical)
+                })
+                .filter(|(distance, _)| distance <= tolerance)
+                .min_by_key(|(distance, _)| *distance)
+                .map_or(pixel, |(_, canonical)| Rgb(*canonical)),
+        }
+    }
+}
+
+impl FramePreprocessor for PaletteRemap {
+    fn name(&self) -> &'static str {
+        "palette_remap"
+    }
+
+    fn process(&self, mut image: RgbImage) -> RgbImage {
+        for pixel in image.pixels_mut() {
+            *pixel = self.remap(*pixel);
+        }
+        image
+    }
+}
+
+#[derive(Clone)]
+pub struct FramePreprocessLayer {
+    preprocessors: Arc<Vec<Box<dyn FramePreprocessor>>>,
+}
+
+impl FramePreprocessLayer {
+    pub fn new(preprocessors: Vec<Box<dyn FramePreprocessor>>) -> Self {
+        Self {
+            preprocessors: Arc::new(preprocessors),
+        }
+    }
+}
+
+impl<S> Layer<S> for FramePreprocessLayer {
+    type Service = FramePreprocess<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FramePreprocess {
+            inner,
+            preprocessors: self.preprocessors.clone(),
+        }
+    }
+}
+
+// Runs the preprocessors in order over the frame image before forwarding the frame.
+#[derive(Clone)]
+pub struct FramePreprocess<S> {
+    inner: S,
+    preprocessors: Arc<Vec<Box<dyn FramePreprocessor>>>,
+}
+
+impl<S> Service<FrameContext<IngestedState>> for FramePreprocess<S>
+where
+    S: Service<FrameContext<IngestedState>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: FrameContext<IngestedState>) -> Self::Future {
+        if !self.preprocessors.is_empty() {
+            let started = Instant::now();
+            let image = self
+                .preprocessors
+                .iter()
+                .fold(req.frame().get_image().to_rgb8(), |image, preprocessor| {
+                    preprocessor.process(image)
+                });
+            let names: Vec<&str> = self.preprocessors.iter().map(|p| p.name()).collect();
+            let outcome = format!(
+                "{} -> {}x{}",
+                names.join(", "),
+                image.width(),
+                image.height()
+            );
+            req.replace_image(DynamicImage::ImageRgb8(image));
+            req.record_step("preprocess", outcome, started.elapsed());
+        }
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::common::Frame;
+    use crate::pipeline::detection::overworld_kind_classifier::OverworldKindClassifier;
+    use crate::pipeline::domain::scene_analysis::OverworldKind;
+    use crate::pipeline::orchestration::service::analyzer_service::AnalyzerService;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    fn letterboxed(width: u32, height: u32, margin_x: u32, margin_y: u32) -> RgbImage {
+        RgbImage::from_fn(width, height, |x, y| {
+            let inside = (margin_x..width - margin_x).contains(&x)
+                && (margin_y..height - margin_y).contains(&y);
+            if inside {
+                Rgb([120, 160, 200])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        })
+    }
+
+    #[test]
+    fn border_cropper_removes_black_margins() {
+        let cropped = BorderCropper::new().process(letterboxed(200, 160, 20, 8));
+        assert_eq!(cropped.dimensions(), (160, 144));
+        assert!(cropped.pixels().all(|p| *p == Rgb([120, 160, 200])));
+    }
+
+    #[test]
+    fn border_cropper_keeps_all_black_frames() {
+        let black = RgbImage::from_pixel(64, 48, Rgb([0, 0, 0]));
+        assert_eq!(BorderCropper::new().process(black).dimensions(), (64, 48));
+    }
+
+    #[test]
+    fn scanline_denoiser_fills_dark_rows() {
+        let image = RgbImage::from_fn(8, 8, |_, y| {
+            if y % 2 == 1 {
+                Rgb([20, 20, 20])
+            } else {
+                Rgb([200, 200, 200])
+            }
+        });
+        let denoised = ScanlineDenoiser::new().process(image);
+        assert!(denoised.pixels().all(|p| *p == Rgb([200, 200, 200])));
+    }
+
+    #[test]
+    fn palette_matrix_undoes_a_hue_rotating_shader() {
+        // The shader rotates the channels, turning grass red: (r, g, b) -> (g, b, r).
+        let shaded = RgbImage::from_fn(256, 192, |x, _| {
+            if x < 200 {
+                Rgb([200, 88, 96])
+            } else {
+                Rgb([144, 216, 192])
+            }
+        });
+        let classifier = OverworldKindClassifier::new();
+        assert_ne!(classifier.classify(&shaded), Some(OverworldKind::Outdoor));
+
+        let remap = PaletteRemap::Matrix([[0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]);
+        let restored = remap.process(shaded);
+        assert_eq!(*restored.get_pixel(0, 0), Rgb([96, 200, 88]));
+        assert_eq!(classifier.classify(&restored), Some(OverworldKind::Outdoor));
+    }
+
+    #[test]
+    fn palette_lookup_only_replaces_listed_colors() {
+        let remap = PaletteRemap::Lookup {
+            colors: vec![([200, 88, 96], [96, 200, 88])],
+            tolerance: 4,
+        };
+        let image = RgbImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgb([202, 86, 96])
+            } else {
+                Rgb([10, 20, 30])
+            }
+        });
+        let remapped = remap.process(image);
+        assert_eq!(*remapped.get_pixel(0, 0), Rgb([96, 200, 88]));
+        assert_eq!(*remapped.get_pixel(1, 0), Rgb([10, 20, 30]));
+    }
+
+    #[test]
+    fn downscaler_halves_dimensions() {
+        let image = RgbImage::from_pixel(512, 384, Rgb([1, 2, 3]));
+        assert_eq!(Nearest2xDownscaler.process(image).dimensions(), (256, 192));
+    }
+
+    #[tokio::test]
+    async fn layer_updates_the_frame_before_analysis() {
+        let mut service = FramePreprocessLayer::new(vec![Box::new(BorderCropper::new())])
+            .layer(AnalyzerService::new(Box::new(SceneAnalyzer::new())));
+        let frame = Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(letterboxed(200, 160, 20, 8)),
+            Utc::now(),
+            Uuid::new_v4(),
+        );
+
+        let analyzed = service.call(FrameContext::new(frame)).await.unwrap();
+        let image = analyzed.frame().get_image();
+        assert_eq!((image.width(), image.height()), (160, 144));
+    }
+}
@@ -0,0 +1,279 @@
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use futures::task::{Context, Poll};
+use serde::{Deserialize, Serialize};
+use tower::{Layer, Service};
+use uuid::Uuid;
+
+use crate::common::Frame;
+use crate::error::AppError;
+use crate::pipeline::context::frame_context::FrameContext;
+use crate::pipeline::context::state::IngestedState;
+use crate::pipeline::orchestration::processing_pipeline::ProcessingPipeline;
+
+// Metadata stored next to each recorded PNG, `{sequence:08}.json` / `{sequence:08}.png`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrameMetadata {
+    pub sequence: u64,
+    pub client_id: Uuid,
+    pub frame_id: Uuid,
+    pub captured_at: DateTime<Utc>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone)]
+pub struct FrameRecorderLayer {
+    directory: PathBuf,
+}
+
+impl FrameRecorderLayer {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+impl<S> Layer<S> for FrameRecorderLayer {
+    type Service = FrameRecorder<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FrameRecorder::new(inner, self.directory.clone())
+    }
+}
+
+// Pass-through service that writes every incoming frame to disk before forwarding it.
+// Recording failures are logged and never fail the frame.
+#[derive(Clone)]
+pub struct FrameRecorder<S> {
+    inner: S,
+    directory: PathBuf,
+    next_sequence: Arc<AtomicU64>,
+}
+
+impl<S> FrameRecorder<S> {
+    pub fn new(inner: S, directory: PathBuf) -> Self {
+        Self {
+            inner,
+            directory,
+            next_sequence: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+// Writes the frame and its metadata under the given sequence number.
+fn record_frame(directory: &Path, sequence: u64, frame: &Frame) -> Result<(), AppError> {
+    fs::create_dir_all(directory)?;
+    let image = frame.get_image();
+    let metadata = RecordedFrameMetadata {
+        sequence,
+        client_id: frame.get_client_id(),
+        frame_id: frame.get_frame_id(),
+        captured_at: frame.get_captured_at(),
+        width: image.width(),
+        height: image.height(),
+    };
+
+    image.save(directory.join(format!("{:08}.png", sequence)))?;
+    let json = serde_json::to_vec_pretty(&metadata)?;
+    fs::write(directory.join(format!("{:08}.json", sequence)), json)?;
+    Ok(())
+}
+
+impl<S> Service<FrameContext<IngestedState>> for FrameRecorder<S>
+where
+    S: Service<FrameContext<IngestedState>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: FrameContext<IngestedState>) -> Self::Future {
+        let started = Instant::now();
+        // Encoding the PNG is slow, so the frame is written on the blocking pool while the rest
+        // of the pipeline works on it. The frame completes once both are done.
+        let sequence = self.next_sequence.fetch_add(1, Ordering::SeqCst);
+        let directory = self.directory.clone();
+        let frame = req.frame().clone();
+        let written =
+            tokio::task::spawn_blocking(move || record_frame(&directory, sequence, &frame));
+        req.record_step(
+            "record",
+            format!("recording as {:08}", sequence),
+            started.elapsed(),
+        );
+        let response = self.inner.call(req);
+        Box::pin(async move {
+            match written.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!("Failed to record frame {:08}: {}", sequence, e),
+                Err(e) => tracing::warn!("Recording frame {:08} panicked: {}", sequence, e),
+            }
+            response.await
+        })
+    }
+}
+
+// Reads frames written by FrameRecorder back in sequence order.
+pub struct FrameReplayer {
+    metadata_paths: Vec<PathBuf>,
+}
+
+impl FrameReplayer {
+    pub fn open(directory: &Path) -> Result<Self, AppError> {
+        let mut metadata_paths: Vec<PathBuf> = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        metadata_paths.sort();
+        Ok(Self { metadata_paths })
+    }
+
+    pub fn len(&self) -> usize {
+        self.metadata_paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metadata_paths.is_empty()
+    }
+
+    pub fn load(&self, index: usize) -> Result<(RecordedFrameMetadata, Frame), AppError> {
+        let metadata_path = self
+            .metadata_paths
+            .get(index)
+            .ok_or_else(|| AppError::Pipeline(format!("No recorded frame at index {}", index)))?;
+        let metadata: RecordedFrameMetadata = serde_json::from_slice(&fs::read(metadata_path)?)?;
+        let image = image::open(metadata_path.with_extension("png"))?;
+        let frame = Frame::new(
+            metadata.client_id,
+            image,
+            metadata.captured_at,
+            metadata.frame_id,
+        );
+        Ok((metadata, frame))
+    }
+
+    // Feeds every recorded frame through the pipeline, waiting `interval` between frames.
+    pub async fn replay(
+        &self,
+        pipeline: &mut ProcessingPipeline,
+        interval: Duration,
+    ) -> Result<usize, AppError> {
+        for index in 0..self.len() {
+            if index > 0 && !interval.is_zero() {
+                tokio::time::sleep(interval).await;
+            }
+            let (_, frame) = self.load(index)?;
+            match pipeline.process(frame).await {
+                Ok(_) | Err(AppError::FrameSkipped(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageBuffer, Rgb};
+
+    use super::*;
+    use crate::pipeline::orchestration::service::analyzer_service::AnalyzerService;
+    use crate::pipeline::orchestration::step::scene_analyzer::SceneAnalyzer;
+
+    fn frame(width: u32, height: u32) -> Frame {
+        Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, Vec<u8>>::from_pixel(
+                width,
+                height,
+                Rgb([10, 20, 30]),
+            )),
+            Utc::now(),
+            Uuid::new_v4(),
+        )
+    }
+
+    #[tokio::test]
+    async fn records_and_replays_frames_in_order() {
+        let directory = std::env::temp_dir().join(format!("pokebot-replay-{}", Uuid::new_v4()));
+        let mut recorder = FrameRecorderLayer::new(directory.clone())
+            .layer(AnalyzerService::new(Box::new(SceneAnalyzer::new())));
+
+        let frames = [frame(48, 36), frame(64, 48), frame(80, 60)];
+        for frame in &frames {
+            recorder
+                .call(FrameContext::new(frame.clone()))
+                .await
+                .unwrap();
+        }
+
+        for sequence in 0..3 {
+            assert!(directory.join(format!("{:08}.png", sequence)).exists());
+            assert!(directory.join(format!("{:08}.json", sequence)).exists());
+        }
+
+        let replayer = FrameReplayer::open(&directory).unwrap();
+        assert_eq!(replayer.len(), 3);
+        for (index, original) in frames.iter().enumerate() {
+            let (metadata, replayed) = replayer.load(index).unwrap();
+            assert_eq!(metadata.sequence, index as u64);
+            assert_eq!(replayed.get_frame_id(), original.get_frame_id());
+            assert_eq!(replayed.get_image().width(), original.get_image().width());
+            assert_eq!(replayed.get_image().height(), original.get_image().height());
+        }
+
+        let mut pipeline = ProcessingPipeline::builder()
+            .add_analyzer(Box::new(SceneAnalyzer::new()))
+            .build();
+        let replayed = replayer
+            .replay(&mut pipeline, Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(replayed, 3);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_recordings_surface_typed_errors() {
+        let directory = std::env::temp_dir().join(format!("pokebot-corrupt-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("00000000.json"), b"not json").unwrap();
+        let metadata = RecordedFrameMetadata {
+            sequence: 1,
+            client_id: Uuid::new_v4(),
+            frame_id: Uuid::new_v4(),
+            captured_at: Utc::now(),
+            width: 1,
+            height: 1,
+        };
+        fs::write(
+            directory.join("00000001.json"),
+            serde_json::to_vec(&metadata).unwrap(),
+        )
+        .unwrap();
+        fs::write(directory.join("00000001.png"), b"not a png").unwrap();
+
+        let replayer = FrameReplayer::open(&directory).unwrap();
+        assert!(matches!(replayer.load(0), Err(AppError::Json(_))));
+        assert!(matches!(replayer.load(1), Err(AppError::Image(_))));
+        assert!(matches!(
+            FrameReplayer::open(&directory.join("missing")),
+            Err(AppError::Io(_))
+        ));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
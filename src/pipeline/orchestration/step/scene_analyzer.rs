@@ -1,17 +1,39 @@
 use crate::error::AppError;
 use crate::pipeline::context::frame_context::FrameContext;
+use crate::pipeline::context::metrics::{DetectorTiming, PerformanceStats};
+use crate::pipeline::context::scene_smoother::SceneSmoother;
+use crate::pipeline::context::scene_transitions::SceneTransitionTracker;
 use crate::pipeline::context::state::IngestedState;
+use crate::pipeline::detection::battle_menu_cursor_detector::BattleMenuCursorDetector;
+use crate::pipeline::detection::color_thresholds::ColorThresholds;
+use crate::pipeline::detection::confidence_curve::ConfidenceCurve;
+use crate::pipeline::detection::game_registry::{GameKind, GameRegistry};
+use crate::pipeline::detection::image_region::ImageRegion;
+use crate::pipeline::detection::overworld_kind_classifier::OverworldKindClassifier;
+use crate::pipeline::detection::scene_detector::SceneDetector;
 use crate::pipeline::domain::scene_analysis::SceneAnalysis;
 use crate::pipeline::domain::scene_analysis::SceneType;
+use crate::pipeline::orchestration::frame_decimator::FrameDecimator;
 use crate::pipeline::orchestration::processing_pipeline::AnalyzerStep;
 use crate::pipeline::orchestration::processing_pipeline::ProcessingPipeline;
 use crate::pipeline::orchestration::processing_pipeline::ProcessingPipelineBuilder;
 use crate::pipeline::orchestration::service::analyzer_service::AnalyzerService;
+use crate::pipeline::orchestration::service::preprocess::FramePreprocessLayer;
+use crate::pipeline::orchestration::service::replay::FrameRecorderLayer;
 use async_trait::async_trait;
-use std::time::Duration;
+use image::{DynamicImage, RgbImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
 use tower::timeout::TimeoutLayer;
 use tower::util::BoxService;
+use uuid::Uuid;
 
 pub struct AnalyzerBuilder {
     pub config: ProcessingPipelineBuilder,
@@ -27,36 +49,1218 @@ impl AnalyzerBuilder {
 
     pub fn build(self) -> ProcessingPipeline {
         let analyzer_builder = ServiceBuilder::new()
+            .option_layer(self.config.record_frames_dir.map(FrameRecorderLayer::new))
+            .option_layer(
+                (!self.config.preprocessors.is_empty())
+                    .then(|| FramePreprocessLayer::new(self.config.preprocessors)),
+            )
             .option_layer(self.analyzer_timeout.map(TimeoutLayer::new))
             .service(AnalyzerService::new(self.analyzer));
 
         ProcessingPipeline {
             enable_metrics: self.config.enable_metrics,
+            stats: Arc::new(Mutex::new(PerformanceStats::new())),
+            transitions: Arc::new(Mutex::new(SceneTransitionTracker::default())),
+            min_frame_size: self.config.min_frame_size,
+            undersized_clients: HashSet::new(),
+            decimator: self.config.decimation.map(FrameDecimator::new),
             analyzer_step: Box::new(BoxService::new(analyzer_builder)),
         }
     }
 }
 
+// How the individual detector results are combined into a single scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SceneResolution {
+    // The single most confident detector wins.
+    #[default]
+    Highest,
+    // Every detector adds confidence * weight to its scene's tally and the largest tally wins.
+    WeightedVote,
+}
+
+// Per-client replacements for the analyzer-wide settings, unset fields fall back to them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ClientOverrides {
+    pub confidence_threshold: Option<f32>,
+    pub resolution: Option<SceneResolution>,
+}
+
+// Analyzer settings loaded from the configuration file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyzerSettings {
+    // Detections less confident than this are ignored.
+    pub confidence_threshold: f32,
+    // Names of the detectors to run, e.g. ["cave", "trainer_card"]. When unset every detector
+    // registered for the game runs.
+    pub enabled_detectors: Option<Vec<String>>,
+    // When set, scenes are smoothed per client, see SceneAnalyzer::with_smoothing.
+    pub smoothing: Option<SmoothingSettings>,
+}
+
+impl Default for AnalyzerSettings {
+    fn default() -> Self {
+        Self {
+            confidence_threshold: 0.8,
+            enabled_detectors: None,
+            smoothing: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SmoothingSettings {
+    pub window: usize,
+    pub switch_after: usize,
+}
+
+// The smoothed scenes, and per client the last analysis that agreed with its smoothed scene
+// so the details read from that frame outlive a flicker.
+struct Smoothing {
+    smoother: SceneSmoother,
+    last_agreeing: HashMap<Uuid, SceneAnalysis>,
+}
+
 pub struct SceneAnalyzer {
     confidence_threshold: f32,
+    resolution: SceneResolution,
+    client_overrides: HashMap<Uuid, ClientOverrides>,
+    scene_thresholds: HashMap<SceneType, f32>,
+    mask_regions: Vec<ImageRegion>,
+    parallel: bool,
+    early_exit: Option<f32>,
+    smoothing: Option<Mutex<Smoothing>>,
+    detector_weights: HashMap<&'static str, f32>,
+    calibrations: HashMap<&'static str, ConfidenceCurve>,
+    detectors: Vec<Box<dyn SceneDetector>>,
+    disabled_detectors: Mutex<HashSet<&'static str>>,
+    battle_menu_cursor: BattleMenuCursorDetector,
+    overworld_kind: OverworldKindClassifier,
+    last_report: Mutex<Vec<(String, SceneType, f32)>>,
+    detector_timings: Mutex<HashMap<&'static str, DetectorTiming>>,
 }
 
 impl SceneAnalyzer {
     pub fn new() -> Self {
         Self {
             confidence_threshold: 0.8,
+            resolution: SceneResolution::default(),
+            client_overrides: HashMap::new(),
+            scene_thresholds: HashMap::new(),
+            mask_regions: Vec::new(),
+            parallel: false,
+            early_exit: None,
+            smoothing: None,
+            detector_weights: HashMap::new(),
+            calibrations: HashMap::new(),
+            detectors: GameRegistry::new().detectors_for(GameKind::Pokemon),
+            disabled_detectors: Mutex::new(HashSet::new()),
+            battle_menu_cursor: BattleMenuCursorDetector::new(),
+            overworld_kind: OverworldKindClassifier::new(),
+            last_report: Mutex::new(Vec::new()),
+            detector_timings: Mutex::new(HashMap::new()),
         }
     }
 
+    // The game's detectors from the GameRegistry, all classifying pixels with `thresholds`.
+    pub fn for_game(kind: GameKind, thresholds: ColorThresholds) -> Self {
+        let mut analyzer = Self::new().with_detectors(
+            GameRegistry::new()
+                .with_color_thresholds(thresholds)
+                .detectors_for(kind),
+        );
+        analyzer.battle_menu_cursor = analyzer
+            .battle_menu_cursor
+            .with_color_thresholds(thresholds);
+        analyzer.overworld_kind = analyzer.overworld_kind.with_color_thresholds(thresholds);
+        analyzer
+    }
+
+    // Replaces the scene detectors, e.g. with a game-specific set from the GameRegistry.
+    pub fn with_detectors(mut self, detectors: Vec<Box<dyn SceneDetector>>) -> Self {
+        self.detectors = detectors;
+        self
+    }
+
+    // Adds a detector of the caller's own, e.g. for a ROM hack's custom screens, next to the
+    // ones already installed.
+    pub fn with_scene_detector(mut self, detector: Box<dyn SceneDetector>) -> Self {
+        self.detectors.push(detector);
+        self
+    }
+
     pub fn with_confidence_threshold(mut self, threshold: f32) -> Self {
         self.confidence_threshold = threshold;
         self
     }
+
+    // Applies the configured threshold and smoothing, and disables every detector not listed
+    // as enabled. Listed names no detector answers to are logged and otherwise ignored.
+    pub fn with_settings(mut self, settings: &AnalyzerSettings) -> Self {
+        self.confidence_threshold = settings.confidence_threshold;
+        if let Some(smoothing) = settings.smoothing {
+            self = self.with_smoothing(smoothing.window, smoothing.switch_after);
+        }
+        if let Some(enabled) = &settings.enabled_detectors {
+            for name in enabled {
+                if !self
+                    .detectors
+                    .iter()
+                    .any(|detector| detector.name() == name)
+                {
+                    tracing::warn!("No scene detector named {} to enable", name);
+                }
+            }
+            if let Ok(disabled) = self.disabled_detectors.get_mut() {
+                disabled.extend(
+                    self.detectors
+                        .iter()
+                        .map(|detector| detector.name())
+                        .filter(|name| !enabled.iter().any(|enabled| enabled == name)),
+                );
+            }
+        }
+        self
+    }
+
+    pub fn with_resolution(mut self, resolution: SceneResolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    // Threshold for a single scene type, taking precedence over the analyzer-wide and
+    // per-client thresholds, e.g. to hold battles to a higher bar than the overworld.
+    pub fn with_scene_threshold(mut self, scene_type: SceneType, threshold: f32) -> Self {
+        self.scene_thresholds.insert(scene_type, threshold);
+        self
+    }
+
+    // Regions blacked out before detection, e.g. FPS counters or hotkey hints drawn by the emulator.
+    pub fn with_mask_regions(mut self, mask_regions: Vec<ImageRegion>) -> Self {
+        self.mask_regions = mask_regions;
+        self
+    }
+
+    // Runs the detectors on the rayon thread pool instead of one after another. Worth it with
+    // several expensive detectors on large frames, tiny ones cost less than handing them out.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    // Runs the detectors in priority order and stops at the first one reporting at least
+    // `min_confidence` that also clears its scene threshold, skipping the rest. Trades a little
+    // accuracy for speed, and only applies to sequential detection.
+    pub fn with_early_exit(mut self, min_confidence: f32) -> Self {
+        self.early_exit = Some(min_confidence);
+        self
+    }
+
+    // Smooths the scene per client over the last `window` frames, switching scenes only after
+    // a new one has dominated the window for `switch_after` consecutive frames.
+    pub fn with_smoothing(mut self, window: usize, switch_after: usize) -> Self {
+        self.smoothing = Some(Mutex::new(Smoothing {
+            smoother: SceneSmoother::new(window, switch_after),
+            last_agreeing: HashMap::new(),
+        }));
+        self
+    }
+
+    // Lets one client run with different settings than the rest, e.g. for side-by-side experiments.
+    pub fn with_client_overrides(mut self, client_id: Uuid, overrides: ClientOverrides) -> Self {
+        self.client_overrides.insert(client_id, overrides);
+        self
+    }
+
+    // Weight applied to a detector's confidence in WeightedVote mode, detectors default to 1.0.
+    pub fn with_detector_weight(mut self, detector: &'static str, weight: f32) -> Self {
+        self.detector_weights.insert(detector, weight);
+        self
+    }
+
+    // Remaps the named detector's raw confidence before selection and voting, e.g. to tone down
+    // a detector that reports 0.95 for frames it often gets wrong.
+    pub fn with_calibration(mut self, detector: &'static str, curve: ConfidenceCurve) -> Self {
+        self.calibrations.insert(detector, curve);
+        self
+    }
+
+    // Stops running the named detector until it is enabled again, e.g. to measure its impact on
+    // a live session. Returns false if no detector has that name.
+    pub fn disable_detector(&self, name: &str) -> bool {
+        let Some(detector) = self
+            .detectors
+            .iter()
+            .find(|detector| detector.name() == name)
+        else {
+            return false;
+        };
+        if let Ok(mut disabled) = self.disabled_detectors.lock() {
+            disabled.insert(detector.name());
+        }
+        true
+    }
+
+    // Returns false if no detector has that name.
+    pub fn enable_detector(&self, name: &str) -> bool {
+        if let Ok(mut disabled) = self.disabled_detectors.lock() {
+            disabled.remove(name);
+        }
+        self.detectors
+            .iter()
+            .any(|detector| detector.name() == name)
+    }
+
+    // Names of the detectors that currently run, in registration order.
+    pub fn enabled_detectors(&self) -> Vec<&'static str> {
+        self.active_detectors()
+            .iter()
+            .map(|detector| detector.name())
+            .collect()
+    }
+
+    fn active_detectors(&self) -> Vec<&dyn SceneDetector> {
+        let disabled = self
+            .disabled_detectors
+            .lock()
+            .map(|disabled| disabled.clone())
+            .unwrap_or_default();
+        self.detectors
+            .iter()
+            .map(|detector| detector.as_ref())
+            .filter(|detector| !disabled.contains(detector.name()))
+            .collect()
+    }
+
+    pub fn detect_best_scene(&self, image: &RgbImage) -> SceneAnalysis {
+        self.detect_with(image, ClientOverrides::default(), &CancellationToken::new())
+            .unwrap_or_else(|| SceneAnalysis::new(SceneType::Unknown, 0.0))
+    }
+
+    // Like detect_best_scene, but honours any overrides registered for the client.
+    pub fn detect_best_scene_for(&self, client_id: Uuid, image: &RgbImage) -> SceneAnalysis {
+        self.detect_best_scene_until(client_id, image, &CancellationToken::new())
+            .unwrap_or_else(|| SceneAnalysis::new(SceneType::Unknown, 0.0))
+    }
+
+    // Like detect_best_scene_for, but stops before the next detector once `cancel` fires and
+    // returns None. Detectors already running finish, their timings are still recorded.
+    pub fn detect_best_scene_until(
+        &self,
+        client_id: Uuid,
+        image: &RgbImage,
+        cancel: &CancellationToken,
+    ) -> Option<SceneAnalysis> {
+        let overrides = self
+            .client_overrides
+            .get(&client_id)
+            .copied()
+            .unwrap_or_default();
+        self.detect_with(image, overrides, cancel)
+    }
+
+    fn detect_with(
+        &self,
+        image: &RgbImage,
+        overrides: ClientOverrides,
+        cancel: &CancellationToken,
+    ) -> Option<SceneAnalysis> {
+        let threshold = overrides
+            .confidence_threshold
+            .unwrap_or(self.confidence_threshold);
+        let detectors = self.active_detectors();
+        let results: Vec<(&'static str, Option<SceneAnalysis>, Duration)> =
+            if self.parallel && detectors.len() > 1 {
+                detectors
+                    .par_iter()
+                    .filter(|_| !cancel.is_cancelled())
+                    .map(|detector| Self::run_detector(*detector, image))
+                    .collect()
+            } else if let Some(min_confidence) = self.early_exit {
+                self.run_until_confident(detectors, image, min_confidence, threshold, cancel)
+            } else {
+                detectors
+                    .iter()
+                    .take_while(|_| !cancel.is_cancelled())
+                    .map(|detector| Self::run_detector(*detector, image))
+                    .collect()
+            };
+
+        for (name, _, elapsed) in &results {
+            self.record_timing(name, *elapsed);
+        }
+        if cancel.is_cancelled() {
+            return None;
+        }
+
+        let mut detections = Vec::new();
+        for (name, detection, _) in results {
+            let Some(analysis) = detection else {
+                continue;
+            };
+            let analysis = self.calibrate(name, analysis);
+            tracing::debug!(
+                "Detector {} reported {:?} with confidence {:.2}",
+                name,
+                analysis.scene_type(),
+                analysis.confidence()
+            );
+            detections.push((name, analysis));
+        }
+        self.record_report(&detections);
+
+        let best = match overrides.resolution.unwrap_or(self.resolution) {
+            SceneResolution::Highest => self.highest_confidence(detections, threshold),
+            SceneResolution::WeightedVote => self.weighted_vote(detections, threshold),
+        };
+        Some(best.unwrap_or_else(|| SceneAnalysis::new(SceneType::Unknown, 0.0)))
+    }
+
+    fn run_until_confident(
+        &self,
+        mut detectors: Vec<&dyn SceneDetector>,
+        image: &RgbImage,
+        min_confidence: f32,
+        threshold: f32,
+        cancel: &CancellationToken,
+    ) -> Vec<(&'static str, Option<SceneAnalysis>, Duration)> {
+        detectors.sort_by_key(|detector| Reverse(detector.priority()));
+        let mut results = Vec::new();
+        for detector in detectors {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let result = Self::run_detector(detector, image);
+            let decisive = result.1.as_ref().is_some_and(|analysis| {
+                let confidence = self.calibrated_confidence(result.0, analysis.confidence());
+                confidence >= min_confidence
+                    && confidence >= self.threshold_for(analysis.scene_type(), threshold)
+            });
+            results.push(result);
+            if decisive {
+                break;
+            }
+        }
+        results
+    }
+
+    fn calibrated_confidence(&self, detector: &str, confidence: f32) -> f32 {
+        self.calibrations
+            .get(detector)
+            .map_or(confidence, |curve| curve.apply(confidence))
+    }
+
+    fn calibrate(&self, detector: &str, analysis: SceneAnalysis) -> SceneAnalysis {
+        let confidence = self.calibrated_confidence(detector, analysis.confidence());
+        analysis.with_confidence(confidence)
+    }
+
+    // A detector that panics is logged and counts as not having recognized anything, one
+    // broken detector shouldn't take the pipeline down with it.
+    fn run_detector(
+        detector: &dyn SceneDetector,
+        image: &RgbImage,
+    ) -> (&'static str, Option<SceneAnalysis>, Duration) {
+        let started = Instant::now();
+        let detection = panic::catch_unwind(AssertUnwindSafe(|| detector.detect(image)))
+            .unwrap_or_else(|_| {
+                tracing::error!("Scene detector {} panicked, skipping it", detector.name());
+                None
+            });
+        (detector.name(), detection, started.elapsed())
+    }
+
+    // Name, scene and confidence of every detector that fired during the most recent
+    // detect_best_scene call, for debugging misclassified frames.
+    pub fn last_detection_report(&self) -> Vec<(String, SceneType, f32)> {
+        self.last_report
+            .lock()
+            .map(|report| report.clone())
+            .unwrap_or_default()
+    }
+
+    // Cumulative time spent in each detector since the analyzer was created.
+    pub fn per_detector_timings(&self) -> HashMap<&'static str, DetectorTiming> {
+        self.detector_timings
+            .lock()
+            .map(|timings| timings.clone())
+            .unwrap_or_default()
+    }
+
+    // Runs every frame through the detectors `iters` times and returns the detector timings,
+    // slowest total first. Timings gathered before the benchmark are discarded.
+    pub fn run_benchmark(
+        &self,
+        frames: &[DynamicImage],
+        iters: usize,
+    ) -> Vec<(&'static str, DetectorTiming)> {
+        if let Ok(mut timings) = self.detector_timings.lock() {
+            timings.clear();
+        }
+        let frames: Vec<RgbImage> = frames.iter().map(DynamicImage::to_rgb8).collect();
+        for _ in 0..iters {
+            for frame in &frames {
+                self.detect_best_scene(frame);
+            }
+        }
+        let mut report: Vec<_> = self.per_detector_timings().into_iter().collect();
+        report.sort_by_key(|(_, timing)| Reverse(timing.total()));
+        report
+    }
+
+    fn record_timing(&self, detector: &'static str, duration: Duration) {
+        if let Ok(mut timings) = self.detector_timings.lock() {
+            timings.entry(detector).or_default().record(duration);
+        }
+    }
+
+    fn record_report(&self, detections: &[(&'static str, SceneAnalysis)]) {
+        if let Ok(mut report) = self.last_report.lock() {
+            *report = detections
+                .iter()
+                .map(|(name, analysis)| {
+                    (
+                        name.to_string(),
+                        analysis.scene_type(),
+                        analysis.confidence(),
+                    )
+                })
+                .collect();
+        }
+    }
+
+    fn threshold_for(&self, scene_type: SceneType, fallback: f32) -> f32 {
+        self.scene_thresholds
+            .get(&scene_type)
+            .copied()
+            .unwrap_or(fallback)
+    }
+
+    fn priority_of(&self, name: &str) -> u8 {
+        self.detectors
+            .iter()
+            .find(|detector| detector.name() == name)
+            .map_or(0, |detector| detector.priority())
+    }
+
+    // Picks the most confident detection that clears the threshold. Equal confidences go to
+    // the detector with the higher priority, and only equal priorities to the earlier detector,
+    // so adding a detector can't silently flip a tie.
+    fn highest_confidence(
+        &self,
+        detections: Vec<(&'static str, SceneAnalysis)>,
+        threshold: f32,
+    ) -> Option<SceneAnalysis> {
+        let mut best: Option<(u8, SceneAnalysis)> = None;
+        for (name, analysis) in detections {
+            if analysis.confidence() < self.threshold_for(analysis.scene_type(), threshold) {
+                continue;
+            }
+            let priority = self.priority_of(name);
+            if best.as_ref().is_none_or(|(best_priority, best)| {
+                analysis.confidence() > best.confidence()
+                    || (analysis.confidence() == best.confidence() && priority > *best_priority)
+            }) {
+                best = Some((priority, analysis));
+            }
+        }
+        best.map(|(_, analysis)| analysis)
+    }
+
+    // Sums weighted confidences per scene and picks the largest tally that clears the threshold.
+    // Equal tallies go to the scene whose highest priority voter outranks the other's, like in
+    // highest_confidence. The strongest detection of the winning scene is returned, so details
+    // like the party count survive, with the tally as its confidence.
+    fn weighted_vote(
+        &self,
+        detections: Vec<(&'static str, SceneAnalysis)>,
+        threshold: f32,
+    ) -> Option<SceneAnalysis> {
+        let mut tallies: Vec<(SceneType, f32, u8)> = Vec::new();
+        for (name, analysis) in &detections {
+            let weight = self.detector_weights.get(name).copied().unwrap_or(1.0);
+            let vote = analysis.confidence() * weight;
+            let priority = self.priority_of(name);
+            match tallies
+                .iter_mut()
+                .find(|(scene_type, _, _)| *scene_type == analysis.scene_type())
+            {
+                Some((_, tally, highest)) => {
+                    *tally += vote;
+                    *highest = (*highest).max(priority);
+                }
+                None => tallies.push((analysis.scene_type(), vote, priority)),
+            }
+        }
+
+        let (winner, tally, _) = tallies
+            .into_iter()
+            .filter(|(scene_type, tally, _)| *tally >= self.threshold_for(*scene_type, threshold))
+            .fold(
+                None,
+                |best: Option<(SceneType, f32, u8)>, candidate| match best {
+                    Some(best)
+                        if best.1 > candidate.1
+                            || (best.1 == candidate.1 && best.2 >= candidate.2) =>
+                    {
+                        Some(best)
+                    }
+                    _ => Some(candidate),
+                },
+            )?;
+        detections
+            .into_iter()
+            .map(|(_, analysis)| analysis)
+            .filter(|analysis| analysis.scene_type() == winner)
+            .max_by(|a, b| a.confidence().total_cmp(&b.confidence()))
+            .map(|analysis| analysis.with_confidence(tally.min(1.0)))
+    }
+}
+
+impl Default for SceneAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[async_trait]
 impl AnalyzerStep for SceneAnalyzer {
     async fn analyze(&self, ctx: &FrameContext<IngestedState>) -> Result<SceneAnalysis, AppError> {
-        Ok(SceneAnalysis::new(SceneType::Unknown, 0.0))
+        let mut image = ctx.frame().get_image().to_rgb8();
+        for region in &self.mask_regions {
+            region.mask(&mut image);
+        }
+        let client_id = ctx.frame().get_client_id();
+        let Some(mut analysis) =
+            self.detect_best_scene_until(client_id, &image, ctx.cancel_token())
+        else {
+            return Err(AppError::FrameSkipped(
+                "frame processing cancelled".to_string(),
+            ));
+        };
+        analysis = match analysis.scene_type() {
+            SceneType::Battle => {
+                let selection = self.battle_menu_cursor.detect(&image);
+                analysis.with_battle_menu_selection(selection)
+            }
+            SceneType::Overworld => {
+                let kind = self.overworld_kind.classify(&image);
+                analysis.with_overworld_kind(kind)
+            }
+            _ => analysis,
+        };
+        if let Some(smoothing) = &self.smoothing
+            && let Ok(mut smoothing) = smoothing.lock()
+        {
+            let smoothed = smoothing.smoother.smooth(client_id, analysis.scene_type());
+            if smoothed == analysis.scene_type() {
+                smoothing.last_agreeing.insert(client_id, analysis.clone());
+            } else {
+                let agreement = smoothing.smoother.agreement(client_id);
+                analysis = SceneAnalysis::new(smoothed, agreement);
+                if let Some(last) = smoothing
+                    .last_agreeing
+                    .get(&client_id)
+                    .filter(|last| last.scene_type() == smoothed)
+                {
+                    analysis = analysis
+                        .with_battle_menu_selection(last.battle_menu_selection())
+                        .with_pokemon_count(last.pokemon_count())
+                        .with_badges_earned(last.badges_earned())
+                        .with_overworld_kind(last.overworld_kind());
+                }
+            }
+        }
+        // Values the emulator read from RAM beat anything estimated from the pixels.
+        if let Some(state) = ctx.frame().get_emulator_state() {
+            if state.badges.is_some() {
+                analysis = analysis.with_badges_earned(state.badges);
+            }
+            if state.party_size.is_some() {
+                analysis = analysis.with_pokemon_count(state.party_size);
+            }
+        }
+        Ok(analysis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use image::Rgb;
+
+    use super::*;
+    use crate::common::EmulatorState;
+    use crate::common::frame::Frame;
+    use crate::pipeline::detection::cave_scene_detector::CaveSceneDetector;
+    use crate::pipeline::detection::fade_screen_detector::FadeScreenDetector;
+    use crate::pipeline::detection::image_stats::edge_density;
+    use crate::pipeline::detection::synthetic_frames::SyntheticFrame;
+    use crate::pipeline::detection::title_screen_detector::TitleScreenDetector;
+    use crate::pipeline::domain::scene_analysis::OverworldKind;
+
+    struct FixedDetector {
+        name: &'static str,
+        scene_type: SceneType,
+        confidence: f32,
+    }
+
+    impl SceneDetector for FixedDetector {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn detect(&self, _image: &RgbImage) -> Option<SceneAnalysis> {
+            Some(SceneAnalysis::new(self.scene_type, self.confidence))
+        }
+    }
+
+    struct PrioritizedDetector(FixedDetector, u8);
+
+    impl SceneDetector for PrioritizedDetector {
+        fn name(&self) -> &'static str {
+            self.0.name()
+        }
+
+        fn priority(&self) -> u8 {
+            self.1
+        }
+
+        fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+            self.0.detect(image)
+        }
+    }
+
+    fn analyzer_with(detectors: Vec<(&'static str, SceneType, f32)>) -> SceneAnalyzer {
+        SceneAnalyzer::new().with_detectors(
+            detectors
+                .into_iter()
+                .map(|(name, scene_type, confidence)| {
+                    Box::new(FixedDetector {
+                        name,
+                        scene_type,
+                        confidence,
+                    }) as Box<dyn SceneDetector>
+                })
+                .collect(),
+        )
+    }
+
+    // Reports a menu whenever the top-left corner is busy, like a text detector tripped by an overlay.
+    struct CornerTextDetector;
+
+    impl SceneDetector for CornerTextDetector {
+        fn name(&self) -> &'static str {
+            "corner_text"
+        }
+
+        fn detect(&self, image: &RgbImage) -> Option<SceneAnalysis> {
+            (edge_density(image, 0..32, 0..16) > 0.1)
+                .then(|| SceneAnalysis::new(SceneType::Menu, 0.9))
+        }
+    }
+
+    // Cancels the frame it looks at, like a newer frame arriving halfway through analysis.
+    struct CancellingDetector(CancellationToken);
+
+    impl SceneDetector for CancellingDetector {
+        fn name(&self) -> &'static str {
+            "canceller"
+        }
+
+        fn detect(&self, _image: &RgbImage) -> Option<SceneAnalysis> {
+            self.0.cancel();
+            None
+        }
+    }
+
+    fn frame() -> RgbImage {
+        RgbImage::from_pixel(16, 16, Rgb([0, 0, 0]))
+    }
+
+    fn agreeing_battle_detectors() -> SceneAnalyzer {
+        analyzer_with(vec![
+            ("hp_bar", SceneType::Battle, 0.5),
+            ("battle_menu", SceneType::Battle, 0.5),
+            ("menu", SceneType::Menu, 0.85),
+        ])
+    }
+
+    #[test]
+    fn highest_mode_takes_the_single_strongest_detector() {
+        let analysis = agreeing_battle_detectors().detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+    }
+
+    #[test]
+    fn weighted_vote_lets_agreeing_detectors_win() {
+        let analysis = agreeing_battle_detectors()
+            .with_resolution(SceneResolution::WeightedVote)
+            .detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Battle);
+    }
+
+    #[test]
+    fn calibration_can_tip_a_borderline_vote() {
+        let analysis = agreeing_battle_detectors()
+            .with_resolution(SceneResolution::WeightedVote)
+            .with_calibration("hp_bar", ConfidenceCurve::scaled(0.5))
+            .detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+    }
+
+    #[test]
+    fn weighted_vote_respects_detector_weights() {
+        let analysis = agreeing_battle_detectors()
+            .with_resolution(SceneResolution::WeightedVote)
+            .with_detector_weight("battle_menu", 0.5)
+            .detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+    }
+
+    #[tokio::test]
+    async fn analyze_populates_the_detection_report() {
+        let analyzer = agreeing_battle_detectors();
+        assert!(analyzer.last_detection_report().is_empty());
+
+        let frame = Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(frame()),
+            Utc::now(),
+            Uuid::new_v4(),
+        );
+        analyzer.analyze(&FrameContext::new(frame)).await.unwrap();
+
+        let report = analyzer.last_detection_report();
+        assert_eq!(report.len(), 3);
+        assert!(report.contains(&("menu".to_string(), SceneType::Menu, 0.85)));
+    }
+
+    #[test]
+    fn client_overrides_apply_only_to_their_client() {
+        let lenient = Uuid::new_v4();
+        let analyzer = analyzer_with(vec![("battle", SceneType::Battle, 0.7)])
+            .with_client_overrides(
+                lenient,
+                ClientOverrides {
+                    confidence_threshold: Some(0.6),
+                    ..Default::default()
+                },
+            );
+
+        assert_eq!(
+            analyzer
+                .detect_best_scene_for(lenient, &frame())
+                .scene_type(),
+            SceneType::Battle
+        );
+        assert_eq!(
+            analyzer
+                .detect_best_scene_for(Uuid::new_v4(), &frame())
+                .scene_type(),
+            SceneType::Unknown
+        );
+    }
+
+    #[test]
+    fn scene_thresholds_change_the_winner() {
+        let detectors = || {
+            analyzer_with(vec![
+                ("battle", SceneType::Battle, 0.9),
+                ("overworld", SceneType::Overworld, 0.7),
+            ])
+            .with_confidence_threshold(0.6)
+        };
+        assert_eq!(
+            detectors().detect_best_scene(&frame()).scene_type(),
+            SceneType::Battle
+        );
+
+        let strict_battles = detectors().with_scene_threshold(SceneType::Battle, 0.95);
+        assert_eq!(
+            strict_battles.detect_best_scene(&frame()).scene_type(),
+            SceneType::Overworld
+        );
+    }
+
+    #[tokio::test]
+    async fn masked_overlays_are_ignored() {
+        let image = RgbImage::from_fn(64, 48, |x, y| {
+            if x < 32 && y < 16 && (x + y) % 2 == 0 {
+                Rgb([255, 255, 255])
+            } else {
+                Rgb([0, 0, 0])
+            }
+        });
+        let context = || {
+            FrameContext::new(Frame::new(
+                Uuid::new_v4(),
+                DynamicImage::ImageRgb8(image.clone()),
+                Utc::now(),
+                Uuid::new_v4(),
+            ))
+        };
+
+        let unmasked = SceneAnalyzer::new().with_detectors(vec![Box::new(CornerTextDetector)]);
+        let analysis = unmasked.analyze(&context()).await.unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+
+        let masked = SceneAnalyzer::new()
+            .with_detectors(vec![Box::new(CornerTextDetector)])
+            .with_mask_regions(vec![ImageRegion::new(0, 0, 40, 20)]);
+        let analysis = masked.analyze(&context()).await.unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Unknown);
+    }
+
+    #[tokio::test]
+    async fn grass_frames_are_outdoor_overworld() {
+        let route = SyntheticFrame::blank(256, 384, Rgb([96, 200, 88])).build();
+        let context = FrameContext::new(Frame::new(
+            Uuid::new_v4(),
+            DynamicImage::ImageRgb8(route),
+            Utc::now(),
+            Uuid::new_v4(),
+        ));
+        let analysis = SceneAnalyzer::for_game(GameKind::Pokemon, ColorThresholds::default())
+            .analyze(&context)
+            .await
+            .unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::Overworld);
+        assert_eq!(analysis.overworld_kind(), Some(OverworldKind::Outdoor));
+
+        // With everything below this luma counted as unlit rock, the same grass is no route.
+        let unlit = ColorThresholds {
+            rock_luma: 200,
+            ..ColorThresholds::default()
+        };
+        let analysis = SceneAnalyzer::for_game(GameKind::Pokemon, unlit)
+            .analyze(&context)
+            .await
+            .unwrap();
+        assert_ne!(analysis.scene_type(), SceneType::Overworld);
+    }
+
+    #[tokio::test]
+    async fn emulator_reported_state_overrides_pixel_estimates() {
+        let card = SyntheticFrame::trainer_card(2).build();
+        let frame = || {
+            Frame::new(
+                Uuid::new_v4(),
+                DynamicImage::ImageRgb8(card.clone()),
+                Utc::now(),
+                Uuid::new_v4(),
+            )
+        };
+        let analyzer = SceneAnalyzer::new();
+
+        let estimated = analyzer.analyze(&FrameContext::new(frame())).await.unwrap();
+        assert_eq!(estimated.badges_earned(), Some(2));
+
+        let reported = frame().with_emulator_state(EmulatorState {
+            badges: Some(5),
+            ..EmulatorState::default()
+        });
+        let analysis = analyzer
+            .analyze(&FrameContext::new(reported))
+            .await
+            .unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::TrainerCard);
+        assert_eq!(analysis.badges_earned(), Some(5));
+        assert_eq!(analysis.pokemon_count(), None);
+    }
+
+    #[tokio::test]
+    async fn smoothed_scenes_keep_the_details_of_their_last_frame() {
+        let client_id = Uuid::new_v4();
+        let frame = |image: RgbImage| {
+            FrameContext::new(Frame::new(
+                client_id,
+                DynamicImage::ImageRgb8(image),
+                Utc::now(),
+                Uuid::new_v4(),
+            ))
+        };
+        let analyzer = SceneAnalyzer::new().with_settings(&AnalyzerSettings {
+            smoothing: Some(SmoothingSettings {
+                window: 3,
+                switch_after: 2,
+            }),
+            ..AnalyzerSettings::default()
+        });
+        for _ in 0..2 {
+            analyzer
+                .analyze(&frame(SyntheticFrame::trainer_card(2).build()))
+                .await
+                .unwrap();
+        }
+
+        // A single black frame is smoothed away and the badges read before it are kept.
+        let flicker = RgbImage::new(256, 192);
+        let analysis = analyzer.analyze(&frame(flicker)).await.unwrap();
+        assert_eq!(analysis.scene_type(), SceneType::TrainerCard);
+        assert_eq!(analysis.badges_earned(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_before_the_remaining_detectors() {
+        for early_exit in [false, true] {
+            let cancel = CancellationToken::new();
+            let mut analyzer = SceneAnalyzer::new().with_detectors(vec![
+                Box::new(FixedDetector {
+                    name: "hp_bar",
+                    scene_type: SceneType::Battle,
+                    confidence: 0.5,
+                }),
+                Box::new(CancellingDetector(cancel.clone())),
+                Box::new(FixedDetector {
+                    name: "menu",
+                    scene_type: SceneType::Menu,
+                    confidence: 0.85,
+                }),
+            ]);
+            if early_exit {
+                analyzer = analyzer.with_early_exit(0.95);
+            }
+            let mut ctx = FrameContext::new(Frame::new(
+                Uuid::new_v4(),
+                DynamicImage::ImageRgb8(frame()),
+                Utc::now(),
+                Uuid::new_v4(),
+            ));
+            ctx.set_cancel_token(cancel);
+
+            let result = analyzer.analyze(&ctx).await;
+            assert!(matches!(result, Err(AppError::FrameSkipped(_))));
+            let timings = analyzer.per_detector_timings();
+            assert!(timings.contains_key("hp_bar"));
+            assert!(timings.contains_key("canceller"));
+            assert!(!timings.contains_key("menu"));
+            assert!(analyzer.last_detection_report().is_empty());
+        }
+    }
+
+    #[test]
+    fn benchmark_reports_timings_for_every_detector() {
+        let analyzer = SceneAnalyzer::new().with_detectors(vec![
+            Box::new(TitleScreenDetector::new()),
+            Box::new(CaveSceneDetector::new()),
+        ]);
+        let frames = vec![DynamicImage::ImageRgb8(RgbImage::from_pixel(
+            256,
+            192,
+            Rgb([40, 40, 40]),
+        ))];
+
+        let report = analyzer.run_benchmark(&frames, 3);
+        assert_eq!(report.len(), 2);
+        for (name, timing) in &report {
+            assert!(["title_screen", "cave"].contains(name));
+            assert_eq!(timing.count(), 3);
+            assert!(timing.total() > Duration::ZERO);
+            assert!(timing.max() >= timing.avg());
+        }
+        assert!(report[0].1.total() >= report[1].1.total());
+    }
+
+    #[test]
+    fn disabled_detectors_stop_reporting_until_enabled() {
+        let analyzer = agreeing_battle_detectors().with_resolution(SceneResolution::WeightedVote);
+        assert_eq!(
+            analyzer.detect_best_scene(&frame()).scene_type(),
+            SceneType::Battle
+        );
+
+        assert!(analyzer.disable_detector("hp_bar"));
+        assert!(!analyzer.disable_detector("missing"));
+        assert_eq!(analyzer.enabled_detectors(), vec!["battle_menu", "menu"]);
+        assert_eq!(
+            analyzer.detect_best_scene(&frame()).scene_type(),
+            SceneType::Menu
+        );
+        assert!(
+            analyzer
+                .last_detection_report()
+                .iter()
+                .all(|(name, _, _)| name != "hp_bar")
+        );
+
+        assert!(analyzer.enable_detector("hp_bar"));
+        assert_eq!(
+            analyzer.detect_best_scene(&frame()).scene_type(),
+            SceneType::Battle
+        );
+    }
+
+    #[test]
+    fn settings_pick_the_threshold_and_the_detectors_that_run() {
+        let analyzer = agreeing_battle_detectors().with_settings(&AnalyzerSettings {
+            confidence_threshold: 0.3,
+            enabled_detectors: Some(vec!["menu".to_string(), "missing".to_string()]),
+            smoothing: None,
+        });
+        assert_eq!(analyzer.enabled_detectors(), vec!["menu"]);
+        let analysis = analyzer.detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+
+        let strict = agreeing_battle_detectors().with_settings(&AnalyzerSettings {
+            confidence_threshold: 0.99,
+            enabled_detectors: None,
+            smoothing: None,
+        });
+        assert_eq!(strict.enabled_detectors().len(), 3);
+        assert_eq!(
+            strict.detect_best_scene(&frame()).scene_type(),
+            SceneType::Unknown
+        );
+    }
+
+    #[test]
+    fn early_exit_skips_lower_priority_detectors() {
+        let analyzer = SceneAnalyzer::new()
+            .with_detectors(vec![
+                Box::new(CaveSceneDetector::new()),
+                Box::new(FadeScreenDetector::new()),
+            ])
+            .with_early_exit(0.85);
+
+        let analysis = analyzer.detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::FadeTransition);
+        let timings = analyzer.per_detector_timings();
+        assert!(timings.contains_key("fade_screen"));
+        assert!(!timings.contains_key("cave"));
+    }
+
+    #[test]
+    fn equal_confidences_go_to_the_higher_priority() {
+        let fixed = |name, scene_type, priority| {
+            Box::new(PrioritizedDetector(
+                FixedDetector {
+                    name,
+                    scene_type,
+                    confidence: 0.85,
+                },
+                priority,
+            )) as Box<dyn SceneDetector>
+        };
+        let detectors = || {
+            vec![
+                fixed("menu", SceneType::Menu, 40),
+                fixed("party", SceneType::PartyMenu, 80),
+                fixed("card", SceneType::TrainerCard, 80),
+            ]
+        };
+
+        for resolution in [SceneResolution::Highest, SceneResolution::WeightedVote] {
+            let analyzer = SceneAnalyzer::new()
+                .with_detectors(detectors())
+                .with_resolution(resolution);
+            let analysis = analyzer.detect_best_scene(&frame());
+            assert_eq!(analysis.scene_type(), SceneType::PartyMenu);
+        }
+    }
+
+    #[test]
+    fn weak_signals_do_not_exit_early() {
+        let analyzer = analyzer_with(vec![
+            ("hp_bar", SceneType::Battle, 0.6),
+            ("menu", SceneType::Menu, 0.85),
+        ])
+        .with_confidence_threshold(0.5)
+        .with_early_exit(0.8);
+
+        let analysis = analyzer.detect_best_scene(&frame());
+        assert_eq!(analysis.scene_type(), SceneType::Menu);
+        assert_eq!(analyzer.per_detector_timings().len(), 2);
+    }
+
+    #[test]
+    fn parallel_and_sequential_detection_agree() {
+        let detectors = || -> Vec<Box<dyn SceneDetector>> {
+            vec![
+                Box::new(TitleScreenDetector::new()),
+                Box::new(CaveSceneDetector::new()),
+                Box::new(FixedDetector {
+                    name: "menu",
+                    scene_type: SceneType::Menu,
+                    confidence: 0.85,
+                }),
+                Box::new(CornerTextDetector),
+            ]
+        };
+        let image = RgbImage::from_fn(256, 192, |x, y| {
+            if (x / 8 + y / 8) % 2 == 0 {
+                Rgb([10, 10, 10])
+            } else {
+                Rgb([200, 180, 40])
+            }
+        });
+
+        let sequential = SceneAnalyzer::new().with_detectors(detectors());
+        let parallel = SceneAnalyzer::new()
+            .with_detectors(detectors())
+            .with_parallel(true);
+        let sequential_best = sequential.detect_best_scene(&image);
+        let parallel_best = parallel.detect_best_scene(&image);
+
+        assert_eq!(
+            sequential.last_detection_report(),
+            parallel.last_detection_report()
+        );
+        assert_eq!(sequential_best.scene_type(), parallel_best.scene_type());
+        assert_eq!(sequential_best.confidence(), parallel_best.confidence());
+        assert_eq!(parallel.per_detector_timings().len(), 4);
+    }
+
+    struct PanickingDetector;
+
+    impl SceneDetector for PanickingDetector {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn detect(&self, _image: &RgbImage) -> Option<SceneAnalysis> {
+            panic!("detector bug")
+        }
+    }
+
+    #[test]
+    fn panicking_detectors_are_skipped() {
+        for parallel in [false, true] {
+            let analyzer = analyzer_with(vec![("menu", SceneType::Menu, 0.9)])
+                .with_scene_detector(Box::new(PanickingDetector))
+                .with_parallel(parallel);
+            let analysis = analyzer.detect_best_scene(&frame());
+            assert_eq!(analysis.scene_type(), SceneType::Menu);
+            assert!(analyzer.per_detector_timings().contains_key("broken"));
+        }
+    }
+
+    #[test]
+    fn added_detectors_take_part_next_to_the_built_in_ones() {
+        let analyzer = SceneAnalyzer::new().with_scene_detector(Box::new(FixedDetector {
+            name: "rom_hack_menu",
+            scene_type: SceneType::Menu,
+            confidence: 0.99,
+        }));
+        let cave = RgbImage::from_fn(256, 192, |x, y| {
+            if (40..216).contains(&x) && (40..152).contains(&y) {
+                Rgb([110, 90, 70])
+            } else {
+                Rgb([12, 10, 8])
+            }
+        });
+
+        assert_eq!(
+            analyzer.detect_best_scene(&cave).scene_type(),
+            SceneType::Menu
+        );
+        let reported: Vec<String> = analyzer
+            .last_detection_report()
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        assert!(reported.contains(&"rom_hack_menu".to_string()));
+        assert!(reported.contains(&"cave".to_string()));
     }
 }
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::common::{ButtonPress, ButtonSet, GameAction};
+use crate::emulator::emulator_client::EmulatorControl;
+use crate::pipeline::detection::image_stats::luma_thumbnail;
+use crate::pipeline::domain::scene_analysis::SceneType;
+
+// How hard the watchdog is trying to get a client unstuck, in escalation order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escalation {
+    // Cycle through the directions in case the client walked into a wall.
+    AlternateDirections,
+    // Alternate Start and B to open or back out of menus and dialogs.
+    StartOrB,
+    // Press the soft-reset combination (L+R+Start+Select), only when enabled.
+    SoftReset,
+}
+
+// What the watchdog currently knows about a client, for debugging.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogState {
+    pub scene_type: SceneType,
+    pub unchanged_for: Duration,
+    pub escalation: Option<Escalation>,
+}
+
+struct ClientWatch {
+    scene_type: SceneType,
+    signature: u64,
+    since: Instant,
+    last_nudge: Option<Instant>,
+    nudges: usize,
+    soft_resets: u32,
+}
+
+// Watches for clients whose scene and image stay the same for too long and escalates
+// through increasingly drastic inputs. Each escalation step lasts one scene timeout.
+pub struct StuckWatchdog {
+    default_timeout: Duration,
+    scene_timeouts: HashMap<SceneType, Duration>,
+    nudge_interval: Duration,
+    soft_reset: bool,
+    emulator_reset: Option<(u32, EmulatorControl)>,
+    signature_resolution: u32,
+    clients: HashMap<Uuid, ClientWatch>,
+}
+
+impl StuckWatchdog {
+    const DIRECTIONS: [GameAction; 4] = [
+        GameAction::Up,
+        GameAction::Right,
+        GameAction::Down,
+        GameAction::Left,
+    ];
+
+    pub fn new(default_timeout: Duration) -> Self {
+        Self {
+            default_timeout,
+            scene_timeouts: HashMap::new(),
+            nudge_interval: Duration::from_millis(500),
+            soft_reset: false,
+            emulator_reset: None,
+            signature_resolution: 8,
+            clients: HashMap::new(),
+        }
+    }
+
+    // Time a scene may stay unchanged before escalating, e.g. longer for cutscenes.
+    pub fn with_scene_timeout(mut self, scene_type: SceneType, timeout: Duration) -> Self {
+        self.scene_timeouts.insert(scene_type, timeout);
+        self
+    }
+
+    // Side of the thumbnail frames are fingerprinted on. Higher resolutions notice smaller
+    // changes like a blinking cursor, so fewer frames look unchanged, at a higher cost per frame.
+    pub fn with_signature_resolution(mut self, resolution: u32) -> Self {
+        self.signature_resolution = resolution.max(1);
+        self
+    }
+
+    // Fingerprint of a frame at this watchdog's resolution, for `observe`.
+    pub fn signature(&self, image: &DynamicImage) -> u64 {
+        image_signature(image, self.signature_resolution)
+    }
+
+    // Minimum time between two inputs sent while stuck.
+    pub fn with_nudge_interval(mut self, nudge_interval: Duration) -> Self {
+        self.nudge_interval = nudge_interval;
+        self
+    }
+
+    pub fn with_soft_reset(mut self, soft_reset: bool) -> Self {
+        self.soft_reset = soft_reset;
+        self
+    }
+
+    // Last resort once `after_soft_resets` soft resets in a row left the client stuck: resets
+    // the emulator through `control` and forgets the client, which starts over like a new one.
+    // Enables soft resets.
+    pub fn with_emulator_reset(mut self, after_soft_resets: u32, control: EmulatorControl) -> Self {
+        self.soft_reset = true;
+        self.emulator_reset = Some((after_soft_resets, control));
+        self
+    }
+
+    // Records the latest frame of a client and returns the input to send if it is stuck.
+    pub fn observe(
+        &mut self,
+        client_id: Uuid,
+        scene_type: SceneType,
+        signature: u64,
+        now: Instant,
+    ) -> Option<ButtonPress> {
+        let watch = self.clients.entry(client_id).or_insert(ClientWatch {
+            scene_type,
+            signature,
+            since: now,
+            last_nudge: None,
+            nudges: 0,
+            soft_resets: 0,
+        });
+        // A fade is the game moving on by itself and darkens or brightens on every frame, so
+        // those changes don't restart the timer. Until the timeout inputs would land in the next
+        // scene and none are sent, a fade lasting longer is a black or white hang like any other.
+        let fading = scene_type == SceneType::FadeTransition;
+        if watch.scene_type != scene_type || (watch.signature != signature && !fading) {
+            *watch = ClientWatch {
+                scene_type,
+                signature,
+                since: now,
+                last_nudge: None,
+                nudges: 0,
+                soft_resets: 0,
+            };
+            return None;
+        }
+
+        let timeout = Self::timeout(&self.scene_timeouts, self.default_timeout, scene_type);
+        let escalation = Self::escalation(timeout, self.soft_reset, now - watch.since)?;
+        if watch
+            .last_nudge
+            .is_some_and(|last| now - last < self.nudge_interval)
+        {
+            return None;
+        }
+        watch.last_nudge = Some(now);
+        watch.nudges += 1;
+
+        if escalation == Escalation::SoftReset
+            && let Some((after_soft_resets, control)) = &self.emulator_reset
+            && watch.soft_resets >= *after_soft_resets
+        {
+            tracing::error!(
+                "Client {} still stuck on {:?} after {} soft resets, resetting the emulator",
+                client_id,
+                scene_type,
+                watch.soft_resets
+            );
+            control.request_reset();
+            self.clients.remove(&client_id);
+            return None;
+        }
+
+        let buttons = match escalation {
+            Escalation::AlternateDirections => {
+                ButtonSet::from(Self::DIRECTIONS[(watch.nudges - 1) % Self::DIRECTIONS.len()])
+            }
+            Escalation::StartOrB if watch.nudges % 2 == 1 => ButtonSet::from(GameAction::Start),
+            Escalation::StartOrB => ButtonSet::from(GameAction::B),
+            Escalation::SoftReset => {
+                // Start over once the reset is sent, the game needs time to come back.
+                watch.since = now;
+                watch.nudges = 0;
+                watch.soft_resets += 1;
+                [
+                    GameAction::L,
+                    GameAction::R,
+                    GameAction::Start,
+                    GameAction::Select,
+                ]
+                .into_iter()
+                .collect()
+            }
+        };
+        tracing::warn!(
+            "Client {} stuck on {:?}, escalating with {:?}: {:?}",
+            client_id,
+            scene_type,
+            escalation,
+            buttons
+        );
+        Some(ButtonPress::tap(buttons))
+    }
+
+    pub fn state(&self, client_id: Uuid, now: Instant) -> Option<WatchdogState> {
+        let watch = self.clients.get(&client_id)?;
+        let timeout = Self::timeout(&self.scene_timeouts, self.default_timeout, watch.scene_type);
+        let unchanged_for = now.saturating_duration_since(watch.since);
+        Some(WatchdogState {
+            scene_type: watch.scene_type,
+            unchanged_for,
+            escalation: Self::escalation(timeout, self.soft_reset, unchanged_for),
+        })
+    }
+
+    fn timeout(
+        scene_timeouts: &HashMap<SceneType, Duration>,
+        default_timeout: Duration,
+        scene_type: SceneType,
+    ) -> Duration {
+        scene_timeouts
+            .get(&scene_type)
+            .copied()
+            .unwrap_or(default_timeout)
+    }
+
+    fn escalation(timeout: Duration, soft_reset: bool, stuck_for: Duration) -> Option<Escalation> {
+        if timeout.is_zero() || stuck_for < timeout {
+            return None;
+        }
+        Some(match stuck_for.as_secs_f64() / timeout.as_secs_f64() {
+            steps if steps < 2.0 => Escalation::AlternateDirections,
+            steps if steps < 3.0 || !soft_reset => Escalation::StartOrB,
+            _ => Escalation::SoftReset,
+        })
+    }
+}
+
+// Coarse fingerprint of an image on a `resolution` x `resolution` thumbnail that ignores noise,
+// two frames with the same signature look the same to the watchdog.
+pub fn image_signature(image: &DynamicImage, resolution: u32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for luma in luma_thumbnail(image, resolution) {
+        (luma / 16).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn watchdog() -> StuckWatchdog {
+        StuckWatchdog::new(TIMEOUT)
+            .with_nudge_interval(Duration::ZERO)
+            .with_soft_reset(true)
+    }
+
+    #[test]
+    fn escalates_in_order_as_the_client_stays_stuck() {
+        let mut watchdog = watchdog();
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert_eq!(
+            watchdog.observe(client, SceneType::Overworld, 1, at(0)),
+            None
+        );
+        assert_eq!(
+            watchdog.observe(client, SceneType::Overworld, 1, at(5)),
+            None
+        );
+
+        let first = watchdog.observe(client, SceneType::Overworld, 1, at(10));
+        assert_eq!(first, Some(ButtonPress::tap(GameAction::Up)));
+        let second = watchdog.observe(client, SceneType::Overworld, 1, at(11));
+        assert_eq!(second, Some(ButtonPress::tap(GameAction::Right)));
+        assert_eq!(
+            watchdog.state(client, at(11)).unwrap().escalation,
+            Some(Escalation::AlternateDirections)
+        );
+
+        let start_or_b = watchdog.observe(client, SceneType::Overworld, 1, at(20));
+        assert!(matches!(
+            start_or_b.map(|press| press.buttons()),
+            Some(buttons) if buttons == ButtonSet::from(GameAction::Start)
+                || buttons == ButtonSet::from(GameAction::B)
+        ));
+
+        let reset = watchdog
+            .observe(client, SceneType::Overworld, 1, at(30))
+            .unwrap();
+        assert!(reset.buttons().contains(GameAction::Select));
+        assert!(reset.buttons().contains(GameAction::L));
+        assert_eq!(watchdog.state(client, at(30)).unwrap().escalation, None);
+    }
+
+    #[test]
+    fn changes_reset_the_timer() {
+        let mut watchdog = watchdog();
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        watchdog.observe(client, SceneType::Overworld, 1, start);
+        assert!(
+            watchdog
+                .observe(client, SceneType::Overworld, 2, start + TIMEOUT)
+                .is_none()
+        );
+        assert!(
+            watchdog
+                .observe(client, SceneType::Battle, 2, start + TIMEOUT * 2)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn scene_timeouts_override_the_default() {
+        let mut watchdog = watchdog().with_scene_timeout(SceneType::Cutscene, TIMEOUT * 3);
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        watchdog.observe(client, SceneType::Cutscene, 1, start);
+        assert!(
+            watchdog
+                .observe(client, SceneType::Cutscene, 1, start + TIMEOUT)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn emulator_reset_follows_failed_soft_resets() {
+        let control = EmulatorControl::new();
+        let mut watchdog = StuckWatchdog::new(TIMEOUT)
+            .with_nudge_interval(Duration::ZERO)
+            .with_emulator_reset(1, control.clone());
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        watchdog.observe(client, SceneType::Overworld, 1, at(0));
+        let soft_reset = watchdog
+            .observe(client, SceneType::Overworld, 1, at(30))
+            .unwrap();
+        assert!(soft_reset.buttons().contains(GameAction::Start));
+        assert!(!control.take_reset());
+
+        // The soft reset restarted the timer, the next SoftReset step resets the emulator.
+        assert!(
+            watchdog
+                .observe(client, SceneType::Overworld, 1, at(60))
+                .is_none()
+        );
+        assert!(control.take_reset());
+        assert!(watchdog.state(client, at(60)).is_none());
+
+        // The client starts over from scratch.
+        assert!(
+            watchdog
+                .observe(client, SceneType::Overworld, 1, at(61))
+                .is_none()
+        );
+        assert_eq!(
+            watchdog.state(client, at(61)).unwrap().unchanged_for,
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn fades_only_escalate_once_they_outlast_the_timeout() {
+        let mut watchdog = watchdog();
+        let client = Uuid::new_v4();
+        let start = Instant::now();
+        // Darkening frames within the timeout are left alone.
+        for step in 0..10 {
+            let at = start + TIMEOUT * step / 10;
+            assert!(
+                watchdog
+                    .observe(client, SceneType::FadeTransition, step as u64, at)
+                    .is_none()
+            );
+        }
+
+        // A screen that stays black escalates like any other hang.
+        assert_eq!(
+            watchdog.observe(client, SceneType::FadeTransition, 9, start + TIMEOUT),
+            Some(ButtonPress::tap(GameAction::Up))
+        );
+        let reset = watchdog
+            .observe(client, SceneType::FadeTransition, 9, start + TIMEOUT * 3)
+            .unwrap();
+        assert!(reset.buttons().contains(GameAction::Select));
+    }
+
+    #[test]
+    fn small_changes_only_show_at_higher_signature_resolutions() {
+        let dark = DynamicImage::ImageRgb8(image::RgbImage::new(256, 192));
+        let mut cursor = dark.to_rgb8();
+        for y in 100..104 {
+            for x in 100..104 {
+                cursor.put_pixel(x, y, image::Rgb([255, 255, 255]));
+            }
+        }
+        let cursor = DynamicImage::ImageRgb8(cursor);
+
+        let coarse = StuckWatchdog::new(TIMEOUT);
+        assert_eq!(coarse.signature(&dark), coarse.signature(&cursor));
+        let fine = StuckWatchdog::new(TIMEOUT).with_signature_resolution(32);
+        assert_ne!(fine.signature(&dark), fine.signature(&cursor));
+    }
+}